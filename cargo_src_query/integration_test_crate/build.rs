@@ -0,0 +1,13 @@
+// build.rs
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("generated.rs"), "
+        pub struct GeneratedExampleStruct {}
+    ").unwrap();
+    println!("cargo:rerun-if-changed=build.rs");
+}
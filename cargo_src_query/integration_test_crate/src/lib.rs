@@ -2,6 +2,10 @@ use serde::{Deserialize, Serialize};
 
 mod nested_directory;
 
+pub use nested_in_lib::GenericExampleStruct as ReexportedExampleStruct;
+#[allow(unused_imports)]
+use nested_in_lib::public_example_function;
+
 pub struct PublicExampleStruct {}
 
 #[derive(Clone, Copy)]
@@ -16,7 +20,15 @@ pub mod nested_in_lib {
 
     pub fn public_example_function() {}
 
+    pub struct GenericExampleStruct<'a, A, B = u32> {
+        pub first: &'a A,
+        // Regular comments are only kept in verbatim source text
+        pub second: Vec<B>,
+    }
+
     mod deeply_nested {
         pub struct PublicExampleStruct {}
     }
+
+    include!(concat!(env!("OUT_DIR"), "/generated.rs"));
 }
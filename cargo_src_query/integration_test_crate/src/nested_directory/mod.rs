@@ -1,3 +1,32 @@
 mod another_file;
 
-pub struct ExampleStruct {}
\ No newline at end of file
+pub struct ExampleStruct {}
+
+pub enum ExampleEnum {
+    First,
+    Second(u32),
+}
+
+pub trait ExampleTrait {
+    type Output;
+
+    fn example(&self) -> Self::Output;
+}
+
+impl ExampleStruct {
+    pub const LIMIT: usize = 8;
+}
+
+impl ExampleTrait for ExampleStruct {
+    type Output = u32;
+
+    fn example(&self) -> u32 { Self::LIMIT as u32 }
+}
+
+pub type ExampleAlias<T> = crate::ReexportedExampleStruct<'static, T>;
+
+mod constructors {
+    impl super::ExampleStruct {
+        pub fn new() -> Self { super::ExampleStruct {} }
+    }
+}
@@ -0,0 +1,71 @@
+//! # Query Backends
+//! A [QueryBackend] turns a [Query] into the items it refers to. Backends are set on the
+//! [QueryContext] with [QueryContextBuilder::backend](crate::context::QueryContextBuilder::backend).
+//!
+//! | Backend              | Resolution                                                      |
+//! |----------------------|-----------------------------------------------------------------|
+//! | [SyntacticBackend]   | Walks the module hierarchy by parsing files with `syn`          |
+//! | `RustAnalyzerBackend`| Semantic resolution with rust-analyzer's library crates. Handles macro-generated items, glob imports and trait resolution. Requires the `rust-analyzer` feature. |
+//!
+//! Every backend is expected to pass the conformance tests in `tests/backend_conformance.rs`.
+
+#[cfg(feature = "rust-analyzer")]
+pub mod rust_analyzer;
+
+use serde::{Deserialize, Serialize};
+use syn::Item;
+use crate::context::QueryContext;
+use crate::core::{Query, query_cargo_src_core};
+use crate::errors::CargoQueryError;
+use crate::flags::Flags;
+use crate::r#impl::trace::{ResolutionTrace, Tracer};
+
+#[cfg(feature = "rust-analyzer")]
+pub use self::rust_analyzer::RustAnalyzerBackend;
+
+pub trait QueryBackend: Send + Sync {
+    fn query(&self, context: &QueryContext, flags: Flags, query: Query) -> Result<Vec<Item>, CargoQueryError>;
+
+    /// Resolves `query` like [query](QueryBackend::query), and also returns a trace of the
+    /// candidates tried for each segment. Backends which do not record traces return an
+    /// unsupported [ResolutionTrace].
+    fn explain(&self, context: &QueryContext, flags: Flags, query: Query) -> (Result<Vec<Item>, CargoQueryError>, ResolutionTrace) {
+        let trace = ResolutionTrace::unsupported(query.to_string());
+        (self.query(context, flags, query), trace)
+    }
+
+    /// Identifies the backend to other processes, e.g. the [daemon](crate::daemon). Queries
+    /// of contexts whose backend returns `None` are answered in-process.
+    fn kind(&self) -> Option<BackendKind> {
+        None
+    }
+}
+
+/// The backends a query can be sent to the [daemon](crate::daemon) with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    Syntactic,
+    RustAnalyzer,
+}
+
+/// Resolves queries by parsing the files of the module hierarchy. See
+/// [find_mod](crate::r#impl::find_mod).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SyntacticBackend;
+
+impl QueryBackend for SyntacticBackend {
+    fn query(&self, context: &QueryContext, flags: Flags, query: Query) -> Result<Vec<Item>, CargoQueryError> {
+        query_cargo_src_core(context, flags, query, &Tracer::disabled())
+    }
+
+    fn explain(&self, context: &QueryContext, flags: Flags, query: Query) -> (Result<Vec<Item>, CargoQueryError>, ResolutionTrace) {
+        let tracer = Tracer::enabled(query.to_string());
+        let result = query_cargo_src_core(context, flags, query, &tracer);
+        (result, tracer.into_trace())
+    }
+
+    fn kind(&self) -> Option<BackendKind> {
+        Some(BackendKind::Syntactic)
+    }
+}
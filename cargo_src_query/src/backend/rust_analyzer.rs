@@ -0,0 +1,195 @@
+//! Semantic query backend built on rust-analyzer's library crates (`ra_ap_*`). Workspaces are
+//! loaded with the features and target of the [QueryContext], including build script outputs,
+//! so items generated by macros and re-exported through globs resolve the same way they do
+//! for the compiler.
+//!
+//! Module queries return a module's declarations, `use` declarations and impls. Private items
+//! are left out when [Flags::EXCLUDE_PRIVATE] is set.
+//!
+//! Loading a workspace is expensive, so each workspace is loaded once per backend. Share a
+//! single backend between contexts, e.g. in the [daemon](crate::daemon), to reuse it.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use cargo_metadata::CargoOpt;
+use ra_ap_hir::{Adt, Crate as HirCrate, HasSource, InFile, Module, ModuleDef, ModuleSource, ScopeDef};
+use ra_ap_ide_db::RootDatabase;
+use ra_ap_ide_db::syntax_helpers::insert_whitespace_into_node::insert_ws_into;
+use ra_ap_load_cargo::{load_workspace_at, LoadCargoConfig, ProcMacroServerChoice};
+use ra_ap_project_model::{CargoConfig, CargoFeatures, RustLibSource};
+use ra_ap_syntax::{ast, AstNode};
+use ra_ap_syntax::ast::HasModuleItem;
+use syn::Item;
+use crate::backend::{BackendKind, QueryBackend};
+use crate::context::QueryContext;
+use crate::core::{Crate, is_private, Query, QueryType};
+use crate::errors::CargoQueryError;
+use crate::flags::Flags;
+use crate::r#impl::find_dependencies::Definition;
+
+#[derive(Default)]
+pub struct RustAnalyzerBackend {
+    /// Loaded workspaces, keyed by manifest path
+    workspaces: Mutex<BTreeMap<PathBuf, RootDatabase>>,
+}
+
+impl QueryBackend for RustAnalyzerBackend {
+    fn query(&self, context: &QueryContext, flags: Flags, query: Query) -> Result<Vec<Item>, CargoQueryError> {
+        let mut workspaces = self.workspaces.lock().expect("rust-analyzer workspaces are poisoned");
+        let db = match workspaces.entry(context.manifest_path().to_path_buf()) {
+            std::collections::btree_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::btree_map::Entry::Vacant(entry) => entry.insert(load_workspace(context)?),
+        };
+
+        let krate = find_crate(db, context, query.crate_name())?;
+        let module = find_module(db, krate.root_module(), query.module_path())?;
+
+        match query.query_type() {
+            QueryType::Mod => {
+                let items = module_sources(db, module).iter()
+                    .map(|source| parse_item(source))
+                    .collect::<Result<Vec<Item>, CargoQueryError>>()?;
+                Ok(items.into_iter()
+                    .filter(|item| !flags.contains(Flags::EXCLUDE_PRIVATE) || !is_private(item))
+                    .collect())
+            },
+            QueryType::Definition(name, definition_type) => {
+                // The module scope includes glob imports, re-exports and macro-generated items
+                let def = module.scope(db, None).into_iter().find_map(|(scope_name, def)| match def {
+                    ScopeDef::ModuleDef(def) if scope_name.to_smol_str() == name.to_string()
+                        && is_definition_type(def, *definition_type) => Some(def),
+                    _ => None
+                }).ok_or_else(|| CargoQueryError::could_not_find_definition(name).at_path_segment(query.module_path().segments.len()))?;
+                let source = item_source(db, def).ok_or_else(|| CargoQueryError::could_not_find_definition(name))?;
+                Ok(vec![parse_item(&source)?])
+            }
+        }
+    }
+
+    fn kind(&self) -> Option<BackendKind> {
+        Some(BackendKind::RustAnalyzer)
+    }
+}
+
+fn load_workspace(context: &QueryContext) -> Result<RootDatabase, CargoQueryError> {
+    let cargo_config = CargoConfig {
+        features: cargo_features(context.features()),
+        target: context.target().map(str::to_string),
+        sysroot: Some(RustLibSource::Discover),
+        ..Default::default()
+    };
+    let load_config = LoadCargoConfig {
+        load_out_dirs_from_check: true,
+        with_proc_macro_server: ProcMacroServerChoice::Sysroot,
+        prefill_caches: false,
+    };
+    let (db, _vfs, _proc_macro_server) = load_workspace_at(context.manifest_path(), &cargo_config, &load_config, &|_| {})
+        .map_err(|e| CargoQueryError::could_not_load_workspace(context.manifest_path(), e))?;
+    Ok(db)
+}
+
+fn cargo_features(features: &[CargoOpt]) -> CargoFeatures {
+    if features.iter().any(|f| matches!(f, CargoOpt::AllFeatures)) {
+        return CargoFeatures::All
+    }
+    CargoFeatures::Selected {
+        features: features.iter().flat_map(|f| match f {
+            CargoOpt::SomeFeatures(features) => features.clone(),
+            _ => vec![],
+        }).collect(),
+        no_default_features: features.iter().any(|f| matches!(f, CargoOpt::NoDefaultFeatures)),
+    }
+}
+
+fn find_crate(db: &RootDatabase, context: &QueryContext, crate_name: &Crate) -> Result<HirCrate, CargoQueryError> {
+    let name = match crate_name {
+        Crate::Internal => context.package(crate_name)?.name.clone(),
+        Crate::External(name) => name.to_string(),
+    }.replace('-', "_");
+
+    HirCrate::all(db).into_iter()
+        .find(|krate| krate.display_name(db).is_some_and(|n| n.to_string().replace('-', "_") == name))
+        .ok_or_else(|| match crate_name {
+            Crate::External(name) => CargoQueryError::could_not_find_crate(name),
+            Crate::Internal => CargoQueryError::could_not_find_module_from_path(context.manifest_path()),
+        })
+}
+
+/// Follows the module path through each module's scope, so modules re-exported with
+/// `pub use` or through globs are found as well.
+fn find_module(db: &RootDatabase, root: Module, module_path: &syn::Path) -> Result<Module, CargoQueryError> {
+    module_path.segments.iter().enumerate().try_fold(root, |module, (position, segment)| {
+        module.scope(db, None).into_iter().find_map(|(name, def)| match def {
+            ScopeDef::ModuleDef(ModuleDef::Module(child)) if name.to_smol_str() == segment.ident.to_string() => Some(child),
+            _ => None
+        }).ok_or_else(|| CargoQueryError::could_not_find_module_in_scope(segment.ident.clone()).at_path_segment(position))
+    })
+}
+
+/// Renders every item of a module: its declarations, including those generated by macros, its
+/// `use` declarations and its impls. Impls expanded from macros are left out, since most are
+/// generated by derives, which are already part of the attributes of the deriving type.
+fn module_sources(db: &RootDatabase, module: Module) -> Vec<String> {
+    let mut sources: Vec<String> = module.declarations(db).into_iter()
+        .filter_map(|def| item_source(db, def))
+        .collect();
+
+    let items: Vec<ast::Item> = match module.definition_source(db).value {
+        ModuleSource::SourceFile(file) => file.items().collect(),
+        ModuleSource::Module(module) => module.item_list().map(|list| list.items().collect()).unwrap_or_default(),
+        ModuleSource::BlockExpr(_) => vec![],
+    };
+    sources.extend(items.into_iter().filter_map(|item| match item {
+        ast::Item::Use(item_use) => Some(item_use.syntax().to_string()),
+        _ => None
+    }));
+
+    sources.extend(module.impl_defs(db).into_iter()
+        .filter_map(|impl_def| impl_def.source(db))
+        .filter(|source| !source.file_id.is_macro())
+        .map(|source| source.value.syntax().to_string()));
+    sources
+}
+
+/// Whether an item is visible only within its crate or module. Items without a visibility,
+/// such as impls, are never private.
+fn is_definition_type(def: ModuleDef, definition_type: Definition) -> bool {
+    match definition_type {
+        Definition::Struct => matches!(def, ModuleDef::Adt(Adt::Struct(_))),
+        Definition::Enum => matches!(def, ModuleDef::Adt(Adt::Enum(_))),
+        Definition::Fn => matches!(def, ModuleDef::Function(_)),
+        Definition::Union => matches!(def, ModuleDef::Adt(Adt::Union(_))),
+        Definition::Trait => matches!(def, ModuleDef::Trait(_)),
+        Definition::Type => matches!(def, ModuleDef::TypeAlias(_)),
+    }
+}
+
+/// Renders the source of a definition. Items generated by macros don't have any whitespace in
+/// their syntax tree, so whitespace is inserted before rendering.
+fn item_source(db: &RootDatabase, def: ModuleDef) -> Option<String> {
+    fn render<N: AstNode>(source: InFile<N>) -> String {
+        if source.file_id.is_macro() {
+            insert_ws_into(source.value.syntax().clone()).to_string()
+        } else {
+            source.value.syntax().to_string()
+        }
+    }
+
+    match def {
+        ModuleDef::Module(m) => m.declaration_source(db).map(render),
+        ModuleDef::Function(f) => f.source(db).map(render),
+        ModuleDef::Adt(Adt::Struct(s)) => s.source(db).map(render),
+        ModuleDef::Adt(Adt::Enum(e)) => e.source(db).map(render),
+        ModuleDef::Adt(Adt::Union(u)) => u.source(db).map(render),
+        ModuleDef::Const(c) => c.source(db).map(render),
+        ModuleDef::Static(s) => s.source(db).map(render),
+        ModuleDef::Trait(t) => t.source(db).map(render),
+        ModuleDef::TypeAlias(t) => t.source(db).map(render),
+        _ => None,
+    }
+}
+
+fn parse_item(source: &str) -> Result<Item, CargoQueryError> {
+    syn::parse_str(source).map_err(|e| CargoQueryError::could_not_parse_item(source, e))
+}
@@ -0,0 +1,411 @@
+//! Command line tooling for `cargo_src_query`.
+//!
+//! ```text
+//! cargo_src_query --explain <code>
+//! cargo_src_query --replay <bundle>
+//! cargo_src_query --trace <Cargo.toml> <path::to::module> [struct|fn|enum|union|trait|type <Name>]
+//! cargo_src_query --source <Cargo.toml> <path::to::module> [struct|fn|enum|union|trait|type <Name>]
+//! cargo_src_query --locate <Cargo.toml> <file[:line[:column]]>
+//! cargo_src_query --public-path <Cargo.toml> <path::to::Name>
+//! cargo_src_query --associated <Cargo.toml> <path::to::Type::member>
+//! cargo_src_query --alias <Cargo.toml> <path::to::Alias<Arguments>>
+//! cargo_src_query --references <Cargo.toml> <path::to::Definition> [--workspace]
+//! cargo_src_query --find <Cargo.toml> <[kind] path pattern, e.g. 'struct crate::**::*Config'>
+//! cargo_src_query --attribute <Cargo.toml> <path::to::module> <path::to::attribute> [arguments]
+//! cargo_src_query --derive <Cargo.toml> <path::to::module> <path::to::Trait>
+//! cargo_src_query --graph <Cargo.toml> dot|mermaid [--items] [--reexports]
+//! cargo_src_query --api <Cargo.toml>
+//! cargo_src_query --api-diff <crate> <old version> <new version>
+//! ```
+
+use std::path::Path;
+use quote::ToTokens;
+use cargo_src_query::{AttributePattern, Crate, Definition, GraphFormat, GraphOptions, PathPattern, QueryContext, ReferenceScope, Severity, SourceLocation};
+use cargo_src_query::errors::ErrorKind;
+use cargo_src_query::flags::DEFAULT_FLAGS;
+use cargo_src_query::reproducer::replay;
+
+const USAGE: &str = "usage: cargo_src_query --explain <code>
+       cargo_src_query --replay <bundle>
+       cargo_src_query --trace <Cargo.toml> <path::to::module> [struct|fn|enum|union|trait|type <Name>]
+       cargo_src_query --source <Cargo.toml> <path::to::module> [struct|fn|enum|union|trait|type <Name>]
+       cargo_src_query --locate <Cargo.toml> <file[:line[:column]]>
+       cargo_src_query --public-path <Cargo.toml> <path::to::Name>
+       cargo_src_query --associated <Cargo.toml> <path::to::Type::member>
+       cargo_src_query --alias <Cargo.toml> <path::to::Alias<Arguments>>
+       cargo_src_query --references <Cargo.toml> <path::to::Definition> [--workspace]
+       cargo_src_query --find <Cargo.toml> <[kind] path pattern, e.g. 'struct crate::**::*Config'>
+       cargo_src_query --attribute <Cargo.toml> <path::to::module> <path::to::attribute> [arguments]
+       cargo_src_query --derive <Cargo.toml> <path::to::module> <path::to::Trait>
+       cargo_src_query --graph <Cargo.toml> dot|mermaid [--items] [--reexports]
+       cargo_src_query --api <Cargo.toml>
+       cargo_src_query --api-diff <crate> <old version> <new version>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["--explain", code] => explain(code),
+        ["--replay", bundle] => replay_bundle(Path::new(bundle)),
+        ["--trace", manifest, module] => trace(Path::new(manifest), module, None),
+        ["--trace", manifest, module, definition_type, name] => trace(Path::new(manifest), module, Some((definition_type, name))),
+        ["--source", manifest, module] => source(Path::new(manifest), module, None),
+        ["--source", manifest, module, definition_type, name] => source(Path::new(manifest), module, Some((definition_type, name))),
+        ["--locate", manifest, location] => locate(Path::new(manifest), location),
+        ["--public-path", manifest, definition] => public_path(Path::new(manifest), definition),
+        ["--associated", manifest, path] => associated(Path::new(manifest), path),
+        ["--alias", manifest, path] => alias(Path::new(manifest), path),
+        ["--references", manifest, path] => references(Path::new(manifest), path, ReferenceScope::Crate),
+        ["--references", manifest, path, "--workspace"] => references(Path::new(manifest), path, ReferenceScope::Workspace),
+        ["--find", manifest, pattern] => find(Path::new(manifest), pattern),
+        ["--attribute", manifest, module, attribute] => search_attributes(Path::new(manifest), module, AttributePattern::attribute(attribute)),
+        ["--attribute", manifest, module, attribute, arguments] =>
+            search_attributes(Path::new(manifest), module, AttributePattern::attribute(attribute).arguments(arguments)),
+        ["--derive", manifest, module, derived] => search_attributes(Path::new(manifest), module, AttributePattern::derive(derived)),
+        ["--graph", manifest, format, options @ ..] => graph(Path::new(manifest), format, options),
+        ["--api", manifest] => api(Path::new(manifest)),
+        ["--api-diff", crate_name, old_version, new_version] => api_diff(crate_name, old_version, new_version),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2)
+        }
+    }
+}
+
+fn explain(code: &str) {
+    match ErrorKind::from_code(code) {
+        Some(kind) => print!("{}", kind.explanation()),
+        None => {
+            eprintln!("error: `{code}` is not a valid error code");
+            eprintln!("valid codes are {}", ErrorKind::ALL.map(ErrorKind::code).join(", "));
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Exits successfully when the recorded failure is reproduced.
+fn replay_bundle(bundle: &Path) {
+    let replay = match replay(bundle) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("error: could not read reproducer `{}`: {e}", bundle.display());
+            std::process::exit(2)
+        }
+    };
+
+    println!("recorded: error[{}]: {}", replay.expected.kind, replay.expected.top_level_message);
+    match &replay.actual {
+        Ok(items) => {
+            println!("replayed: the query succeeded");
+            for item in items {
+                println!("{}", item.to_token_stream());
+            }
+        },
+        Err(e) => println!("replayed: {e}"),
+    }
+
+    if replay.reproduced() {
+        println!("the failure was reproduced");
+    } else {
+        println!("the failure was not reproduced");
+        std::process::exit(1)
+    }
+}
+
+/// Parses a module path relative to the crate root, where `crate` or an empty path is the root.
+fn parse_module_path(module: &str) -> syn::Path {
+    if module.is_empty() || module == "crate" {
+        return syn::Path { leading_colon: None, segments: Default::default() }
+    }
+    syn::parse_str(module.trim_start_matches("crate::")).unwrap_or_else(|_| {
+        eprintln!("error: `{module}` is not a valid path");
+        std::process::exit(2)
+    })
+}
+
+fn parse_definition(definition_type: &str, name: &str) -> (Definition, syn::Ident) {
+    let definition_type = match definition_type {
+        "struct" => Definition::Struct,
+        "fn" => Definition::Fn,
+        "enum" => Definition::Enum,
+        "union" => Definition::Union,
+        "trait" => Definition::Trait,
+        "type" => Definition::Type,
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2)
+        }
+    };
+    let name = syn::parse_str(name).unwrap_or_else(|_| {
+        eprintln!("error: `{name}` is not a valid identifier");
+        std::process::exit(2)
+    });
+    (definition_type, name)
+}
+
+/// Prints how the query was resolved as a tree. Exits successfully when the query resolves.
+fn trace(manifest: &Path, module: &str, definition: Option<(&str, &str)>) {
+    let module_path = parse_module_path(module);
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    let (result, resolution_trace) = match definition {
+        None => context.explain_module(DEFAULT_FLAGS, Crate::Internal, module_path),
+        Some((definition_type, name)) => {
+            let (definition_type, name) = parse_definition(definition_type, name);
+            let (result, resolution_trace) = context.explain_definition(DEFAULT_FLAGS, Crate::Internal, module_path, name, definition_type);
+            (result.map(|item| vec![item]), resolution_trace)
+        }
+    };
+
+    println!("{resolution_trace}");
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1)
+    }
+}
+
+/// Prints the verbatim source text of a module's items or of a definition, each preceded by the
+/// file and line it starts on.
+fn source(manifest: &Path, module: &str, definition: Option<(&str, &str)>) {
+    let module_path = parse_module_path(module);
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    let result = match definition {
+        None => context.get_module_verbatim(DEFAULT_FLAGS, Crate::Internal, module_path),
+        Some((definition_type, name)) => {
+            let (definition_type, name) = parse_definition(definition_type, name);
+            context.get_definition_verbatim(DEFAULT_FLAGS, Crate::Internal, module_path, name, definition_type)
+                .map(|item| vec![item])
+        }
+    };
+
+    match result {
+        Ok(items) => for item in items {
+            println!("// {}:{}", item.file_path.display(), item.line);
+            println!("{}", item.text);
+        },
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Prints the full path of the item at a file location, e.g. `crate::foo::Bar::method`.
+fn locate(manifest: &Path, location: &str) {
+    let location: SourceLocation = match location.parse() {
+        Ok(location) => location,
+        Err(e) => {
+            eprintln!("error: `{location}` is not a valid location: {e}");
+            std::process::exit(2)
+        }
+    };
+
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    match context.locate(&Crate::Internal, &location) {
+        Ok(lookup) => {
+            println!("{lookup}");
+            if let Some(item) = &lookup.item {
+                println!("  = kind: {}", item.kind);
+            }
+            println!("  = module: {}", std::iter::once("crate").chain(lookup.module.iter().map(String::as_str)).collect::<Vec<_>>().join("::"));
+        },
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Prints the kind and full path of every module and definition matching the pattern.
+fn find(manifest: &Path, pattern: &str) {
+    let pattern: PathPattern = match pattern.parse() {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2)
+        }
+    };
+
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    match context.find_paths(&Crate::Internal, &pattern) {
+        Ok(matches) => for found in matches {
+            println!("{} {found}", found.kind);
+        },
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Prints the kind and full path of every item carrying the attribute, followed by the attribute.
+fn search_attributes(manifest: &Path, module: &str, pattern: AttributePattern) {
+    let module_path = parse_module_path(module);
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    match context.find_attributes(&Crate::Internal, &module_path, &pattern) {
+        Ok(matches) => for found in matches {
+            println!("{} {found}", found.kind);
+            println!("  = attribute: {}", found.attribute.to_token_stream());
+        },
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Prints every public path of a definition, starting with the canonical one.
+fn public_path(manifest: &Path, definition: &str) {
+    let definition = definition.trim_start_matches("crate::");
+    let (module, name) = definition.rsplit_once("::").unwrap_or(("", definition));
+    let module_path: syn::Path = match module {
+        "" => syn::Path { leading_colon: None, segments: Default::default() },
+        module => syn::parse_str(module).unwrap_or_else(|_| {
+            eprintln!("error: `{module}` is not a valid path");
+            std::process::exit(2)
+        }),
+    };
+    let name: syn::Ident = syn::parse_str(name).unwrap_or_else(|_| {
+        eprintln!("error: `{name}` is not a valid identifier");
+        std::process::exit(2)
+    });
+
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    match context.public_paths(&Crate::Internal, &module_path, &name) {
+        Ok(paths) => for path in paths {
+            println!("{}", path.to_token_stream().to_string().replace(' ', ""));
+        },
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Prints the kind and full path of every member named by the path, followed by the file
+/// containing it and its enclosing impl, trait or enum.
+fn associated(manifest: &Path, path: &str) {
+    let path: syn::Path = syn::parse_str(path).unwrap_or_else(|_| {
+        eprintln!("error: `{path}` is not a valid path");
+        std::process::exit(2)
+    });
+
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    match context.get_associated_items(&Crate::Internal, &path) {
+        Ok(items) => for item in items {
+            println!("{} {item}", item.member.kind());
+            println!("  = file: {}", item.file_path.display());
+            println!("{}", item.header.to_token_stream());
+        },
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Prints each type alias followed from the path, then the full path of the definition behind
+/// them, with the generic arguments substituted into it.
+fn alias(manifest: &Path, path: &str) {
+    let path: syn::Path = syn::parse_str(path).unwrap_or_else(|_| {
+        eprintln!("error: `{path}` is not a valid path");
+        std::process::exit(2)
+    });
+
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    match context.resolve_type_aliases(&Crate::Internal, path) {
+        Ok(resolution) => {
+            for step in &resolution.aliases {
+                println!("alias {}{} = {}", step.path, step.arguments.to_token_stream(), step.alias.ty.to_token_stream());
+            }
+            println!("{}{}", resolution.path, resolution.arguments.to_token_stream());
+            println!("{}", resolution.definition.to_token_stream());
+        },
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Prints the kind and full path of every item mentioning the definition, how it mentions it,
+/// and where the item is declared.
+fn references(manifest: &Path, path: &str, scope: ReferenceScope) {
+    let path: syn::Path = syn::parse_str(path).unwrap_or_else(|_| {
+        eprintln!("error: `{path}` is not a valid path");
+        std::process::exit(2)
+    });
+
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    match context.find_references(&Crate::Internal, path, scope) {
+        Ok(references) => for reference in references {
+            println!("{} {reference}: {} `{}`", reference.kind, reference.usage, reference.mention);
+            match reference.line {
+                Some(line) => println!("  = file: {}:{line}", reference.file_path.display()),
+                None => println!("  = file: {}", reference.file_path.display()),
+            }
+        },
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Prints the module hierarchy of the crate as Graphviz DOT or Mermaid text.
+fn graph(manifest: &Path, format: &str, options: &[&str]) {
+    let format: GraphFormat = format.parse().unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(2)
+    });
+    let mut graph_options = GraphOptions::default();
+    for option in options {
+        match *option {
+            "--items" => graph_options.items = true,
+            "--reexports" => graph_options.reexports = true,
+            _ => {
+                eprintln!("{USAGE}");
+                std::process::exit(2)
+            }
+        }
+    }
+
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    match context.module_graph(&Crate::Internal, format, graph_options) {
+        Ok(graph) => print!("{graph}"),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Prints every publicly reachable definition of the crate with its signature.
+fn api(manifest: &Path) {
+    let context = QueryContext::builder(manifest).reproducer(false).build();
+    match context.public_api(&Crate::Internal) {
+        Ok(api) => for item in api {
+            println!("{}: {}", item.path.join("::"), item.signature);
+        },
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Prints the changes between two versions of a crate from the local registry. Exits with 3
+/// when a major version bump is required.
+fn api_diff(crate_name: &str, old_version: &str, new_version: &str) {
+    let context = QueryContext::builder("Cargo.toml").reproducer(false).build();
+    match context.diff_registry_versions(crate_name, old_version, new_version) {
+        Ok(diff) => {
+            println!("{diff}");
+            if diff.severity() == Severity::Major {
+                std::process::exit(3)
+            }
+        },
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1)
+        }
+    }
+}
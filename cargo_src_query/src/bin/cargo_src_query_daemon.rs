@@ -0,0 +1,43 @@
+//! Starts the `cargo_src_query` daemon.
+//!
+//! ```text
+//! cargo_src_query_daemon [--socket <path>] [--threads <count>]
+//! ```
+
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::sync::Arc;
+#[cfg(unix)]
+use cargo_src_query::daemon::default_socket_path;
+#[cfg(unix)]
+use cargo_src_query::daemon::server::Daemon;
+
+/// The daemon listens on a unix socket, so there's nothing to start on other platforms.
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("error: cargo_src_query_daemon is only supported on unix");
+    std::process::exit(1)
+}
+
+#[cfg(unix)]
+fn main() -> std::io::Result<()> {
+    let mut socket_path = default_socket_path();
+    let mut threads = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--socket" => socket_path = PathBuf::from(args.next().expect("`--socket` requires a path")),
+            "--threads" => threads = Some(args.next()
+                .and_then(|t| t.parse().ok())
+                .expect("`--threads` requires a number")),
+            _ => {
+                eprintln!("usage: cargo_src_query_daemon [--socket <path>] [--threads <count>]");
+                std::process::exit(2)
+            }
+        }
+    }
+
+    Arc::new(Daemon::new(threads)).serve(&socket_path)
+}
@@ -0,0 +1,719 @@
+//! # Query Context
+//! A [QueryContext] holds everything a query needs to know about its environment: which
+//! `Cargo.toml` the query is made from, how `cargo metadata` should be resolved, and where
+//! source files are read from. Nothing in the context is derived from the current working
+//! directory, so queries behave the same inside and outside of cargo.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use cargo_src_query::context::QueryContext;
+//! # use cargo_src_query::source::FileSystem;
+//! // Inside of a build script or procedural macro
+//! let context = QueryContext::from_env().unwrap();
+//!
+//! // Anywhere else
+//! let context = QueryContext::builder("/path/to/crate/Cargo.toml")
+//!     .target("x86_64-unknown-linux-gnu")
+//!     .source_provider(Arc::new(FileSystem))
+//!     .build();
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use cargo_metadata::{CargoOpt, Metadata, MetadataCommand, Package, PackageId, Target};
+use log::warn;
+use maplit::btreemap;
+use syn::{Ident, Item, PathSegment};
+use crate::backend::{QueryBackend, SyntacticBackend};
+use crate::core::{CARGO_ENV_VAR, Crate, Query, QueryType, Resolver};
+#[cfg(unix)]
+use crate::daemon::protocol::{ContextConfig, Features};
+use crate::errors::CargoQueryError;
+use crate::flags::Flags;
+use crate::r#impl::associated::{AssociatedItem, find_associated};
+use crate::r#impl::attribute_search::{AttributeMatch, AttributePattern, find_attributes};
+use crate::r#impl::find_dependencies::{CrateLocation, Definition, DependencyLookup};
+use crate::r#impl::generics::substitute_generics;
+use crate::r#impl::include::IncludeExpanding;
+use crate::r#impl::index::{CrateIndex, index_crate, index_crates};
+use crate::r#impl::locate::{locate, ReverseLookup, SourceLocation};
+use crate::r#impl::module_graph::{GraphFormat, GraphOptions, module_graph};
+use crate::r#impl::path_pattern::{find_paths, PathMatch, PathPattern};
+use crate::r#impl::public_api::{ApiDiff, ApiItem, cargo_home, diff_api, public_api, registry_crate_path};
+use crate::r#impl::public_path::public_paths;
+use crate::r#impl::references::{find_references, Reference, ReferenceScope};
+use crate::r#impl::trace::ResolutionTrace;
+use crate::r#impl::type_alias::{AliasResolution, find_type, is_caller_parameter, resolve_type_aliases};
+use crate::r#impl::verbatim::{VerbatimItem, verbatim_items};
+use crate::logger;
+use crate::reproducer::{self, RecordedQuery};
+use crate::source::{FileSystem, Recording, SourceProvider};
+use crate::tracking::{TrackedFiles, Tracking};
+
+/// Determines where the `cargo metadata` of a [QueryContext] comes from.
+pub enum MetadataSource {
+    /// Run `cargo metadata` on the manifest path the first time metadata is needed.
+    Command,
+    /// Use metadata which has already been loaded.
+    Precomputed(Box<Metadata>),
+}
+
+/// The environment a query is resolved in. See the [module documentation](self) for examples.
+pub struct QueryContext {
+    manifest_path: PathBuf,
+    features: Vec<CargoOpt>,
+    target: Option<String>,
+    metadata_source: MetadataSource,
+    source_provider: Arc<dyn SourceProvider>,
+    backend: Arc<dyn QueryBackend>,
+    threads: Option<usize>,
+    metadata: OnceLock<Metadata>,
+    /// Records the sources read by queries, set when reproducers are enabled
+    recording: Option<Arc<Recording>>,
+    /// Reports the sources read by [QueryContext::tracked]
+    tracking: Arc<Tracking>,
+    // The remaining fields are only kept for the daemon, see [QueryContext::daemon_config]
+    #[cfg(unix)]
+    profile: String,
+    #[cfg(unix)]
+    reproducer: bool,
+    /// Whether the provided source provider reads the files on disk
+    #[cfg(unix)]
+    reads_file_system: bool,
+}
+
+/// Builds a [QueryContext]. Created with [QueryContext::builder].
+pub struct QueryContextBuilder {
+    manifest_path: PathBuf,
+    features: Vec<CargoOpt>,
+    target: Option<String>,
+    metadata_source: MetadataSource,
+    source_provider: Arc<dyn SourceProvider>,
+    backend: Arc<dyn QueryBackend>,
+    threads: Option<usize>,
+    reproducer: bool,
+    profile: String,
+}
+
+impl QueryContextBuilder {
+    /// Features passed to `cargo metadata`. Defaults to all features when none are provided.
+    pub fn features(mut self, features: CargoOpt) -> Self {
+        self.features.push(features);
+        self
+    }
+
+    /// Target triple used to filter the dependencies reported by `cargo metadata`.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub fn metadata_source(mut self, metadata_source: MetadataSource) -> Self {
+        self.metadata_source = metadata_source;
+        self
+    }
+
+    pub fn source_provider(mut self, source_provider: Arc<dyn SourceProvider>) -> Self {
+        self.source_provider = source_provider;
+        self
+    }
+
+    /// Backend which resolves queries. Defaults to the [SyntacticBackend].
+    pub fn backend(mut self, backend: Arc<dyn QueryBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Caps the number of threads used to index crates. Defaults to one thread per CPU.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Writes a reproducer bundle when a query fails. Defaults to whether
+    /// [REPRODUCER_ENV_VAR](crate::reproducer::REPRODUCER_ENV_VAR) is set. See
+    /// [reproducer](crate::reproducer).
+    pub fn reproducer(mut self, enabled: bool) -> Self {
+        self.reproducer = enabled;
+        self
+    }
+
+    /// Cargo profile whose build script outputs are used to expand
+    /// `include!(concat!(env!("OUT_DIR"), ...))`. Defaults to `dev`. See
+    /// [include](crate::r#impl::include).
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = profile.into();
+        self
+    }
+
+    pub fn build(self) -> QueryContext {
+        let QueryContextBuilder {
+            manifest_path, mut features, target, metadata_source, mut source_provider, backend, threads, reproducer, profile
+        } = self;
+        if features.is_empty() {
+            features.push(CargoOpt::AllFeatures);
+        }
+        if let Some(manifest_dir) = manifest_path.parent() {
+            logger::init(&logger::target_directory(manifest_dir));
+        }
+        #[cfg(unix)]
+        let reads_file_system = source_provider.reads_file_system();
+        let tracking = Arc::new(Tracking::new(source_provider));
+        source_provider = tracking.clone();
+        let recording = reproducer.then(|| Arc::new(Recording::new(source_provider.clone())));
+        if let Some(recording) = &recording {
+            source_provider = recording.clone();
+        }
+        // Precomputed metadata may point into a reproducer bundle, which contains the build script
+        // outputs read when the bundle was written
+        let target_directory = match &metadata_source {
+            MetadataSource::Precomputed(metadata) => metadata.target_directory.clone().into_std_path_buf(),
+            MetadataSource::Command => logger::target_directory(manifest_path.parent().unwrap_or(Path::new(""))),
+        };
+        source_provider = Arc::new(IncludeExpanding::new(source_provider, target_directory, &profile, target.clone()));
+
+        QueryContext {
+            manifest_path,
+            features,
+            target,
+            metadata_source,
+            source_provider,
+            backend,
+            threads,
+            metadata: OnceLock::new(),
+            recording,
+            tracking,
+            #[cfg(unix)]
+            profile,
+            #[cfg(unix)]
+            reproducer,
+            #[cfg(unix)]
+            reads_file_system,
+        }
+    }
+}
+
+/// The name code refers to a package's library by, e.g. `my_crate` for `my-crate`.
+fn library_name(package: &Package) -> String {
+    library_target(package)
+        .map_or(&package.name, |t| &t.name)
+        .replace('-', "_")
+}
+
+fn library_target(package: &Package) -> Option<&Target> {
+    package.targets.iter().find(|t| t.kind.iter().any(|k| k == "lib" || k == "rlib" || k == "proc-macro"))
+}
+
+impl QueryContext {
+    /// Creates a builder for the crate defined by the `Cargo.toml` at `manifest_path`.
+    pub fn builder(manifest_path: impl Into<PathBuf>) -> QueryContextBuilder {
+        QueryContextBuilder {
+            manifest_path: manifest_path.into(),
+            features: vec![],
+            target: None,
+            metadata_source: MetadataSource::Command,
+            source_provider: Arc::new(FileSystem),
+            backend: Arc::new(SyntacticBackend),
+            threads: None,
+            reproducer: reproducer::enabled(),
+            profile: "dev".to_string(),
+        }
+    }
+
+    /// Creates a context with default settings for the crate defined by the `Cargo.toml` at
+    /// `manifest_path`.
+    pub fn from_manifest_path(manifest_path: impl Into<PathBuf>) -> QueryContext {
+        Self::builder(manifest_path).build()
+    }
+
+    /// Creates a context with default settings for the crate currently being compiled by cargo,
+    /// based on the `CARGO_MANIFEST_DIR` environment variable.
+    pub fn from_env() -> Result<QueryContext, CargoQueryError> {
+        Ok(Self::builder(Self::env_manifest_dir()?.join("Cargo.toml")).build())
+    }
+
+    pub(crate) fn env_manifest_dir() -> Result<PathBuf, CargoQueryError> {
+        std::env::var(CARGO_ENV_VAR)
+            .map(PathBuf::from)
+            .map_err(|e| CargoQueryError::could_not_read_environment_variable(CARGO_ENV_VAR, e))
+    }
+
+    pub fn manifest_path(&self) -> &Path {
+        &self.manifest_path
+    }
+
+    pub fn source_provider(&self) -> &dyn SourceProvider {
+        self.source_provider.as_ref()
+    }
+
+    /// Runs `f`, returning the files it read through this context. Procedural macros should add
+    /// the returned files to their expansion, so that they're re-expanded when the files change.
+    /// See [tracking](crate::tracking).
+    pub fn tracked<T>(&self, f: impl FnOnce(&Self) -> T) -> (T, TrackedFiles) {
+        self.tracking.track(|| f(self))
+    }
+
+    /// Adds files read on behalf of this context, e.g. by the daemon, to the open
+    /// [tracked](Self::tracked) sessions.
+    #[cfg(unix)]
+    pub(crate) fn record_reads(&self, files: &TrackedFiles) {
+        self.tracking.record(files);
+    }
+
+    pub fn features(&self) -> &[CargoOpt] {
+        &self.features
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    /// The configuration the [daemon](crate::daemon) rebuilds this context from. `None` when the
+    /// context can't be rebuilt in another process, because its sources don't come from the file
+    /// system or its backend doesn't identify itself.
+    #[cfg(unix)]
+    pub fn daemon_config(&self) -> Option<ContextConfig> {
+        if !self.reads_file_system { return None }
+        Some(ContextConfig {
+            manifest_path: self.manifest_path.clone(),
+            features: self.features.iter().map(Features::from).collect(),
+            target: self.target.clone(),
+            profile: self.profile.clone(),
+            backend: self.backend.kind()?,
+            metadata: match &self.metadata_source {
+                MetadataSource::Command => None,
+                MetadataSource::Precomputed(metadata) => Some(metadata.clone()),
+            },
+            reproducer: self.reproducer,
+        })
+    }
+
+    /// Returns the `cargo metadata` for the manifest path. When the metadata source is
+    /// [MetadataSource::Command], `cargo metadata` is only run once per context.
+    pub fn metadata(&self) -> Result<&Metadata, CargoQueryError> {
+        if let Some(metadata) = self.metadata.get() {
+            return Ok(metadata)
+        }
+        let metadata = match &self.metadata_source {
+            MetadataSource::Precomputed(metadata) => metadata.as_ref().clone(),
+            MetadataSource::Command => self.metadata_command().exec().map_err(|e|
+                CargoQueryError::could_not_load_cargo_metadata(&self.manifest_path, e)
+            )?
+        };
+        Ok(self.metadata.get_or_init(|| metadata))
+    }
+
+    fn metadata_command(&self) -> MetadataCommand {
+        let mut command = MetadataCommand::new();
+        command.manifest_path(&self.manifest_path);
+        for features in &self.features {
+            command.features(features.clone());
+        }
+        if let Some(target) = &self.target {
+            command.other_options(vec!["--filter-platform".to_string(), target.clone()]);
+        }
+        command
+    }
+
+    /// Returns the directory containing the `Cargo.toml` of the queried crate.
+    pub fn crate_path(&self, crate_name: &Crate) -> Result<PathBuf, CargoQueryError> {
+        match crate_name {
+            Crate::Internal => Ok(self.manifest_dir()),
+            Crate::External(_) => {
+                // Remove `Cargo.toml` from end of src_path
+                // Run `cargo metadata` and search for `manifest_path` to see an example of a path
+                let mut path = self.package(crate_name)?.manifest_path.to_owned();
+                path.pop();
+                Ok(path.into_std_path_buf())
+            }
+        }
+    }
+
+    /// Names the queried crate the way paths within it refer to it: `crate` for the crate of the
+    /// context, and the library name for any other crate.
+    pub(crate) fn crate_location(&self, crate_name: &Crate) -> Result<CrateLocation, CargoQueryError> {
+        Ok(CrateLocation {
+            name: match crate_name {
+                Crate::Internal => "crate".to_string(),
+                Crate::External(ident) => ident.to_string(),
+            },
+            path: self.crate_path(crate_name)?,
+        })
+    }
+
+    /// Returns the package of the queried crate from `cargo metadata`.
+    pub fn package(&self, crate_name: &Crate) -> Result<&Package, CargoQueryError> {
+        let packages = &self.metadata()?.packages;
+        match crate_name {
+            Crate::Internal => packages.iter()
+                .find(|&p| p.manifest_path.as_std_path() == self.manifest_path)
+                .ok_or_else(|| CargoQueryError::could_not_find_module_from_path(&self.manifest_path)),
+            Crate::External(src_crate_name) => {
+                let name = src_crate_name.to_string();
+                // Dependencies are declared with `-`, but are referred to in code with `_`
+                packages.iter().find(|&p| p.name == name || p.name.replace('-', "_") == name)
+                    .ok_or_else(|| CargoQueryError::could_not_find_crate(src_crate_name))
+            }
+        }
+    }
+
+    /// Indexes every module of the queried crate. See [CrateIndex].
+    pub fn index_crate(&self, crate_name: &Crate) -> Result<CrateIndex, CargoQueryError> {
+        let package = self.package(crate_name)?;
+        let target = library_target(package).ok_or_else(|| CargoQueryError::no_library_target(&package.name))?;
+        index_crate(self.source_provider(), target.src_path.as_std_path(), self.threads)
+    }
+
+    /// Finds the module implemented by a file, and the item enclosing a line and column within
+    /// it. Relative file paths are resolved from the crate's directory. See
+    /// [locate](crate::r#impl::locate).
+    pub fn locate(&self, crate_name: &Crate, location: &SourceLocation) -> Result<ReverseLookup, CargoQueryError> {
+        let index = self.index_crate(crate_name)?;
+        locate(&index, &self.crate_path(crate_name)?, location)
+    }
+
+    /// Every path through which other crates can name the definition `name` within
+    /// `module_path`, starting with the crate's name and ranked from the canonical, shortest
+    /// path. Fails when the definition is not publicly reachable. See
+    /// [public_path](crate::r#impl::public_path).
+    pub fn public_paths(&self, crate_name: &Crate, module_path: &syn::Path, name: &Ident)
+        -> Result<Vec<syn::Path>, CargoQueryError> {
+        let index = self.index_crate(crate_name)?;
+        let module: Vec<String> = module_path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let paths = public_paths(&index, &module, &name.to_string())
+            .ok_or_else(|| CargoQueryError::could_not_find_definition(name))?;
+        if paths.is_empty() {
+            let definition = module.iter().chain([&name.to_string()]).cloned().collect::<Vec<_>>().join("::");
+            return Err(CargoQueryError::not_publicly_reachable(&definition))
+        }
+
+        let crate_ident = self.crate_ident(crate_name)?;
+        Ok(paths.into_iter()
+            .map(|path| crate::core::module_path(&[vec![crate_ident.clone()], path].concat()))
+            .collect())
+    }
+
+    /// The shortest of [QueryContext::public_paths], which is how rustdoc names the definition.
+    pub fn canonical_public_path(&self, crate_name: &Crate, module_path: &syn::Path, name: &Ident)
+        -> Result<syn::Path, CargoQueryError> {
+        Ok(self.public_paths(crate_name, module_path, name)?.remove(0))
+    }
+
+    /// Lists every publicly reachable definition of the queried crate. See
+    /// [public_api](crate::r#impl::public_api).
+    pub fn public_api(&self, crate_name: &Crate) -> Result<Vec<ApiItem>, CargoQueryError> {
+        Ok(public_api(&self.index_crate(crate_name)?))
+    }
+
+    /// Compares the public APIs of two versions of a crate, both read from the unpacked sources
+    /// in the local registry, e.g. `~/.cargo/registry/src`. The library of each version is found
+    /// through its `Cargo.toml`, so crates with a `[lib] path` are compared as well.
+    pub fn diff_registry_versions(&self, crate_name: &str, old_version: &str, new_version: &str)
+        -> Result<ApiDiff, CargoQueryError> {
+        let api = |version: &str| -> Result<Vec<ApiItem>, CargoQueryError> {
+            let crate_path = cargo_home()
+                .and_then(|cargo_home| registry_crate_path(&cargo_home, crate_name, version))
+                .ok_or_else(|| CargoQueryError::could_not_find_registry_crate(crate_name, version))?;
+            let manifest_path = crate_path.join("Cargo.toml");
+            let metadata = MetadataCommand::new().manifest_path(&manifest_path).no_deps().exec()
+                .map_err(|e| CargoQueryError::could_not_load_cargo_metadata(&manifest_path, e))?;
+            // Registry crates are never part of a workspace, so theirs is the only package
+            let package = metadata.packages.first()
+                .ok_or_else(|| CargoQueryError::could_not_find_module_from_path(&manifest_path))?;
+            let library = library_target(package).ok_or_else(|| CargoQueryError::no_library_target(&package.name))?;
+            let index = index_crate(self.source_provider(), library.src_path.as_std_path(), self.threads)?;
+            Ok(public_api(&index))
+        };
+        Ok(diff_api(&api(old_version)?, &api(new_version)?))
+    }
+
+    /// Renders the module hierarchy of the queried crate as Graphviz DOT or Mermaid text. See
+    /// [module_graph](crate::r#impl::module_graph).
+    pub fn module_graph(&self, crate_name: &Crate, format: GraphFormat, options: GraphOptions) -> Result<String, CargoQueryError> {
+        let index = self.index_crate(crate_name)?;
+        Ok(module_graph(&index, &self.crate_ident(crate_name)?, format, options))
+    }
+
+    /// Finds the impl items, trait items or enum variants named by the last segment of `path`,
+    /// e.g. `crate::Type::method` or `crate::Enum::Variant`, each with the header of its
+    /// enclosing impl, trait or enum. See [associated](crate::r#impl::associated).
+    pub fn get_associated_items(&self, crate_name: &Crate, path: &syn::Path) -> Result<Vec<AssociatedItem>, CargoQueryError> {
+        let index = self.index_crate(crate_name)?;
+        let resolver = Resolver { source_provider: self.source_provider(), dependencies: self };
+        let krate = self.crate_location(crate_name)?;
+        let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let skipped = segments.iter().take_while(|segment| *segment == "crate").count();
+        find_associated(&resolver, &krate, &index, &segments[skipped..]).map_err(|e| e.offset_path_segment(skipped))
+    }
+
+    /// Finds every module and definition of the crate whose path matches `pattern`, e.g.
+    /// `struct crate::**::*Config`. See [path_pattern](crate::r#impl::path_pattern).
+    pub fn find_paths(&self, crate_name: &Crate, pattern: &PathPattern) -> Result<Vec<PathMatch>, CargoQueryError> {
+        find_paths(&self.index_crate(crate_name)?, pattern)
+    }
+
+    /// Finds every item within `module_path` and its descendants which carries an attribute or
+    /// derive matching `pattern`. An empty path searches the whole crate. See
+    /// [attribute_search](crate::r#impl::attribute_search).
+    pub fn find_attributes(&self, crate_name: &Crate, module_path: &syn::Path, pattern: &AttributePattern)
+        -> Result<Vec<AttributeMatch>, CargoQueryError> {
+        let index = self.index_crate(crate_name)?;
+        let module: Vec<String> = module_path.segments.iter().map(|s| s.ident.to_string()).collect();
+        find_attributes(&index, &module, pattern)
+    }
+
+    /// The name other crates refer to the queried crate by, e.g. `my_crate` for `my-crate`.
+    fn crate_ident(&self, crate_name: &Crate) -> Result<String, CargoQueryError> {
+        match crate_name {
+            Crate::External(ident) => Ok(ident.to_string()),
+            Crate::Internal => Ok(library_name(self.package(crate_name)?)),
+        }
+    }
+
+    /// Indexes the queried crate and every crate it transitively depends on, keyed by package.
+    /// Packages without a library target are skipped.
+    pub fn index_dependency_closure(&self, crate_name: &Crate)
+        -> Result<BTreeMap<PackageId, Result<CrateIndex, CargoQueryError>>, CargoQueryError> {
+        let metadata = self.metadata()?;
+        let root = self.package(crate_name)?.id.clone();
+
+        let mut closure = BTreeSet::new();
+        let mut unvisited = vec![root];
+        let nodes = metadata.resolve.as_ref().map(|r| r.nodes.as_slice()).unwrap_or_default();
+        while let Some(id) = unvisited.pop() {
+            if !closure.insert(id.clone()) { continue }
+            if let Some(node) = nodes.iter().find(|n| n.id == id) {
+                unvisited.extend(node.deps.iter().map(|d| d.pkg.clone()));
+            }
+        }
+
+        let roots = closure.into_iter().filter_map(|id| {
+            let target = library_target(&metadata[&id])?;
+            Some((id, target.src_path.clone().into_std_path_buf()))
+        }).collect();
+        index_crates(self.source_provider(), roots, self.threads)
+    }
+
+    /// Finds every item mentioning the struct, enum, union, trait or type alias at
+    /// `definition_path`, relative to the crate root, in its fields, signatures, impl headers or
+    /// bounds. See [references](crate::r#impl::references).
+    pub fn find_references(&self, crate_name: &Crate, definition_path: syn::Path, scope: ReferenceScope)
+        -> Result<Vec<Reference>, CargoQueryError> {
+        let segment_count = definition_path.segments.len();
+        let mut segments: Vec<PathSegment> = definition_path.segments.into_iter()
+            .skip_while(|segment| segment.ident == "crate")
+            .collect();
+        let skipped = segment_count - segments.len();
+        let definition = segments.pop().ok_or_else(CargoQueryError::could_not_find_module)?;
+        let module: Vec<String> = segments.iter().map(|segment| segment.ident.to_string()).collect();
+
+        let resolver = Resolver { source_provider: self.source_provider(), dependencies: self };
+        let krate = self.crate_location(crate_name)?;
+        let found = find_type(&resolver, &[(krate.clone(), module.clone(), definition.ident)])
+            .map_err(|e| e.at_path_segment(module.len()).offset_path_segment(skipped))?;
+        let crates = match scope {
+            ReferenceScope::Crate => vec![(krate, self.index_crate(crate_name)?)],
+            ReferenceScope::Workspace => self.index_workspace(crate_name)?,
+        };
+        find_references(&resolver, &found, &crates)
+    }
+
+    /// Indexes the queried crate followed by every other member of its workspace, from the
+    /// library or else the first binary of each member.
+    fn index_workspace(&self, crate_name: &Crate) -> Result<Vec<(CrateLocation, CrateIndex)>, CargoQueryError> {
+        let metadata = self.metadata()?;
+        let queried = self.package(crate_name)?;
+        let members = std::iter::once(queried)
+            .chain(metadata.workspace_members.iter().map(|id| &metadata[id]).filter(|package| package.id != queried.id));
+
+        let mut crates = vec![];
+        for package in members {
+            let target = library_target(package)
+                .or_else(|| package.targets.iter().find(|t| t.kind.iter().any(|k| k == "bin")));
+            let (Some(target), Some(path)) = (target, package.manifest_path.parent()) else { continue };
+            let location = match package.id == queried.id {
+                true => self.crate_location(crate_name)?,
+                false => CrateLocation { name: library_name(package), path: path.as_std_path().to_path_buf() },
+            };
+            crates.push((location, target.src_path.clone().into_std_path_buf()));
+        }
+
+        let roots = crates.iter().enumerate().map(|(position, (_, root))| (position, root.clone())).collect();
+        index_crates(self.source_provider(), roots, self.threads)?.into_iter()
+            .map(|(position, index)| Ok((crates[position].0.clone(), index?)))
+            .collect()
+    }
+
+    fn manifest_dir(&self) -> PathBuf {
+        self.manifest_path.parent().map(Path::to_path_buf).unwrap_or_default()
+    }
+
+    pub(crate) fn query(&self, flags: Flags, query: Query) -> Result<Vec<Item>, CargoQueryError> {
+        let recording = match &self.recording {
+            Some(recording) => recording,
+            None => return self.backend.query(self, flags, query),
+        };
+
+        let recorded_query = RecordedQuery::new(self, flags, &query);
+        let ((result, trace), sources) = recording.record(|| logger::capture(|| self.backend.query(self, flags, query)));
+        result.map_err(|e| match reproducer::write(self, recorded_query, &e, sources, trace) {
+            Ok(bundle) => e.add_messages(btreemap! { "reproducer" => bundle.to_string_lossy().to_string() }),
+            Err(io_error) => {
+                warn!("could not write reproducer: {io_error}");
+                e
+            }
+        })
+    }
+
+    pub fn get_module(&self, flags: Flags, crate_name: Crate, module_path: syn::Path) -> Result<Vec<Item>, CargoQueryError> {
+        self.query(flags, Query {
+            crate_name,
+            module_path,
+            query_type: QueryType::Mod,
+        })
+    }
+
+    /// Finds the definition named `definition_name` of type `definition_type` within the module
+    /// at `module_path`.
+    pub fn get_definition(&self, flags: Flags, crate_name: Crate, module_path: syn::Path,
+                          definition_name: Ident, definition_type: Definition) -> Result<Item, CargoQueryError> {
+        let mut items = self.query(flags, Query {
+            crate_name,
+            module_path,
+            query_type: QueryType::Definition(definition_name.clone(), definition_type),
+        })?;
+        items.pop().ok_or_else(|| CargoQueryError::could_not_find_definition(&definition_name))
+    }
+
+    /// [get_module](QueryContext::get_module), which also returns the verbatim source text of
+    /// each item, including comments and formatting. See [VerbatimItem].
+    pub fn get_module_verbatim(&self, flags: Flags, crate_name: Crate, module_path: syn::Path) -> Result<Vec<VerbatimItem>, CargoQueryError> {
+        let module: Vec<String> = module_path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let (items, files) = self.tracked(|context| context.get_module(flags, crate_name, module_path));
+        verbatim_items(self.source_provider(), files.paths(), &module, items?)
+    }
+
+    /// [get_definition](QueryContext::get_definition), which also returns the verbatim source
+    /// text of the definition, including comments and formatting. See [VerbatimItem].
+    pub fn get_definition_verbatim(&self, flags: Flags, crate_name: Crate, module_path: syn::Path,
+                                   definition_name: Ident, definition_type: Definition) -> Result<VerbatimItem, CargoQueryError> {
+        let module: Vec<String> = module_path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let (definition, files) = self.tracked(|context|
+            context.get_definition(flags, crate_name, module_path, definition_name.clone(), definition_type)
+        );
+        verbatim_items(self.source_provider(), files.paths(), &module, vec![definition?])?
+            .pop()
+            .ok_or_else(|| CargoQueryError::could_not_find_definition(&definition_name))
+    }
+
+    /// [get_module](QueryContext::get_module), which also returns how each segment of
+    /// `module_path` was resolved. See [ResolutionTrace].
+    pub fn explain_module(&self, flags: Flags, crate_name: Crate, module_path: syn::Path) -> (Result<Vec<Item>, CargoQueryError>, ResolutionTrace) {
+        self.backend.explain(self, flags, Query {
+            crate_name,
+            module_path,
+            query_type: QueryType::Mod,
+        })
+    }
+
+    /// [get_definition](QueryContext::get_definition), which also returns how each segment of
+    /// the path was resolved, including the re-exports which were followed. See [ResolutionTrace].
+    pub fn explain_definition(&self, flags: Flags, crate_name: Crate, module_path: syn::Path,
+                              definition_name: Ident, definition_type: Definition) -> (Result<Item, CargoQueryError>, ResolutionTrace) {
+        let (result, trace) = self.backend.explain(self, flags, Query {
+            crate_name,
+            module_path,
+            query_type: QueryType::Definition(definition_name.clone(), definition_type),
+        });
+        let definition = result.and_then(|mut items|
+            items.pop().ok_or_else(|| CargoQueryError::could_not_find_definition(&definition_name))
+        );
+        (definition, trace)
+    }
+
+    /// Finds the definition at `definition_path`, relative to the crate root, and substitutes the
+    /// generic arguments of its last segment. For example, `crate::Pair<String, T>` returns
+    /// `Pair<T>` with `String` substituted for its first parameter. See [substitute_generics].
+    ///
+    /// With [Flags::FOLLOW_TYPE_ALIASES], a path naming a type alias returns the definition
+    /// behind it, which must be of type `definition_type`.
+    pub fn get_definition_at_path(&self, flags: Flags, crate_name: Crate, definition_path: syn::Path,
+                                  definition_type: Definition) -> Result<Item, CargoQueryError> {
+        let segment_count = definition_path.segments.len();
+        if flags.contains(Flags::FOLLOW_TYPE_ALIASES) {
+            let name = definition_path.segments.last().map(|segment| segment.ident.clone());
+            let definition = self.resolve_type_aliases(&crate_name, definition_path)?.definition;
+            return match (Definition::get_definition_name(&definition, definition_type), name) {
+                (Some(_), _) => Ok(definition),
+                (None, Some(name)) => Err(CargoQueryError::could_not_find_definition(&name).at_path_segment(segment_count - 1)),
+                (None, None) => Err(CargoQueryError::could_not_find_module()),
+            }
+        }
+        let mut segments: Vec<PathSegment> = definition_path.segments.into_iter()
+            .skip_while(|segment| segment.ident == "crate")
+            .collect();
+        let skipped = segment_count - segments.len();
+        let definition = segments.pop().ok_or_else(CargoQueryError::could_not_find_module)?;
+        let module_path = syn::Path { leading_colon: None, segments: segments.into_iter().collect() };
+
+        let krate = self.crate_location(&crate_name)?;
+        let item = self.get_definition(flags, crate_name, module_path, definition.ident.clone(), definition_type)
+            .map_err(|e| e.offset_path_segment(skipped))?;
+        let resolver = Resolver { source_provider: self.source_provider(), dependencies: self };
+        substitute_generics(item, &definition.arguments, &|name| is_caller_parameter(&resolver, &krate, name))
+            .map_err(|e| e.at_path_segment(segment_count - 1))
+    }
+
+    /// Follows the type alias at `definition_path`, relative to the crate root, to the struct,
+    /// enum, union or trait behind it, substituting generic arguments along the way. A path
+    /// naming a definition directly resolves to it without any aliases. See
+    /// [type_alias](crate::r#impl::type_alias).
+    pub fn resolve_type_aliases(&self, crate_name: &Crate, definition_path: syn::Path) -> Result<AliasResolution, CargoQueryError> {
+        let segment_count = definition_path.segments.len();
+        let mut segments: Vec<PathSegment> = definition_path.segments.into_iter()
+            .skip_while(|segment| segment.ident == "crate")
+            .collect();
+        let skipped = segment_count - segments.len();
+        let definition = segments.pop().ok_or_else(CargoQueryError::could_not_find_module)?;
+        let module = segments.iter().map(|segment| segment.ident.to_string()).collect();
+
+        let resolver = Resolver { source_provider: self.source_provider(), dependencies: self };
+        resolve_type_aliases(&resolver, self.crate_location(crate_name)?, module, definition.ident, definition.arguments)
+            .map_err(|e| e.offset_path_segment(skipped))
+    }
+}
+
+/// Dependencies are resolved with the `cargo metadata` of the context, which names renamed
+/// dependencies by their new name. Without a resolve graph, the dependency table of the package
+/// is used instead.
+impl DependencyLookup for QueryContext {
+    fn dependency(&self, crate_path: &Path, name: &str) -> Option<CrateLocation> {
+        let metadata = self.metadata().ok()?;
+        let manifest_path = std::path::absolute(crate_path.join("Cargo.toml")).ok()?;
+        let package = metadata.packages.iter().find(|p| p.manifest_path.as_std_path() == manifest_path)?;
+        let resolved = metadata.resolve.as_ref()
+            .and_then(|resolve| resolve.nodes.iter().find(|node| node.id == package.id))
+            .map(|node| node.deps.iter().find(|dep| dep.name == name).map(|dep| &metadata[&dep.pkg]));
+        let dependency = match resolved {
+            Some(dependency) => dependency?,
+            None => {
+                let declared = package.dependencies.iter()
+                    .find(|d| d.rename.as_ref().unwrap_or(&d.name).replace('-', "_") == name)?;
+                metadata.packages.iter().find(|p| p.name == declared.name)?
+            }
+        };
+        Some(CrateLocation {
+            name: library_name(dependency),
+            path: dependency.manifest_path.parent()?.as_std_path().to_path_buf(),
+        })
+    }
+
+    fn enabled_features(&self, crate_path: &Path) -> Option<BTreeSet<String>> {
+        let metadata = self.metadata().ok()?;
+        let manifest_path = std::path::absolute(crate_path.join("Cargo.toml")).ok()?;
+        let package = metadata.packages.iter().find(|p| p.manifest_path.as_std_path() == manifest_path)?;
+        let node = metadata.resolve.as_ref()?.nodes.iter().find(|node| node.id == package.id)?;
+        Some(node.features.iter().cloned().collect())
+    }
+}
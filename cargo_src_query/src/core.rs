@@ -1,15 +1,21 @@
-use cargo_metadata::{CargoOpt, MetadataCommand};
-use cargo_metadata::camino::Utf8PathBuf;
-use quote::quote;
-use syn::{Ident, Item, PathSegment, Token};
-use syn::spanned::Spanned;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use log::trace;
+use proc_macro2::Span;
+use syn::{Ident, Item, PathSegment};
+use crate::context::QueryContext;
 use crate::errors::CargoQueryError;
 
 use crate::flags::Flags;
-use crate::r#impl::find_dependencies::Definition;
-use crate::r#impl::find_mod::find_mod_in_crate;
+use crate::helpers::module_path::ModulePath;
+use crate::r#impl::find_dependencies::{CrateLocation, Definition, DependencyLookup, find_definition_in_scope};
+use crate::r#impl::find_mod::find_mod_in_crate_traced;
+use crate::r#impl::include::unexpanded_include;
+use crate::r#impl::recurse_export::{flatten_use_tree, resolve_path_prefix, UsePath};
+use crate::r#impl::trace::{CandidateKind, Rejection, Tracer};
+use crate::source::SourceProvider;
 
-// TODO: Update this with correct environment variable
 /// Ref: [1] https://doc.rust-lang.org/cargo/reference/environment-variables.html#environment-variables-cargo-sets-for-build-scripts
 pub(crate) const CARGO_ENV_VAR: &str = "CARGO_MANIFEST_DIR";
 
@@ -25,55 +31,265 @@ pub struct Query {
     pub(crate) query_type: QueryType
 }
 
+impl Query {
+    pub fn crate_name(&self) -> &Crate {
+        &self.crate_name
+    }
+
+    pub fn module_path(&self) -> &syn::Path {
+        &self.module_path
+    }
+
+    pub fn query_type(&self) -> &QueryType {
+        &self.query_type
+    }
+}
+
 pub enum QueryType {
     Definition(Ident, Definition),
     Mod
 }
 
+/// Formats the queried path, e.g. `nested_in_lib::PublicExampleStruct`.
+impl Display for Query {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut segments: Vec<String> = self.module_path.segments.iter().map(|s| s.ident.to_string()).collect();
+        if let QueryType::Definition(name, _) = &self.query_type {
+            segments.push(name.to_string());
+        }
+        match &self.crate_name {
+            Crate::Internal => write!(f, "{}", segments.join("::")),
+            Crate::External(crate_name) => write!(f, "{crate_name}::{}", segments.join("::")),
+        }
+    }
+}
+
 // TODO: Implement batch search (multiple Items along same path)
 // TODO: Implement private edge case. example: `use syn::__private::TokenStream;`
-pub fn query_cargo_src_core(flags: Flags, query: Query) -> Result<Vec<Item>, CargoQueryError> {
+/// The syntactic resolver used by [SyntacticBackend](crate::backend::SyntacticBackend). Each
+/// candidate tried is recorded to `tracer`.
+pub(crate) fn query_cargo_src_core(context: &QueryContext, flags: Flags, query: Query, tracer: &Tracer) -> Result<Vec<Item>, CargoQueryError> {
     let Query { crate_name, module_path, query_type } = query;
 
-    // TODO: Is this env_var the correct way to go about this? Should we be using cargo_metadata instead?
-    let env_var = std::env::var(CARGO_ENV_VAR).unwrap();
-    let crate_src_path = match crate_name {
-        Crate::Internal => env_var,
-        Crate::External(src_crate_name) => get_cargo_metadata_src_path(src_crate_name)
-    };
-    dbg!(&crate_src_path);
-    let crate_src_path = std::path::Path::new(crate_src_path.as_str());
+    let crate_src_path = context.crate_path(&crate_name)?;
+    trace!("crate path: {crate_src_path:#?}");
 
-    // TODO: Implement logic
     match query_type {
-        QueryType::Definition(_, _) => unimplemented!(),
-        QueryType::Mod => find_mod_in_crate(crate_src_path, module_path.into())
+        QueryType::Definition(name, definition_type) => {
+            // The definition is resolved at the segment after the module path
+            let definition_segment = module_path.segments.len();
+            let module: Vec<String> = module_path.segments.iter().map(|s| s.ident.to_string()).collect();
+            let krate = context.crate_location(&crate_name)?;
+            let resolver = Resolver { source_provider: context.source_provider(), dependencies: context };
+            let definition = find_definition(&resolver, &krate, &module, &name, &[definition_type], tracer)
+                .map_err(|e| e.at_path_segment(definition_segment))?;
+            Ok(vec![definition.item])
+        },
+        QueryType::Mod => {
+            let items = find_mod_in_crate_traced(context.source_provider(), &crate_src_path, module_path.into(),
+                                                 context.enabled_features(&crate_src_path).as_ref(), tracer)?;
+            Ok(items.into_iter()
+                .filter(|item| !flags.contains(Flags::EXCLUDE_PRIVATE) || !is_private(item))
+                .collect())
+        }
     }
 }
 
+/// Whether an item is left out of module queries with [Flags::EXCLUDE_PRIVATE]: every item
+/// with a visibility other than `pub`, e.g. `pub(crate)`. Impls and macros have no visibility
+/// and are always kept.
+pub(crate) fn is_private(item: &Item) -> bool {
+    let visibility = match item {
+        Item::Const(i) => &i.vis,
+        Item::Enum(i) => &i.vis,
+        Item::ExternCrate(i) => &i.vis,
+        Item::Fn(i) => &i.vis,
+        Item::Mod(i) => &i.vis,
+        Item::Static(i) => &i.vis,
+        Item::Struct(i) => &i.vis,
+        Item::Trait(i) => &i.vis,
+        Item::TraitAlias(i) => &i.vis,
+        Item::Type(i) => &i.vis,
+        Item::Union(i) => &i.vis,
+        Item::Use(i) => &i.vis,
+        _ => return false,
+    };
+    !matches!(visibility, syn::Visibility::Public(_))
+}
+
+/// Follows re-exports within the queried crate and into its dependencies.
+pub(crate) struct Resolver<'a> {
+    pub source_provider: &'a dyn SourceProvider,
+    pub dependencies: &'a dyn DependencyLookup,
+}
+
+/// A definition and the module declaring it, which differs from the queried module when the
+/// definition was reached through re-exports.
+pub(crate) struct FoundDefinition {
+    pub item: Item,
+    pub krate: CrateLocation,
+    pub module: Vec<String>,
+}
+
+/// Finds the definition `name` of one of `definition_types` in `module` (segments relative to
+/// the root of `krate`). Definitions which are not declared in the module are searched for in
+/// its re-exports:
+/// 1. `pub use path::to::Name;` and `pub use path::to::Original as Name;` are followed
+/// 2. `pub use path::to::*;` is followed when exactly one glob re-exports the definition
+///
+/// Re-exports from other crates, e.g. `pub use another_crate::Name;`, are followed into the
+/// dependency named by the first segment. See [dependency_crate]. Each path the definition is
+/// reached through is recorded to the provenance of `tracer`.
+pub(crate) fn find_definition(resolver: &Resolver, krate: &CrateLocation, module: &[String], name: &Ident,
+                              definition_types: &[Definition], tracer: &Tracer) -> Result<FoundDefinition, CargoQueryError> {
+    find_reexported_definition(resolver, krate, module, name, definition_types, tracer, &mut BTreeSet::new())
+}
 
-fn get_cargo_metadata_src_path(src_crate: Ident) -> String {
-    let src_crate_name = &src_crate.to_string();
-    let _metadata = MetadataCommand::new()
-        .manifest_path("./Cargo.toml")
-        .features(CargoOpt::AllFeatures)
-        .exec()
-        .unwrap();
+/// The crate, module and name of every path a search has looked up.
+type Visited = BTreeSet<(PathBuf, Vec<String>, String)>;
 
-    // TODO: Can this be processed more efficiently?
-    let package =
-        _metadata.packages.iter().find(|&p| p.name.eq(src_crate_name));
-    let mut path = match package {
-        Some(p) => &p.manifest_path,
-        None => {
-            crate::errors::invalid_crate_name(src_crate_name.span().into(), &src_crate_name);
-            unreachable!("The macro aborts here")
+/// Searches [find_definition] without looking up any path twice, so that re-exports which
+/// re-export each other, e.g. `pub use self::a as b; pub use self::b as a;` or two modules
+/// glob importing each other, end the search. A path which is reached again either leads to a
+/// definition already found, or to none at all.
+fn find_reexported_definition(resolver: &Resolver, krate: &CrateLocation, module: &[String], name: &Ident,
+                              definition_types: &[Definition], tracer: &Tracer, visited: &mut Visited)
+    -> Result<FoundDefinition, CargoQueryError> {
+    if !visited.insert((krate.path.clone(), module.to_vec(), name.to_string())) {
+        return Err(CargoQueryError::could_not_find_definition(name))
+    }
+    let path: Vec<String> = std::iter::once(krate.name.clone()).chain(module.iter().cloned()).chain([name.to_string()]).collect();
+    tracer.provenance(path.join("::"));
+    let features = resolver.dependencies.enabled_features(&krate.path);
+    let scope = find_mod_in_crate_traced(resolver.source_provider, &krate.path, module_path(module).into(), features.as_ref(), tracer)?;
+
+    tracer.step(name);
+    if let Some(definition) = find_definition_in_scope(scope.clone(), definition_types, name) {
+        tracer.candidate(CandidateKind::Definition(name.to_string()), None);
+        return Ok(FoundDefinition { item: definition, krate: krate.clone(), module: module.to_vec() })
+    }
+    tracer.candidate(CandidateKind::Definition(name.to_string()), Some(Rejection::Missing));
+
+    let mut globs = vec![];
+    for item in &scope {
+        let Item::Use(item_use) = item else { continue };
+        let visible = !matches!(item_use.vis, syn::Visibility::Inherited);
+        let global = item_use.leading_colon.is_some();
+        for path in flatten_use_tree(&item_use.tree) {
+            if path.glob {
+                if visible { globs.push((path, global)) }
+                continue
+            }
+            if path.binding() != Some(&name.to_string()) { continue }
+            if !visible {
+                tracer.candidate(CandidateKind::Reexport(use_path_to_string(&path)), Some(Rejection::NotVisible));
+                continue
+            }
+            let Some((target_name, module_segments)) = path.segments.split_last() else { continue };
+            let Some((target_crate, target_module)) = use_path_module(resolver, krate, module, &scope, module_segments, global) else { continue };
+            follow_reexport(krate, &target_crate, &path, tracer);
+            return find_reexported_definition(resolver, &target_crate, &target_module, &ident(target_name), definition_types, tracer, visited)
+                .map_err(CargoQueryError::clear_path_segment)
         }
-    }.to_owned();
-    // Remove `Cargo.toml` from end of src_path
-    // Run `cargo metadata` and search for `manifest_path` to see an example of a path
-    path.pop();
-    path.into_string()
+    }
+
+    // Every glob is probed with its own trace, so that only the followed glob appears in the trace
+    let mut matching_globs: Vec<(UsePath, CrateLocation, FoundDefinition, Tracer)> = globs.into_iter().filter_map(|(path, global)| {
+        let (target_crate, target_module) = use_path_module(resolver, krate, module, &scope, &path.segments, global)?;
+        let probe = tracer.probe();
+        find_reexported_definition(resolver, &target_crate, &target_module, name, definition_types, &probe, visited)
+            .ok().map(|found| (path, target_crate, found, probe))
+    }).collect();
+    match matching_globs.len() {
+        // The definition may have been in a file `include!` couldn't expand
+        0 => Err(unexpanded_include(&scope).unwrap_or_else(|| CargoQueryError::could_not_find_definition(name))),
+        1 => {
+            let (path, target_crate, found, probe) = matching_globs.remove(0);
+            follow_reexport(krate, &target_crate, &path, tracer);
+            tracer.append(probe);
+            Ok(found)
+        },
+        _ => Err(CargoQueryError::ambiguous_glob_export(
+            name, matching_globs.iter().map(|(path, _, _, _)| use_path_to_string(path)).collect()
+        )),
+    }
+}
+
+fn follow_reexport(krate: &CrateLocation, target_crate: &CrateLocation, path: &UsePath, tracer: &Tracer) {
+    tracer.candidate(CandidateKind::Reexport(use_path_to_string(path)), None);
+    if target_crate != krate {
+        tracer.candidate(CandidateKind::Dependency(target_crate.name.clone()), None);
+    }
+    tracer.followed_reexport(use_path_to_string(path));
+}
+
+/// Resolves the module a `use` path within `module` refers to, which can be in another crate.
+/// `scope` holds the items of `module`, and `global` is set for paths starting with `::`.
+pub(crate) fn use_path_module(resolver: &Resolver, krate: &CrateLocation, module: &[String], scope: &[Item], segments: &[String],
+                   global: bool) -> Option<(CrateLocation, Vec<String>)> {
+    if let Some(dependency) = segments.first().and_then(|first| dependency_crate(resolver, krate, module, scope, first, global)) {
+        return Some((dependency, segments[1..].to_vec()))
+    }
+    if global { return None }
+    let (base, rest) = resolve_path_prefix(module, segments)?;
+    Some((krate.clone(), base.into_iter().chain(rest).collect()))
+}
+
+/// The crate the first segment of a `use` path names, if it doesn't name a module of `krate`:
+/// 1. `::name` always names a crate
+/// 2. `extern crate original as name;` in the module or the crate root aliases `original`, and
+///    `extern crate self as name;` aliases `krate` itself
+/// 3. Any other name which isn't declared with `mod name` in the module may name a dependency
+fn dependency_crate(resolver: &Resolver, krate: &CrateLocation, module: &[String], scope: &[Item], first: &str,
+                    global: bool) -> Option<CrateLocation> {
+    if !global && (["crate", "self", "super"].contains(&first)
+        || scope.iter().any(|item| matches!(item, Item::Mod(m) if m.ident == first))) {
+        return None
+    }
+    let alias = extern_crate_alias(scope, first).or_else(|| {
+        if module.is_empty() { return None }
+        let features = resolver.dependencies.enabled_features(&krate.path);
+        let root = find_mod_in_crate_traced(resolver.source_provider, &krate.path, ModulePath::default(), features.as_ref(),
+                                            &Tracer::disabled()).ok()?;
+        extern_crate_alias(&root, first)
+    });
+    match alias.as_deref() {
+        Some("self") => Some(krate.clone()),
+        Some(original) => resolver.dependencies.dependency(&krate.path, original),
+        None => resolver.dependencies.dependency(&krate.path, first),
+    }
+}
+
+/// The crate `extern crate original as name;` binds `name` to.
+fn extern_crate_alias(scope: &[Item], name: &str) -> Option<String> {
+    scope.iter().find_map(|item| match item {
+        Item::ExternCrate(extern_crate) => {
+            let binding = extern_crate.rename.as_ref().map_or(&extern_crate.ident, |(_, rename)| rename);
+            (binding == name).then(|| extern_crate.ident.to_string())
+        },
+        _ => None
+    })
+}
+
+pub(crate) fn module_path(segments: &[String]) -> syn::Path {
+    syn::Path {
+        leading_colon: None,
+        segments: segments.iter().map(|s| PathSegment::from(ident(s))).collect(),
+    }
+}
+
+pub(crate) fn ident(name: &str) -> Ident {
+    match name.strip_prefix("r#") {
+        Some(raw) => Ident::new_raw(raw, Span::call_site()),
+        None => Ident::new(name, Span::call_site()),
+    }
+}
+
+fn use_path_to_string(path: &UsePath) -> String {
+    let mut rendered = path.segments.join("::");
+    if path.glob { rendered.push_str("::*") }
+    if let Some(rename) = &path.rename { rendered.push_str(&format!(" as {rename}")) }
+    rendered
 }
 
 #[cfg(test)]
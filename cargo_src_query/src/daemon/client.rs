@@ -0,0 +1,151 @@
+//! The macro side of the socket. A [DaemonClient] sends queries to a running daemon, starts the
+//! daemon when it isn't running, and falls back to querying in-process when the daemon can't
+//! be reached or can't rebuild the context. See [QueryContext::daemon_config].
+
+use std::io::BufReader;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use log::{debug, warn};
+use syn::{Ident, Item};
+use crate::context::QueryContext;
+use crate::core::Crate;
+use crate::daemon::{DAEMON_EXECUTABLE_ENV_VAR, default_socket_path};
+use crate::daemon::protocol::{crate_to_string, path_to_string, protocol_error, read_message, Request, Response, write_message};
+use crate::errors::CargoQueryError;
+use crate::flags::Flags;
+use crate::r#impl::find_dependencies::Definition;
+
+/// Name of the daemon executable, looked up on `PATH` when [DAEMON_EXECUTABLE_ENV_VAR] is not set.
+pub const DAEMON_EXECUTABLE: &str = "cargo_src_query_daemon";
+
+/// How long to wait for a freshly started daemon to bind its socket.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct DaemonClient {
+    socket_path: PathBuf,
+    /// Start the daemon when the socket is missing
+    autostart: bool,
+}
+
+impl Default for DaemonClient {
+    fn default() -> Self {
+        Self::new(default_socket_path())
+    }
+}
+
+impl DaemonClient {
+    pub fn new(socket_path: impl Into<PathBuf>) -> Self {
+        DaemonClient { socket_path: socket_path.into(), autostart: true }
+    }
+
+    /// Disables starting the daemon when it isn't running. Queries are then answered in-process
+    /// until a daemon is started by other means.
+    pub fn without_autostart(mut self) -> Self {
+        self.autostart = false;
+        self
+    }
+
+    /// Queries the daemon for the contents of a module. See [QueryContext::get_module].
+    pub fn get_module(&self, context: &QueryContext, flags: Flags, crate_name: Crate, module_path: syn::Path)
+        -> Result<Vec<Item>, CargoQueryError> {
+        let Some(config) = context.daemon_config() else {
+            return context.get_module(flags, crate_name, module_path)
+        };
+        let request = Request::Module {
+            context: config,
+            crate_name: crate_to_string(&crate_name),
+            module_path: path_to_string(&module_path),
+            flags: flags.bits(),
+        };
+        match self.send(&request) {
+            Some(response) => {
+                context.record_reads(&response.files());
+                response.into_items()
+            },
+            None => context.get_module(flags, crate_name, module_path),
+        }
+    }
+
+    /// Queries the daemon for a definition. See [QueryContext::get_definition].
+    pub fn get_definition(&self, context: &QueryContext, flags: Flags, crate_name: Crate, module_path: syn::Path,
+                          definition_name: Ident, definition_type: Definition) -> Result<Item, CargoQueryError> {
+        let Some(config) = context.daemon_config() else {
+            return context.get_definition(flags, crate_name, module_path, definition_name, definition_type)
+        };
+        let request = Request::Definition {
+            context: config,
+            crate_name: crate_to_string(&crate_name),
+            module_path: path_to_string(&module_path),
+            definition_name: definition_name.to_string(),
+            definition_type,
+            flags: flags.bits(),
+        };
+        match self.send(&request) {
+            Some(response) => {
+                context.record_reads(&response.files());
+                response.into_items()?.pop()
+                    .ok_or_else(|| CargoQueryError::could_not_find_definition(&definition_name))
+            },
+            None => context.get_definition(flags, crate_name, module_path, definition_name, definition_type),
+        }
+    }
+
+    /// Asks the daemon to shut down. Does nothing when the daemon isn't running.
+    pub fn shutdown(&self) -> Result<(), CargoQueryError> {
+        match self.connect() {
+            Some(stream) => self.exchange(stream, &Request::Shutdown).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends a request to the daemon, starting it if needed. Returns `None` when the daemon
+    /// can't be reached, in which case the query should be answered in-process.
+    fn send(&self, request: &Request) -> Option<Response> {
+        let stream = self.connect().or_else(|| if self.autostart { self.start() } else { None })?;
+        match self.exchange(stream, request) {
+            Ok(response) => Some(response),
+            Err(e) => {
+                warn!("cargo_src_query daemon failed to answer, querying in-process: {}", e.top_level_message());
+                None
+            }
+        }
+    }
+
+    fn exchange(&self, stream: UnixStream, request: &Request) -> Result<Response, CargoQueryError> {
+        let mut writer = stream.try_clone().map_err(|e| protocol_error(e.to_string()))?;
+        write_message(&mut writer, request).map_err(|e| protocol_error(e.to_string()))?;
+        read_message(&mut BufReader::new(stream))
+            .map_err(|e| protocol_error(e.to_string()))?
+            .ok_or_else(|| protocol_error("The daemon closed the connection without responding.".to_string()))
+    }
+
+    fn connect(&self) -> Option<UnixStream> {
+        UnixStream::connect(&self.socket_path).ok()
+    }
+
+    /// Starts the daemon in the background and waits for it to bind the socket.
+    fn start(&self) -> Option<UnixStream> {
+        let executable = std::env::var_os(DAEMON_EXECUTABLE_ENV_VAR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DAEMON_EXECUTABLE));
+        debug!("starting cargo_src_query daemon {executable:?} on {:?}", self.socket_path);
+        if let Err(e) = Command::new(&executable)
+            .arg("--socket").arg(&self.socket_path)
+            .stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null())
+            .spawn() {
+            debug!("could not start cargo_src_query daemon {executable:?}: {e}");
+            return None
+        }
+
+        let started = Instant::now();
+        while started.elapsed() < STARTUP_TIMEOUT {
+            if let Some(stream) = self.connect() {
+                return Some(stream)
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        None
+    }
+}
@@ -0,0 +1,41 @@
+//! # Query Daemon
+//! Each procedural macro invocation runs in a fresh context, so every invocation would normally
+//! reload `cargo metadata` and re-read the same source files. The daemon is a long-running
+//! process which owns a warm [QueryContext](crate::context::QueryContext) per manifest and a
+//! shared source cache, and serves queries over a local Unix domain socket.
+//!
+//! ```text
+//! macro invocation ─┐
+//! macro invocation ─┼─ DaemonClient ── unix socket ── Daemon ── QueryContext (per manifest)
+//! macro invocation ─┘        │                                       └── Cached sources
+//!                            └── in-process fallback when the socket is missing
+//! ```
+//!
+//! Messages are newline delimited JSON. See [protocol].
+
+pub mod client;
+pub mod protocol;
+pub mod server;
+
+use std::path::PathBuf;
+use crate::core::CARGO_ENV_VAR;
+use crate::logger::target_directory;
+
+/// Overrides the socket the daemon listens on and the client connects to.
+pub const SOCKET_ENV_VAR: &str = "CARGO_SRC_QUERY_SOCKET";
+/// Overrides the executable the client starts when the daemon isn't running.
+pub const DAEMON_EXECUTABLE_ENV_VAR: &str = "CARGO_SRC_QUERY_DAEMON";
+
+/// One socket is shared by every crate, so all macro expansions in a workspace build share the
+/// same daemon. The socket is created in `$XDG_RUNTIME_DIR`, which only the current user can
+/// access, or else in the target directory of the crate being compiled.
+pub fn default_socket_path() -> PathBuf {
+    if let Some(socket_path) = std::env::var_os(SOCKET_ENV_VAR) {
+        return PathBuf::from(socket_path)
+    }
+    if let Some(runtime_directory) = std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+        return PathBuf::from(runtime_directory).join("cargo_src_query.sock")
+    }
+    let manifest_dir = std::env::var_os(CARGO_ENV_VAR).map(PathBuf::from).unwrap_or_default();
+    target_directory(&manifest_dir).join("cargo_src_query.sock")
+}
@@ -0,0 +1,228 @@
+//! Messages exchanged between the [client](super::client) and the [server](super::server). Each
+//! message is a single line of JSON.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use cargo_metadata::{CargoOpt, Metadata};
+use proc_macro2::Span;
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use syn::{Ident, Item};
+use crate::backend::{BackendKind, QueryBackend};
+use crate::context::{MetadataSource, QueryContext, QueryContextBuilder};
+use crate::core::Crate;
+use crate::errors::{CargoQueryError, ErrorKind};
+use crate::r#impl::find_dependencies::Definition;
+use crate::source::SourceProvider;
+use crate::tracking::TrackedFiles;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Checks whether the daemon is running
+    Ping,
+    Module {
+        context: ContextConfig,
+        /// `None` for the crate defined by the context's manifest path
+        crate_name: Option<String>,
+        module_path: String,
+        flags: u32,
+    },
+    Definition {
+        context: ContextConfig,
+        crate_name: Option<String>,
+        module_path: String,
+        definition_name: String,
+        definition_type: Definition,
+        flags: u32,
+    },
+    Shutdown,
+}
+
+/// Everything a [QueryContext] is built from, apart from its source provider, since the daemon
+/// always reads sources from the file system. See [QueryContext::daemon_config].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    pub manifest_path: PathBuf,
+    pub features: Vec<Features>,
+    pub target: Option<String>,
+    pub profile: String,
+    pub backend: BackendKind,
+    /// Precomputed `cargo metadata`, or `None` to run `cargo metadata` in the daemon
+    pub metadata: Option<Box<Metadata>>,
+    pub reproducer: bool,
+}
+
+impl ContextConfig {
+    /// Starts building the context described by this configuration.
+    pub fn builder(self, source_provider: Arc<dyn SourceProvider>, backend: Arc<dyn QueryBackend>) -> QueryContextBuilder {
+        let mut builder = QueryContext::builder(self.manifest_path)
+            .profile(self.profile)
+            .reproducer(self.reproducer)
+            .source_provider(source_provider)
+            .backend(backend);
+        for features in self.features {
+            builder = builder.features(features.into());
+        }
+        if let Some(target) = self.target {
+            builder = builder.target(target);
+        }
+        if let Some(metadata) = self.metadata {
+            builder = builder.metadata_source(MetadataSource::Precomputed(metadata));
+        }
+        builder
+    }
+}
+
+/// The features passed to `cargo metadata`. See [CargoOpt].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Features {
+    All,
+    NoDefault,
+    Some(Vec<String>),
+}
+
+impl From<&CargoOpt> for Features {
+    fn from(features: &CargoOpt) -> Self {
+        match features {
+            CargoOpt::AllFeatures => Features::All,
+            CargoOpt::NoDefaultFeatures => Features::NoDefault,
+            CargoOpt::SomeFeatures(features) => Features::Some(features.clone()),
+        }
+    }
+}
+
+impl From<Features> for CargoOpt {
+    fn from(features: Features) -> Self {
+        match features {
+            Features::All => CargoOpt::AllFeatures,
+            Features::NoDefault => CargoOpt::NoDefaultFeatures,
+            Features::Some(features) => CargoOpt::SomeFeatures(features),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    /// Queried items, rendered as Rust source
+    Items {
+        items: Vec<String>,
+        /// The files read while answering, see [tracking](crate::tracking)
+        #[serde(default)]
+        files: Vec<PathBuf>,
+    },
+    Error {
+        kind: ErrorKind,
+        top_level_message: String,
+        indexed_messages: BTreeMap<String, Vec<String>>,
+        path_segment: Option<usize>,
+        #[serde(default)]
+        files: Vec<PathBuf>,
+    },
+}
+
+impl From<Result<Vec<Item>, CargoQueryError>> for Response {
+    fn from(result: Result<Vec<Item>, CargoQueryError>) -> Self {
+        match result {
+            Ok(items) => Response::Items {
+                items: items.iter().map(|item| item.to_token_stream().to_string()).collect(),
+                files: Vec::new(),
+            },
+            Err(e) => Response::Error {
+                kind: e.kind(),
+                top_level_message: e.top_level_message().to_string(),
+                indexed_messages: e.indexed_messages().clone(),
+                path_segment: e.path_segment(),
+                files: Vec::new(),
+            }
+        }
+    }
+}
+
+impl Response {
+    /// Attaches the files read while answering the request.
+    pub fn with_files(mut self, tracked: TrackedFiles) -> Self {
+        if let Response::Items { files, .. } | Response::Error { files, .. } = &mut self {
+            *files = tracked.paths().map(Path::to_path_buf).collect();
+        }
+        self
+    }
+
+    /// The files the daemon read while answering the request.
+    pub fn files(&self) -> TrackedFiles {
+        match self {
+            Response::Items { files, .. } | Response::Error { files, .. } => files.iter().cloned().collect(),
+            Response::Ok => TrackedFiles::default(),
+        }
+    }
+
+    pub fn into_items(self) -> Result<Vec<Item>, CargoQueryError> {
+        match self {
+            Response::Items { items, .. } => items.iter()
+                .map(|item| syn::parse_str(item).map_err(|e| protocol_error(e.to_string())))
+                .collect(),
+            Response::Error { kind, top_level_message, indexed_messages, path_segment, .. } => {
+                let error = CargoQueryError::convert_error(kind, top_level_message, indexed_messages);
+                Err(match path_segment {
+                    Some(index) => error.at_path_segment(index),
+                    None => error,
+                })
+            },
+            Response::Ok => Err(protocol_error("Expected items but the daemon responded with `ok`.".to_string())),
+        }
+    }
+}
+
+pub fn crate_to_string(crate_name: &Crate) -> Option<String> {
+    match crate_name {
+        Crate::Internal => None,
+        Crate::External(name) => Some(name.to_string()),
+    }
+}
+
+pub fn crate_from_string(crate_name: Option<String>) -> Crate {
+    match crate_name {
+        None => Crate::Internal,
+        Some(name) => Crate::External(Ident::new(&name, Span::call_site())),
+    }
+}
+
+pub fn path_to_string(path: &syn::Path) -> String {
+    path.to_token_stream().to_string()
+}
+
+pub fn path_from_string(path: &str) -> Result<syn::Path, CargoQueryError> {
+    if path.trim().is_empty() {
+        return Ok(syn::Path { leading_colon: None, segments: Default::default() })
+    }
+    syn::parse_str(path).map_err(|e| protocol_error(e.to_string()))
+}
+
+/// Writes a message as a single line of JSON.
+pub fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *writer, message)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Reads a single line of JSON. Returns `None` when the connection was closed.
+pub fn read_message<T: for<'de> Deserialize<'de>>(reader: &mut impl BufRead) -> std::io::Result<Option<T>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None)
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+pub fn protocol_error(message: String) -> CargoQueryError {
+    CargoQueryError::convert_error(
+        ErrorKind::DaemonUnavailable,
+        "Invalid message exchanged with the cargo_src_query daemon.".to_string(),
+        maplit::btreemap! { "daemon error".to_string() => vec![message] },
+    )
+}
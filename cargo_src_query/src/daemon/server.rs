@@ -0,0 +1,298 @@
+//! The daemon side of the socket. A single [Daemon] serves every crate in a workspace: a
+//! [QueryContext] is kept per [ContextConfig] so `cargo metadata` is only loaded once per
+//! manifest, features and target, and every context shares a single source cache. Every
+//! directory a source was read from is watched, so edits to workspace members, dependencies,
+//! `#[path]` modules and build script outputs are picked up by the next query.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::BufReader;
+use std::fs::DirBuilder;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::{debug, error, info, warn};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use syn::Item;
+use crate::backend::{BackendKind, QueryBackend, SyntacticBackend};
+use crate::context::QueryContext;
+use crate::core::Crate;
+use crate::daemon::protocol::{ContextConfig, crate_from_string, path_from_string, protocol_error, read_message, Request, Response, write_message};
+use crate::errors::{CargoQueryError, ErrorKind};
+use crate::flags::Flags;
+use crate::r#impl::invalidation::{DependencyGraph, Invalidator};
+use crate::source::{Cached, FileSystem};
+use crate::tracking::TrackedFiles;
+
+/// Every context, with the invalidator holding the dependency graph of its crate.
+type Invalidators = Mutex<Vec<(Arc<QueryContext>, Arc<Invalidator>)>>;
+
+pub struct Daemon {
+    /// Contexts keyed by their [ContextConfig], serialized
+    contexts: Mutex<BTreeMap<String, Arc<QueryContext>>>,
+    /// Notified of every change to a watched file
+    invalidators: Arc<Invalidators>,
+    /// Watches the directories in `watched`. `None` when the platform's watcher is unavailable.
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    watched: Mutex<BTreeSet<PathBuf>>,
+    sources: Arc<Cached>,
+    /// Shared by every context, so that each workspace is only loaded once
+    #[cfg(feature = "rust-analyzer")]
+    rust_analyzer: Arc<crate::backend::RustAnalyzerBackend>,
+    threads: Option<usize>,
+    shutdown: AtomicBool,
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Daemon {
+    /// Creates a daemon which indexes crates with at most `threads` threads.
+    pub fn new(threads: Option<usize>) -> Self {
+        let invalidators: Arc<Invalidators> = Default::default();
+        let watcher = {
+            let invalidators = invalidators.clone();
+            notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+                Ok(event) => invalidate(&invalidators, &event),
+                Err(e) => warn!("file watcher error: {e}"),
+            })
+        };
+        Daemon {
+            contexts: Default::default(),
+            invalidators,
+            watcher: Mutex::new(watcher.map_err(|e| warn!("could not watch sources, they will not be invalidated: {e}")).ok()),
+            watched: Default::default(),
+            sources: Arc::new(Cached::new(Arc::new(FileSystem))),
+            #[cfg(feature = "rust-analyzer")]
+            rust_analyzer: Default::default(),
+            threads,
+            shutdown: AtomicBool::new(false),
+        }
+    }
+
+    /// The source cache shared by every query served by this daemon.
+    pub fn sources(&self) -> &Arc<Cached> {
+        &self.sources
+    }
+
+    /// Returns the warm context for `config`, creating it on first use. The crate of a new
+    /// context is indexed after the context is published, so that queries for other contexts
+    /// aren't blocked while it is indexed.
+    pub fn context(&self, config: ContextConfig) -> Result<Arc<QueryContext>, CargoQueryError> {
+        let key = serde_json::to_string(&config).map_err(|e| protocol_error(e.to_string()))?;
+        let context = {
+            let mut contexts = self.contexts.lock().expect("Daemon contexts are poisoned");
+            if let Some(context) = contexts.get(&key) {
+                return Ok(context.clone())
+            }
+            info!("creating query context for {:?}", config.manifest_path);
+            let backend = self.backend(config.backend)?;
+            let mut builder = config.builder(self.sources.clone(), backend);
+            if let Some(threads) = self.threads {
+                builder = builder.threads(threads);
+            }
+            let context = Arc::new(builder.build());
+            contexts.insert(key, context.clone());
+            context
+        };
+        self.track_structure(&context);
+        Ok(context)
+    }
+
+    fn backend(&self, kind: BackendKind) -> Result<Arc<dyn QueryBackend>, CargoQueryError> {
+        match kind {
+            BackendKind::Syntactic => Ok(Arc::new(SyntacticBackend)),
+            #[cfg(feature = "rust-analyzer")]
+            BackendKind::RustAnalyzer => Ok(self.rust_analyzer.clone()),
+            #[cfg(not(feature = "rust-analyzer"))]
+            BackendKind::RustAnalyzer => Err(CargoQueryError::feature_disabled(
+                "rust-analyzer", "the daemon was built without the rust-analyzer backend"
+            )),
+        }
+    }
+
+    /// Indexes the context's crate, so that changes to its module hierarchy can be detected.
+    fn track_structure(&self, context: &Arc<QueryContext>) {
+        let graph = context.index_crate(&Crate::Internal)
+            .map(|index| DependencyGraph::from_index(&index))
+            .unwrap_or_default();
+        let invalidator = Arc::new(Invalidator::new(self.sources.clone(), graph));
+        self.invalidators.lock().expect("Daemon invalidators are poisoned").push((context.clone(), invalidator));
+        self.watch_read_directories();
+    }
+
+    /// Watches the directory of every source read so far, including those of other workspace
+    /// members, dependencies, `#[path]` modules and `OUT_DIR`.
+    fn watch_read_directories(&self) {
+        let mut watcher = self.watcher.lock().expect("Daemon watcher is poisoned");
+        let Some(watcher) = watcher.as_mut() else { return };
+        let mut watched = self.watched.lock().expect("Daemon watched directories are poisoned");
+        for file_path in self.sources.paths() {
+            let Some(directory) = file_path.parent() else { continue };
+            if !watched.insert(directory.to_path_buf()) { continue }
+            debug!("watching {directory:?}");
+            if let Err(e) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+                warn!("could not watch {directory:?}, sources will not be invalidated: {e}");
+            }
+        }
+    }
+
+    /// Binds `socket_path` and serves connections until a [Request::Shutdown] is received.
+    /// Only the current user can connect to the socket. A stale socket left behind by a previous
+    /// daemon is replaced, but any other file at `socket_path` is left alone.
+    pub fn serve(self: Arc<Self>, socket_path: &Path) -> std::io::Result<()> {
+        if let Ok(metadata) = std::fs::symlink_metadata(socket_path) {
+            if metadata.file_type().is_socket() && UnixStream::connect(socket_path).is_err() {
+                std::fs::remove_file(socket_path)?;
+            }
+        }
+        let directory = socket_path.parent().filter(|directory| !directory.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        std::fs::create_dir_all(directory)?;
+        let listener = bind_private(directory, socket_path)?;
+        self.serve_until_shutdown(listener, Some(socket_path.to_path_buf()))?;
+        std::fs::remove_file(socket_path)
+    }
+
+    /// Serves connections from an already bound listener until a [Request::Shutdown] is received.
+    /// Each connection is served on its own thread.
+    pub fn serve_listener(self: Arc<Self>, listener: UnixListener) -> std::io::Result<()> {
+        let wake_path = listener.local_addr()?.as_pathname().map(Path::to_path_buf);
+        self.serve_until_shutdown(listener, wake_path)
+    }
+
+    /// Serves connections until a [Request::Shutdown] is received, after which the accept loop
+    /// is woken up by connecting to `wake_path`, the path the listener is reachable at.
+    fn serve_until_shutdown(self: Arc<Self>, listener: UnixListener, wake_path: Option<PathBuf>) -> std::io::Result<()> {
+        info!("cargo_src_query daemon listening on {wake_path:?}");
+        for stream in listener.incoming() {
+            if self.shutdown.load(Ordering::SeqCst) {
+                break
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!("could not accept daemon connection: {e}");
+                    continue
+                }
+            };
+            let daemon = self.clone();
+            let wake_path = wake_path.clone();
+            std::thread::spawn(move || match daemon.handle_connection(stream) {
+                Ok(true) => {
+                    info!("cargo_src_query daemon shutting down");
+                    daemon.shutdown.store(true, Ordering::SeqCst);
+                    // Wake up the accept loop so it can observe the shutdown
+                    if let Some(wake_path) = wake_path {
+                        let _ = UnixStream::connect(wake_path);
+                    }
+                },
+                Ok(false) => {},
+                Err(e) => error!("daemon connection failed: {e}"),
+            });
+        }
+        Ok(())
+    }
+
+    /// Serves every request on a connection. Returns whether the daemon should shut down.
+    fn handle_connection(&self, stream: UnixStream) -> std::io::Result<bool> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        while let Some(request) = read_message::<Request>(&mut reader)? {
+            debug!("daemon request: {request:?}");
+            let shutdown = matches!(request, Request::Shutdown);
+            write_message(&mut writer, &self.handle_request(request))?;
+            if shutdown {
+                return Ok(true)
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn handle_request(&self, request: Request) -> Response {
+        let response = self.answer(request);
+        self.watch_read_directories();
+        response
+    }
+
+    fn answer(&self, request: Request) -> Response {
+        match request {
+            Request::Ping | Request::Shutdown => Response::Ok,
+            Request::Module { context, crate_name, module_path, flags } => {
+                let result = path_from_string(&module_path).and_then(|module_path| {
+                    let context = self.context(context)?;
+                    let (result, files) = context.tracked(|context|
+                        context.get_module(flags_from_bits(flags), crate_from_string(crate_name), module_path)
+                    );
+                    Ok((result, files))
+                });
+                respond(result)
+            },
+            Request::Definition { context, crate_name, module_path, definition_name, definition_type, flags } => {
+                let result = path_from_string(&module_path).and_then(|module_path| {
+                    let definition_name = syn::parse_str(&definition_name)
+                        .map_err(|e| CargoQueryError::convert_error(ErrorKind::ParseFailure, e.to_string(), Default::default()))?;
+                    let context = self.context(context)?;
+                    let (result, files) = context.tracked(|context|
+                        context.get_definition(flags_from_bits(flags), crate_from_string(crate_name),
+                                               module_path, definition_name, definition_type)
+                            .map(|item| vec![item])
+                    );
+                    Ok((result, files))
+                });
+                respond(result)
+            },
+        }
+    }
+}
+
+/// Binds a socket within a new directory of `directory` which only the current user can enter,
+/// restricts the socket to the current user, and only then moves it to `socket_path`. Binding
+/// at `socket_path` directly would leave the socket open to every user until its permissions
+/// are changed.
+fn bind_private(directory: &Path, socket_path: &Path) -> std::io::Result<UnixListener> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.subsec_nanos());
+    let private_directory = directory.join(format!(".cargo_src_query_{}_{nanos}", std::process::id()));
+    DirBuilder::new().mode(0o700).create(&private_directory)?;
+    let bound_path = private_directory.join("daemon.sock");
+    let listener = UnixListener::bind(&bound_path).and_then(|listener| {
+        std::fs::set_permissions(&bound_path, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&bound_path, socket_path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_dir_all(&private_directory);
+    listener
+}
+
+/// Answers with the queried items and the files read while querying them. Errors which occur
+/// before querying, e.g. while building the context, are answered without files.
+fn respond(result: Result<(Result<Vec<Item>, CargoQueryError>, TrackedFiles), CargoQueryError>) -> Response {
+    match result {
+        Ok((result, files)) => Response::from(result).with_files(files),
+        Err(e) => Err(e).into(),
+    }
+}
+
+/// Drops the changed files from the source cache, and re-indexes every crate whose module
+/// hierarchy may have changed.
+fn invalidate(invalidators: &Invalidators, event: &Event) {
+    let invalidators = invalidators.lock().expect("Daemon invalidators are poisoned").clone();
+    for (context, invalidator) in invalidators {
+        let invalidations = invalidator.invalidate_event(event);
+        for invalidation in &invalidations {
+            debug!("invalidated modules of {:?}: {:?}", context.manifest_path(), invalidation.modules);
+        }
+        if !invalidations.iter().any(|invalidation| invalidation.structural) { continue }
+        if let Ok(index) = context.index_crate(&Crate::Internal) {
+            invalidator.set_graph(DependencyGraph::from_index(&index));
+        }
+    }
+}
+
+fn flags_from_bits(bits: u32) -> Flags {
+    Flags::from_bits_truncate(bits)
+}
@@ -32,11 +32,14 @@ use std::backtrace::Backtrace;
 
 use std::collections::BTreeMap;
 use std::error::Error;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
+use log::trace;
 use maplit::btreemap;
+use proc_macro_error::{Diagnostic, Level};
 use proc_macro2::Span;
 use proc_macro2::Ident;
 use quote::ToTokens;
+use serde::{Deserialize, Serialize};
 use syn::PathSegment;
 
 pub fn invalid_crate_name(span: Span, crate_name: &String) {
@@ -56,11 +59,17 @@ pub fn invalid_crate_name(span: Span, crate_name: &String) {
 /// [1] https://docs.rs/proc-macro-error/latest/proc_macro_error/#note-attachments
 #[derive(Debug)]
 pub struct CargoQueryError {
+    /// Stable classification of the error, see [ErrorKind]
+    kind: ErrorKind,
     /// Overarching message displayed to the user to indicate the general problem
     top_level_message: String,
     /// Context clues on what the root cause of the problem may be. These messages
     /// are populated at varying scopes in the lifetime of the library.
     indexed_messages: BTreeMap<String, Vec<String>>,
+    /// Index of the segment of the queried path where resolution failed. Spans can't be sent
+    /// between threads, so the span is looked up from the user's path when converting into a
+    /// [Diagnostic].
+    path_segment: Option<usize>,
     /// Backtrace of error generated within the scope of this library
     #[cfg(feature = "backtrace")]
     backtrace: Backtrace
@@ -74,10 +83,141 @@ pub mod message_identifier {
     pub const MODULE_PATH: &str = "module path";
 }
 
+/// Stable classification of a [CargoQueryError]. Codes are never reused or renumbered, so they
+/// can be matched on, searched for and explained with `cargo_src_query --explain CSQ0001`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// `CSQ0001`
+    ModuleNotFound,
+    /// `CSQ0002`
+    FileUnreadable,
+    /// `CSQ0003`
+    AmbiguousGlob,
+    /// `CSQ0004`
+    DefinitionNotFound,
+    /// `CSQ0005`
+    CrateNotFound,
+    /// `CSQ0006`
+    ExportNotFound,
+    /// `CSQ0007`
+    InvalidGenericArguments,
+    /// `CSQ0008`
+    ManifestUnavailable,
+    /// `CSQ0009`
+    ParseFailure,
+    /// `CSQ0010`
+    BackendUnavailable,
+    /// `CSQ0011`
+    DaemonUnavailable,
+    /// `CSQ0012`
+    Internal,
+    /// `CSQ0013`
+    FeatureDisabled,
+    /// `CSQ0014`
+    NotPubliclyReachable,
+    /// `CSQ0015`
+    BuildScriptNotRun,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 15] = [
+        ErrorKind::ModuleNotFound, ErrorKind::FileUnreadable, ErrorKind::AmbiguousGlob,
+        ErrorKind::DefinitionNotFound, ErrorKind::CrateNotFound, ErrorKind::ExportNotFound,
+        ErrorKind::InvalidGenericArguments, ErrorKind::ManifestUnavailable, ErrorKind::ParseFailure,
+        ErrorKind::BackendUnavailable, ErrorKind::DaemonUnavailable, ErrorKind::Internal,
+        ErrorKind::FeatureDisabled, ErrorKind::NotPubliclyReachable, ErrorKind::BuildScriptNotRun,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::ModuleNotFound => "CSQ0001",
+            ErrorKind::FileUnreadable => "CSQ0002",
+            ErrorKind::AmbiguousGlob => "CSQ0003",
+            ErrorKind::DefinitionNotFound => "CSQ0004",
+            ErrorKind::CrateNotFound => "CSQ0005",
+            ErrorKind::ExportNotFound => "CSQ0006",
+            ErrorKind::InvalidGenericArguments => "CSQ0007",
+            ErrorKind::ManifestUnavailable => "CSQ0008",
+            ErrorKind::ParseFailure => "CSQ0009",
+            ErrorKind::BackendUnavailable => "CSQ0010",
+            ErrorKind::DaemonUnavailable => "CSQ0011",
+            ErrorKind::Internal => "CSQ0012",
+            ErrorKind::FeatureDisabled => "CSQ0013",
+            ErrorKind::NotPubliclyReachable => "CSQ0014",
+            ErrorKind::BuildScriptNotRun => "CSQ0015",
+        }
+    }
+
+    /// Looks up a kind by its code. The `CSQ` prefix is optional and case insensitive.
+    ///
+    /// ```
+    /// # use cargo_src_query::errors::ErrorKind;
+    /// assert_eq!(ErrorKind::from_code("CSQ0001"), Some(ErrorKind::ModuleNotFound));
+    /// assert_eq!(ErrorKind::from_code("csq0003"), Some(ErrorKind::AmbiguousGlob));
+    /// assert_eq!(ErrorKind::from_code("0002"), Some(ErrorKind::FileUnreadable));
+    /// assert_eq!(ErrorKind::from_code("CSQ9999"), None);
+    /// ```
+    pub fn from_code(code: &str) -> Option<ErrorKind> {
+        let code = code.trim().to_ascii_uppercase();
+        let code = code.strip_prefix("CSQ").unwrap_or(&code);
+        ErrorKind::ALL.into_iter().find(|kind| kind.code()[3..] == *code)
+    }
+
+    /// Long-form explanation of the error, in the style of `rustc --explain`.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            ErrorKind::ModuleNotFound => include_str!("explanations/CSQ0001.md"),
+            ErrorKind::FileUnreadable => include_str!("explanations/CSQ0002.md"),
+            ErrorKind::AmbiguousGlob => include_str!("explanations/CSQ0003.md"),
+            ErrorKind::DefinitionNotFound => include_str!("explanations/CSQ0004.md"),
+            ErrorKind::CrateNotFound => include_str!("explanations/CSQ0005.md"),
+            ErrorKind::ExportNotFound => include_str!("explanations/CSQ0006.md"),
+            ErrorKind::InvalidGenericArguments => include_str!("explanations/CSQ0007.md"),
+            ErrorKind::ManifestUnavailable => include_str!("explanations/CSQ0008.md"),
+            ErrorKind::ParseFailure => include_str!("explanations/CSQ0009.md"),
+            ErrorKind::BackendUnavailable => include_str!("explanations/CSQ0010.md"),
+            ErrorKind::DaemonUnavailable => include_str!("explanations/CSQ0011.md"),
+            ErrorKind::Internal => include_str!("explanations/CSQ0012.md"),
+            ErrorKind::FeatureDisabled => include_str!("explanations/CSQ0013.md"),
+            ErrorKind::NotPubliclyReachable => include_str!("explanations/CSQ0014.md"),
+            ErrorKind::BuildScriptNotRun => include_str!("explanations/CSQ0015.md"),
+        }
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
 pub trait AddMessages<T : Debug, E: Error> {
     fn add_messages(self, messages: BTreeMap<&str, String>) -> Self;
 }
 
+impl Display for CargoQueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "error[{}]: {}", self.kind, self.top_level_message)?;
+        for (index, messages) in &self.indexed_messages {
+            for message in messages {
+                write!(f, "\n  = {index}: {message}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for CargoQueryError {}
+
+/// Errors without a queried path are reported at the call site of the procedural macro. Use
+/// [CargoQueryError::to_diagnostic] to point at the failing path segment instead.
+impl From<CargoQueryError> for Diagnostic {
+    fn from(error: CargoQueryError) -> Self {
+        error.to_diagnostic(&syn::Path { leading_colon: None, segments: Default::default() })
+    }
+}
+
 ///
 /// ### Syntax
 /// ```
@@ -106,14 +246,16 @@ pub trait IntoProcMacroError<T> {
 }
 
 macro_rules! into_proc_macro_error {
-    ($result_type:path) => {
+    ($result_type:path, $kind:expr) => {
         impl<T> IntoProcMacroError<T> for $result_type {
             fn into_proc_err(self) -> Result<T, CargoQueryError> {
                     match self {
                         Ok(v) => Ok(v),
                         Err(e) => Err(CargoQueryError {
+                            kind: $kind,
                             top_level_message: e.to_string(),
                             indexed_messages: Default::default(),
+                            path_segment: None,
                             #[cfg(feature = "backtrace")]
                             backtrace: Backtrace::capture(),
                         })
@@ -123,9 +265,9 @@ macro_rules! into_proc_macro_error {
     };
 }
 
-into_proc_macro_error!{ anyhow::Result<T> }
-into_proc_macro_error!{ syn::Result<T> }
-into_proc_macro_error!{ std::io::Result<T> }
+into_proc_macro_error!{ anyhow::Result<T>, ErrorKind::Internal }
+into_proc_macro_error!{ syn::Result<T>, ErrorKind::ParseFailure }
+into_proc_macro_error!{ std::io::Result<T>, ErrorKind::FileUnreadable }
 
 
 // TODO: Should these be extension functions on Result<> for conciseness?
@@ -142,53 +284,148 @@ impl CargoQueryError {
     //     }
     // }
 
-      pub fn add_messages(mut self, messages: BTreeMap<&str, String>) -> Self {
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn top_level_message(&self) -> &str {
+        &self.top_level_message
+    }
+
+    pub fn indexed_messages(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.indexed_messages
+    }
+
+    pub fn add_messages(mut self, messages: BTreeMap<&str, String>) -> Self {
         for (index, message) in messages {
             self.indexed_messages.entry(index.to_string()).or_insert(vec![]).push(message);
         }
-        eprintln!("{self:#?}");
+        trace!("{self:#?}");
+        self
+    }
+
+    pub fn path_segment(&self) -> Option<usize> {
+        self.path_segment
+    }
+
+    /// Marks the segment of the queried path where resolution failed. The innermost failure is
+    /// the most precise, so a segment which has already been set is kept.
+    pub fn at_path_segment(mut self, index: usize) -> Self {
+        self.path_segment.get_or_insert(index);
+        self
+    }
+
+    /// Discards the segment the error was tagged with, e.g. when it refers to a path other than
+    /// the queried one.
+    pub(crate) fn clear_path_segment(mut self) -> Self {
+        self.path_segment = None;
+        self
+    }
+
+    /// Shifts the failing segment, e.g. when the queried path had a prefix which was removed
+    /// before resolution.
+    pub fn offset_path_segment(mut self, offset: usize) -> Self {
+        self.path_segment = self.path_segment.map(|index| index + offset);
         self
     }
 
-    pub fn convert_error(description: String, indexed_messages: BTreeMap<String, Vec<String>>) -> CargoQueryError {
+    /// Converts into a proc-macro [Diagnostic] which underlines the segment of `query_path`
+    /// where resolution failed. `help` messages are rendered as help, and every other indexed
+    /// message is rendered as a note.
+    ///
+    /// ```ignore
+    /// // Inside of a procedural macro, where `path` is `foo::baz::boop` and `baz` is missing
+    /// context.get_module(DEFAULT_FLAGS, Crate::Internal, path.clone())
+    ///     .map_err(|e| e.to_diagnostic(&path).abort());
+    /// ```
+    pub fn to_diagnostic(&self, query_path: &syn::Path) -> Diagnostic {
+        let span = self.path_segment
+            .and_then(|index| query_path.segments.iter().nth(index))
+            .map(|segment| segment.ident.span())
+            .unwrap_or_else(Span::call_site);
+
+        let message = format!("[{}] {}", self.kind, self.top_level_message);
+        let mut diagnostic = Diagnostic::spanned(span, Level::Error, message);
+        for (index, messages) in &self.indexed_messages {
+            for message in messages {
+                diagnostic = if index == "help" {
+                    diagnostic.help(message.clone())
+                } else {
+                    diagnostic.note(format!("{index}: {message}"))
+                };
+            }
+        }
+        diagnostic.help(format!("for more information about this error, try `cargo_src_query --explain {}`", self.kind))
+    }
+
+    pub fn convert_error(kind: ErrorKind, description: String, indexed_messages: BTreeMap<String, Vec<String>>) -> CargoQueryError {
         CargoQueryError {
+            kind,
             top_level_message: description,
             indexed_messages,
+            path_segment: None,
             #[cfg(feature = "backtrace")]
             backtrace: Backtrace::capture()
         }
     }
 
-    /// This Error should enver be used and is only useful for placeholder error messaging
-    pub fn generic_error() -> CargoQueryError {
+    pub fn export_does_not_match(module: &Ident) -> CargoQueryError {
         CargoQueryError {
-            top_level_message: "An error has occurred.".to_string(),
+            kind: ErrorKind::ExportNotFound,
+            top_level_message: format!("No `use` export in scope matches `{module}`."),
             indexed_messages: Default::default(),
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn ambiguous_glob_export(definition: &Ident, candidates: Vec<String>) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::AmbiguousGlob,
+            top_level_message: format!("`{definition}` is exported by more than one glob import."),
+            indexed_messages: btreemap! {
+                "candidate".to_string() => candidates
+            },
+            path_segment: None,
             #[cfg(feature = "backtrace")]
-            backtrace: Backtrace::capture(),
+            backtrace: Backtrace::capture()
         }
     }
 
     pub fn could_not_process_lib_rs(error: anyhow::Error) -> CargoQueryError {
+        // Source providers may fail with a more specific error, e.g. while expanding `include!`
+        let error = match error.downcast::<CargoQueryError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
         CargoQueryError {
+            kind: ErrorKind::FileUnreadable,
             top_level_message: "Could not process `lib.rs` in src_path.".to_string(),
             indexed_messages: btreemap! {
                 "file error".to_string() => vec![error.to_string()],
                 "file error backtrace".to_string() => vec![format!("{:#?}", error.backtrace().to_string())]
             },
+            path_segment: None,
             #[cfg(feature = "backtrace")]
             backtrace: Backtrace::capture()
         }
     }
 
     pub fn could_not_process_file(file_path: &std::path::Path, error: anyhow::Error) -> CargoQueryError {
+        let error = match error.downcast::<CargoQueryError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
         let file_path_string = file_path.to_string_lossy();
         CargoQueryError {
+            kind: ErrorKind::FileUnreadable,
             top_level_message: format!("Could not process `{file_path_string}` in src_path."),
             indexed_messages: btreemap! {
                 "file error".to_string() => vec![error.to_string()],
                 "file error backtrace".to_string() => vec![format!("{:#?}", error.backtrace().to_string())]
             },
+            path_segment: None,
             #[cfg(feature = "backtrace")]
             backtrace: Backtrace::capture()
         }
@@ -196,16 +433,20 @@ impl CargoQueryError {
 
     pub fn could_not_find_defined_module(module: &Ident) -> CargoQueryError {
         CargoQueryError {
+            kind: ErrorKind::ModuleNotFound,
             top_level_message: format!("Could not find module `{module}` in scope"),
             indexed_messages: btreemap! {},
+            path_segment: None,
             #[cfg(feature = "backtrace")]
             backtrace: Backtrace::capture()
         }
     }
     pub fn could_not_find_module_in_scope(module: Ident) -> CargoQueryError {
         CargoQueryError {
+            kind: ErrorKind::ModuleNotFound,
             top_level_message: format!("Could not find module `{module}` in scope"),
             indexed_messages: btreemap! {},
+            path_segment: None,
             #[cfg(feature = "backtrace")]
             backtrace: Backtrace::capture()
         }
@@ -215,12 +456,119 @@ impl CargoQueryError {
     pub fn could_not_find_module_from_path(current_directory: &std::path::Path) -> CargoQueryError {
         let current_directory = current_directory.to_str().unwrap_or("Could not find directory");
         CargoQueryError {
+            kind: ErrorKind::ModuleNotFound,
             top_level_message: "Module path does not map to any known module. \
             Using the src_path, manually validate that the module you are looking for exists. \
             If it does exist, cut an issue [here]().".to_string(),
             indexed_messages: btreemap! {
                 "current_directory".into() => vec![current_directory.into()]
             },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn file_not_in_crate(file_path: &std::path::Path) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::ModuleNotFound,
+            top_level_message: "File does not implement any module of the crate.".to_string(),
+            indexed_messages: btreemap! {
+                "file_path".into() => vec![file_path.to_string_lossy().to_string()],
+                "help".into() => vec!["Only files reachable with `mod` declarations from the crate root are modules.".to_string()]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn not_publicly_reachable(definition: &str) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::NotPubliclyReachable,
+            top_level_message: format!("`{definition}` is not publicly reachable."),
+            indexed_messages: btreemap! {
+                "note".into() => vec!["No path of `pub` modules and `pub use` re-exports leads from the crate root to the definition.".to_string()]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn build_script_not_run(package: &str, build_directory: &std::path::Path) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::BuildScriptNotRun,
+            top_level_message: format!("The build script of `{package}` has not run yet, so its `OUT_DIR` does not exist."),
+            indexed_messages: btreemap! {
+                "build directory".into() => vec![build_directory.to_string_lossy().to_string()],
+                "help".into() => vec![format!("build the package first, e.g. with `cargo check -p {package}`")]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_evaluate_include(argument: &impl ToTokens, reason: &str) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::ParseFailure,
+            top_level_message: format!("Could not evaluate the path of `include!`: {reason}."),
+            indexed_messages: btreemap! {
+                "argument".into() => vec![argument.to_token_stream().to_string()]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_recover_source_text(item: &impl ToTokens) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::ParseFailure,
+            top_level_message: "Could not recover the source text of an item, since it doesn't appear in the files read by the query.".to_string(),
+            indexed_messages: btreemap! {
+                "item".into() => vec![item.to_token_stream().to_string()]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn invalid_path_pattern(pattern: &str, reason: &str) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::ParseFailure,
+            top_level_message: format!("Could not parse the path pattern `{pattern}`: {reason}."),
+            indexed_messages: btreemap! {},
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_follow_type_alias(alias: &str, target: &impl ToTokens, reason: &str) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::DefinitionNotFound,
+            top_level_message: format!("Could not follow the type alias `{alias}`: {reason}."),
+            indexed_messages: btreemap! {
+                "alias target".into() => vec![target.to_token_stream().to_string()]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn feature_disabled(feature: &str, reason: &str) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::FeatureDisabled,
+            top_level_message: format!("The `{feature}` feature of `cargo_src_query` is not enabled."),
+            indexed_messages: btreemap! {
+                "note".into() => vec![reason.to_string()],
+                "help".into() => vec![format!("enable it with `cargo_src_query = {{ features = [\"{feature}\"] }}`")]
+            },
+            path_segment: None,
             #[cfg(feature = "backtrace")]
             backtrace: Backtrace::capture()
         }
@@ -228,10 +576,12 @@ impl CargoQueryError {
 
     pub fn could_not_find_module() -> CargoQueryError {
         CargoQueryError {
+            kind: ErrorKind::ModuleNotFound,
             top_level_message: "Module path does not map to any known module. \
             Using the src_path, manually validate that the module you are looking for exists. \
             If it does exist, cut an issue [here]().".to_string(),
             indexed_messages: Default::default(),
+            path_segment: None,
             #[cfg(feature = "backtrace")]
             backtrace: Backtrace::capture()
         }
@@ -239,8 +589,144 @@ impl CargoQueryError {
 
     pub fn could_not_find_module_in_file(module: &Ident) -> CargoQueryError {
         CargoQueryError {
+            kind: ErrorKind::ModuleNotFound,
             top_level_message: format!("Could not find module `{module}` in source file."),
             indexed_messages: Default::default(),
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_find_definition(definition: &Ident) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::DefinitionNotFound,
+            top_level_message: format!("Could not find definition `{definition}` in module."),
+            indexed_messages: Default::default(),
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn invalid_generic_arguments(definition: &Ident, arguments: &impl ToTokens, reason: &str) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::InvalidGenericArguments,
+            top_level_message: format!("Invalid generic arguments for definition `{definition}`: {reason}."),
+            indexed_messages: btreemap! {
+                "generic arguments".to_string() => vec![arguments.to_token_stream().to_string()]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_read_environment_variable(variable: &str, error: std::env::VarError) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::ManifestUnavailable,
+            top_level_message: format!("Could not read environment variable `{variable}`. \
+            Is the query running outside of cargo? If so, build the `QueryContext` with an explicit manifest path."),
+            indexed_messages: btreemap! {
+                "environment error".to_string() => vec![error.to_string()]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_load_cargo_metadata(manifest_path: &std::path::Path, error: cargo_metadata::Error) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::ManifestUnavailable,
+            top_level_message: "Could not load cargo metadata for the manifest path.".to_string(),
+            indexed_messages: btreemap! {
+                "manifest path".to_string() => vec![manifest_path.to_string_lossy().to_string()],
+                "cargo metadata error".to_string() => vec![error.to_string()]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_find_registry_crate(crate_name: &str, version: &str) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::CrateNotFound,
+            top_level_message: format!("Could not find the sources of `{crate_name} {version}` in the local registry."),
+            indexed_messages: btreemap! {
+                "help".to_string() => vec![
+                    format!("download them by depending on `{crate_name} = \"={version}\"` and running `cargo fetch`")
+                ]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_find_crate(crate_name: &Ident) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::CrateNotFound,
+            top_level_message: format!("Invalid crate name `{crate_name}`."),
+            indexed_messages: btreemap! {
+                "help".to_string() => vec![
+                    format!("try including this cargo crate using `cargo add {crate_name}`")
+                ]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn no_library_target(package: &str) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::CrateNotFound,
+            top_level_message: format!("The package `{package}` has no library target to index."),
+            indexed_messages: btreemap! {},
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_build_thread_pool(threads: usize, error: rayon::ThreadPoolBuildError) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::Internal,
+            top_level_message: format!("Could not build a thread pool with {threads} threads for indexing."),
+            indexed_messages: btreemap! {
+                "thread pool error".to_string() => vec![error.to_string()]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_parse_item(source: &str, error: syn::Error) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::ParseFailure,
+            top_level_message: "Could not parse item returned by the query backend.".to_string(),
+            indexed_messages: btreemap! {
+                "item source".to_string() => vec![source.to_string()],
+                "parse error".to_string() => vec![error.to_string()]
+            },
+            path_segment: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture()
+        }
+    }
+
+    pub fn could_not_load_workspace(manifest_path: &std::path::Path, error: anyhow::Error) -> CargoQueryError {
+        CargoQueryError {
+            kind: ErrorKind::BackendUnavailable,
+            top_level_message: "Could not load the cargo workspace with rust-analyzer.".to_string(),
+            indexed_messages: btreemap! {
+                "manifest path".to_string() => vec![manifest_path.to_string_lossy().to_string()],
+                "workspace error".to_string() => vec![error.to_string()]
+            },
+            path_segment: None,
             #[cfg(feature = "backtrace")]
             backtrace: Backtrace::capture()
         }
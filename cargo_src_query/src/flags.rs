@@ -2,7 +2,11 @@ use bitflags::bitflags;
 
 bitflags! {
     pub struct Flags: u32 {
+        /// Leave items which aren't `pub` out of module queries. Definitions are found regardless.
         const EXCLUDE_PRIVATE = 0b00000001;
+        /// Follow type aliases to the definition behind them. See
+        /// [QueryContext::resolve_type_aliases](crate::QueryContext::resolve_type_aliases).
+        const FOLLOW_TYPE_ALIASES = 0b00000010;
     }
 }
 
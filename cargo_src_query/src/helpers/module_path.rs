@@ -4,20 +4,28 @@
 
 use std::fmt::{Display, Formatter};
 use proc_macro2::Ident;
+use quote::ToTokens;
+use syn::PathArguments;
 
 
 /// A ModulePath is a path of the form `crate::module_a::module_b::module_c::Definition`. It is
-/// used to represent the path of a definition in a crate.
+/// used to represent the path of a definition in a crate. Generic arguments of each segment,
+/// e.g. `Wrapper<u32>`, are kept alongside the identifiers.
 #[derive(Clone, PartialEq, Debug, Default)]
 pub struct ModulePath {
     path: Vec<Ident>,
+    arguments: Vec<PathArguments>,
     position: usize,
 }
 
 impl From<syn::Path> for ModulePath {
     fn from(path: syn::Path) -> Self {
+        let (path, arguments) = path.segments.into_iter()
+            .map(|segment| (segment.ident, segment.arguments))
+            .unzip();
         ModulePath {
-            path: path.segments.into_iter().map(|segment| segment.ident).collect(),
+            path,
+            arguments,
             position: 0,
         }
     }
@@ -26,12 +34,15 @@ impl From<syn::Path> for ModulePath {
 impl Display for ModulePath {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut path = String::new();
-        for (i, ident) in self.path.iter().enumerate().skip(self.position) {
+        for (i, (ident, arguments)) in self.path.iter().zip(&self.arguments).enumerate().skip(self.position) {
             if i == 0 {
                 path.push_str(&ident.to_string());
             } else {
                 path.push_str(&format!("::{}", ident));
             }
+            if !arguments.is_empty() {
+                path.push_str(&arguments.to_token_stream().to_string());
+            }
         }
         write!(f, "{}", path)
     }
@@ -60,6 +71,7 @@ impl ModulePath {
     /// ```
     pub fn new(path: Vec<Ident>) -> Self {
         ModulePath {
+            arguments: vec![PathArguments::None; path.len()],
             path,
             position: 0,
         }
@@ -99,6 +111,7 @@ impl ModulePath {
     pub fn clone_and_overwrite(&mut self, overwrite_value: Ident) -> ModulePath {
         let mut cloned_module_path = Self::clone(self);
         cloned_module_path.path[self.position] = overwrite_value;
+        cloned_module_path.arguments[self.position] = PathArguments::None;
         cloned_module_path
     }
 
@@ -135,6 +148,7 @@ impl ModulePath {
     pub fn clone_and_insert(&mut self, insert_value: Ident) -> ModulePath {
         let mut cloned_module_path = Self::clone(self);
         cloned_module_path.path.insert(self.position, insert_value);
+        cloned_module_path.arguments.insert(self.position, PathArguments::None);
         cloned_module_path
     }
 
@@ -163,6 +177,20 @@ impl ModulePath {
         self.path.get(self.position).cloned()
     }
 
+    /// Returns the generic arguments of the path segment at the current position, e.g. `<u32>`
+    /// for `Wrapper<u32>`.
+    ///
+    /// ```
+    /// # use cargo_src_query::helpers::module_path::ModulePath;
+    /// # use syn::PathArguments;
+    /// let module_path = ModulePath::from(syn::parse_str::<syn::Path>("Wrapper<u32>").unwrap());
+    ///
+    /// assert!(matches!(module_path.get_current_arguments(), Some(PathArguments::AngleBracketed(_))));
+    /// ```
+    pub fn get_current_arguments(&self) -> Option<&PathArguments> {
+        self.arguments.get(self.position)
+    }
+
     /// Returns the current position.
     /// ```
     /// # use proc_macro2::Ident;
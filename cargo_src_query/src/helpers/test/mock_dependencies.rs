@@ -0,0 +1,16 @@
+#[cfg(test)]
+pub mod tests {
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+    use crate::r#impl::find_dependencies::{CrateLocation, DependencyLookup};
+
+    /// Dependencies keyed by the directory of the depending crate and the name they're referred
+    /// to by within it.
+    pub struct MockDependencies(pub BTreeMap<(PathBuf, String), CrateLocation>);
+
+    impl DependencyLookup for MockDependencies {
+        fn dependency(&self, crate_path: &Path, name: &str) -> Option<CrateLocation> {
+            self.0.get(&(crate_path.to_path_buf(), name.to_string())).cloned()
+        }
+    }
+}
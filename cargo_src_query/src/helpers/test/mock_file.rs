@@ -3,28 +3,18 @@
 pub mod tests {
     use std::collections::BTreeMap;
     use std::path::PathBuf;
-    use anyhow::Error;
-    use mocktopus::mocking::{Mockable, MockResult};
     use proc_macro2::{Ident, TokenStream};
     use quote::{quote, ToTokens};
     use rand::Rng;
     use syn::{Item, parse2, parse_str};
     use uuid::Uuid;
-    use crate::r#impl::find_mod::parse_file_from_path;
+    use crate::source::InMemory;
 
-    pub fn mock_file_for_path(path_to_file: BTreeMap<PathBuf, syn::File>) {
-        parse_file_from_path.mock_safe(move |path| {
-            match path_to_file.get(path) {
-                Some(file) => MockResult::Return(Ok(file.clone())),
-                None => MockResult::Return(Err(Error::msg(format!(
-                    "Error generated from Mocking function. \
-                    Path `{}` does not exist map of paths. \
-                    Map of path to files: \
-                    {:#?}",
-                    path.to_str().unwrap(), path_to_file
-                ))))
-            }
-        });
+    /// Creates an in-memory [InMemory] source provider which serves each file at its path.
+    pub fn mock_file_for_path(path_to_file: BTreeMap<PathBuf, syn::File>) -> InMemory {
+        path_to_file.into_iter()
+            .map(|(path, file)| (path, file.to_token_stream().to_string()))
+            .collect()
     }
 
     // Rust Construct Generators
@@ -53,4 +43,4 @@ pub mod tests {
     pub fn file(tokens: TokenStream) -> syn::File {
         parse2(tokens.into()).unwrap()
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,5 @@
 pub mod initialize;
 pub mod mock_file;
+pub mod mock_dependencies;
 pub mod assert_tokens_eq;
 mod common;
\ No newline at end of file
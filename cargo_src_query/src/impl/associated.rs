@@ -0,0 +1,246 @@
+//! # Associated Items
+//! Queries end at a module or at a definition declared within a module. Paths to associated
+//! items continue into the definition:
+//!
+//! | Path                      | Resolves to                                               |
+//! |---------------------------|-----------------------------------------------------------|
+//! | `crate::Type::method`     | `fn method` within an inherent or trait impl of `Type`    |
+//! | `crate::Type::CONST`      | `const CONST` within an inherent or trait impl of `Type`  |
+//! | `crate::Trait::AssocType` | `type AssocType` declared by `Trait`                      |
+//! | `crate::Enum::Variant`    | The variant `Variant` of `Enum`                           |
+//!
+//! Each [AssociatedItem] comes with the header of its enclosing impl, trait or enum, from which
+//! every other member is removed:
+//!
+//! ```text
+//! // crate::Type::fmt
+//! impl Display for Type {
+//!     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { ... }
+//! }
+//! ```
+//!
+//! The definition is resolved like a queried definition, so it may be re-exported into the
+//! module, e.g. `crate::Type::method` for `pub use inner::Type;`. Impls are searched for in every
+//! module of the crate. An impl belongs to the definition when its self type resolves to it,
+//! either as a path like `super::Type` or through a `use` within the impl's module. Variants
+//! and trait items of definitions re-exported from another crate aren't searched. A name can be implemented more than once, e.g. `fmt` by both `Debug` and
+//! `Display`, so every match is returned.
+
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::{ImplItem, Item, TraitItem, Type, TypePath, Variant};
+use crate::core::{FoundDefinition, ident, Resolver};
+use crate::errors::CargoQueryError;
+use crate::r#impl::attribute_search::{impl_member, named_item, trait_member};
+use crate::r#impl::find_dependencies::CrateLocation;
+use crate::r#impl::index::CrateIndex;
+use crate::r#impl::references::imported_names;
+use crate::r#impl::type_alias::{find_type, resolve_impl_self_type};
+
+/// A member of an impl, a trait or an enum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Member {
+    Impl(ImplItem),
+    Trait(TraitItem),
+    Variant(Variant),
+}
+
+impl Member {
+    /// The kind of the member, e.g. `fn`, `const`, `type` or `variant`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Member::Impl(item) => impl_member(item).map_or("macro", |(_, kind, _)| kind),
+            Member::Trait(item) => trait_member(item).map_or("macro", |(_, kind, _)| kind),
+            Member::Variant(_) => "variant",
+        }
+    }
+}
+
+impl ToTokens for Member {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            Member::Impl(item) => item.to_tokens(tokens),
+            Member::Trait(item) => item.to_tokens(tokens),
+            Member::Variant(variant) => variant.to_tokens(tokens),
+        }
+    }
+}
+
+/// A member named by a path such as `crate::Type::method`. See the
+/// [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct AssociatedItem {
+    /// Path segments relative to the crate root, ending with the member
+    pub path: Vec<String>,
+    pub member: Member,
+    /// The enclosing impl, trait or enum, containing only `member`
+    pub header: Item,
+    /// File containing the enclosing impl, trait or enum
+    pub file_path: PathBuf,
+}
+
+/// Renders the full path, e.g. `crate::api::Request::new`.
+impl Display for AssociatedItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", std::iter::once("crate").chain(self.path.iter().map(String::as_str)).collect::<Vec<_>>().join("::"))
+    }
+}
+
+/// Finds the members named by the last segment of `path` (relative to the root of `krate`, which
+/// `index` indexes) within the definition named by the segment before it. Enum variants and
+/// trait items come first, followed by impl items in module order.
+pub(crate) fn find_associated(resolver: &Resolver, krate: &CrateLocation, index: &CrateIndex, path: &[String])
+    -> Result<Vec<AssociatedItem>, CargoQueryError> {
+    let [module @ .., parent, member] = path else {
+        return Err(CargoQueryError::could_not_find_module())
+    };
+    let missing = |position: usize| CargoQueryError::could_not_find_definition(&ident(&path[position])).at_path_segment(position);
+    index.module(module).ok_or_else(||
+        CargoQueryError::could_not_find_module_in_scope(ident(&module[module.len() - 1])).at_path_segment(module.len() - 1)
+    )?;
+    let definition = find_type(resolver, &[(krate.clone(), module.to_vec(), ident(parent))])
+        .map_err(|_| missing(path.len() - 2))?;
+    let Some((name, kind, _)) = named_item(&definition.item) else { return Err(missing(path.len() - 2)) };
+    let is_definition = |found: &FoundDefinition| found.krate == definition.krate && found.module == definition.module
+        && named_item(&found.item).is_some_and(|(found_name, found_kind, _)| found_name == name && found_kind == kind);
+
+    let associated = |member: Member, header: Item, file_path: &PathBuf| AssociatedItem {
+        path: path.to_vec(), member, header, file_path: file_path.clone(),
+    };
+    let mut found = vec![];
+    // Only the members of definitions of this crate can be located within its index
+    let definition_module = (definition.krate == *krate).then(|| index.module(&definition.module)).flatten();
+    match (&definition.item, definition_module) {
+        (Item::Enum(item), Some(definition_module)) => for variant in item.variants.iter().filter(|variant| variant.ident == member) {
+            let header = syn::ItemEnum { variants: std::iter::once(variant.clone()).collect(), ..item.clone() };
+            found.push(associated(Member::Variant(variant.clone()), Item::Enum(header), &definition_module.file_path));
+        },
+        (Item::Trait(item), Some(definition_module)) => for trait_item in item.items.iter().filter(|i| trait_member(i).is_some_and(|(name, _, _)| name == *member)) {
+            let header = syn::ItemTrait { items: vec![trait_item.clone()], ..item.clone() };
+            found.push(associated(Member::Trait(trait_item.clone()), Item::Trait(header), &definition_module.file_path));
+        },
+        _ => {}
+    }
+
+    let scopes = index.modules.iter()
+        .map(|impl_module| Ok((impl_module, index.items(impl_module)?)))
+        .collect::<Result<Vec<_>, CargoQueryError>>()?;
+    // Only impls of a type named like the definition, or like one of its imports, are resolved
+    let names = imported_names(scopes.iter().map(|(_, scope)| scope.as_slice()), &name);
+    for (impl_module, scope) in &scopes {
+        for item in scope {
+            let Item::Impl(item_impl) = item else { continue };
+            let Type::Path(TypePath { qself: None, path: self_path }) = item_impl.self_ty.as_ref() else { continue };
+            if !self_path.segments.last().is_some_and(|segment| names.contains(&segment.ident.to_string())) { continue }
+            if !resolve_impl_self_type(resolver, krate, &impl_module.path, item_impl).is_some_and(|found| is_definition(&found)) { continue }
+            for impl_item in item_impl.items.iter().filter(|i| impl_member(i).is_some_and(|(name, _, _)| name == *member)) {
+                let header = syn::ItemImpl { items: vec![impl_item.clone()], ..item_impl.clone() };
+                found.push(associated(Member::Impl(impl_item.clone()), Item::Impl(header), &impl_module.file_path));
+            }
+        }
+    }
+
+    match found.is_empty() {
+        true => Err(missing(path.len() - 1)),
+        false => Ok(found),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use maplit::btreemap;
+    use quote::ToTokens;
+    use crate::core::Resolver;
+    use crate::errors::{CargoQueryError, ErrorKind};
+    use crate::helpers::test::mock_dependencies::tests::MockDependencies;
+    use crate::r#impl::associated::{AssociatedItem, find_associated};
+    use crate::r#impl::find_dependencies::CrateLocation;
+    use crate::r#impl::index::index_crate;
+    use crate::source::InMemory;
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn source_provider() -> InMemory {
+        [
+            (lib_src_path(), [
+                "pub mod api;",
+                "mod fmt;",
+                "pub enum Method { Get, Post(String) }",
+                "impl Method { pub fn is_get(&self) -> bool { matches!(self, Method::Get) } }",
+                "mod reexported { pub struct Reexported {} impl Reexported { pub fn method() {} } }",
+                "pub use reexported::Reexported;",
+            ].join("\n")),
+            (src_path().join("api.rs"), [
+                "pub struct Request {}",
+                "impl Request { pub const LIMIT: usize = 8; pub fn new() -> Self { Request {} } }",
+                "pub trait Handler { type Output; fn handle(&self) -> Self::Output; }",
+                "impl Handler for Request { type Output = (); fn handle(&self) {} }",
+                "mod other { pub struct Request {} impl Request { pub fn new() -> Self { Request {} } } }",
+            ].join("\n")),
+            (src_path().join("fmt.rs"), [
+                "use crate::api::Request as Aliased;",
+                "use crate::api::*;",
+                "impl std::fmt::Debug for Aliased { fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { Ok(()) } }",
+                "impl std::fmt::Display for Request { fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { Ok(()) } }",
+            ].join("\n")),
+        ].into_iter().collect()
+    }
+
+    fn find_associated_at(path: &str) -> Result<Vec<AssociatedItem>, CargoQueryError> {
+        let source_provider = source_provider();
+        let index = index_crate(&source_provider, &lib_src_path(), Some(1)).unwrap();
+        let dependencies = MockDependencies(btreemap! {});
+        let resolver = Resolver { source_provider: &source_provider, dependencies: &dependencies };
+        let root = CrateLocation { name: "crate".to_string(), path: crate_path().to_path_buf() };
+        let path: Vec<String> = path.split("::").map(String::from).collect();
+        find_associated(&resolver, &root, &index, &path)
+    }
+
+    fn find(path: &str) -> Vec<(String, String)> {
+        find_associated_at(path).unwrap().iter()
+            .map(|found| (found.member.kind().to_string(), found.header.to_token_stream().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn find_impl_items_trait_items_and_variants() {
+        let found = find("api::Request::new");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "fn");
+        assert!(found[0].1.starts_with("impl Request { pub fn new"));
+
+        assert_eq!(find("api::Request::LIMIT")[0].0, "const");
+        assert_eq!(find("api::Handler::Output"), vec![("type".to_string(), "pub trait Handler { type Output ; }".to_string())]);
+        assert_eq!(find("api::Request::Output"), vec![("type".to_string(), "impl Handler for Request { type Output = () ; }".to_string())]);
+        assert_eq!(find("Method::Post"), vec![("variant".to_string(), "pub enum Method { Post (String) }".to_string())]);
+        assert_eq!(find("Method::is_get")[0].0, "fn");
+    }
+
+    #[test]
+    fn find_impls_of_imported_types() {
+        let found = find("api::Request::fmt");
+        assert_eq!(found.len(), 2);
+        assert!(found[0].1.starts_with("impl std :: fmt :: Debug for Aliased"));
+        assert!(found[1].1.starts_with("impl std :: fmt :: Display for Request"));
+    }
+
+    #[test]
+    fn find_impls_of_reexported_types() {
+        let found = find("Reexported::method");
+        assert_eq!(found.len(), 1);
+        assert!(found[0].1.starts_with("impl Reexported { pub fn method"));
+    }
+
+    #[test]
+    fn report_the_missing_segment() {
+        let error = find_associated_at("api::Missing::new").unwrap_err();
+        assert_eq!((error.kind(), error.path_segment()), (ErrorKind::DefinitionNotFound, Some(1)));
+        let error = find_associated_at("api::Request::missing").unwrap_err();
+        assert_eq!((error.kind(), error.path_segment()), (ErrorKind::DefinitionNotFound, Some(2)));
+    }
+}
@@ -0,0 +1,346 @@
+//! # Attribute Search
+//! Finds every item of a module subtree which carries an attribute or derive, e.g. to generate
+//! registration code for all structs deriving `Serialize` or all fns marked `#[test]`:
+//!
+//! ```text
+//! #[derive(Debug, serde::Serialize)]        AttributePattern::derive("Serialize")         ✓
+//! struct Config {}                          AttributePattern::derive("serde::Serialize")  ✓
+//!                                           AttributePattern::derive("Deserialize")       ✗
+//!
+//! #[serde(rename_all = "camelCase")]        AttributePattern::attribute("serde")
+//! struct Event {}                               .arguments("rename_all = \"camelCase\"")  ✓
+//! ```
+//!
+//! A pattern's path matches attribute paths ending with the same segments, so `test` also
+//! matches `#[tokio::test]`. Arguments match when they equal all of the attribute's arguments, or
+//! one of its comma separated arguments. Attributes within `#[cfg_attr(condition, ...)]` match
+//! regardless of the condition.
+//!
+//! Items of impl blocks and traits are searched as well, and the items of inline modules are
+//! searched as part of their module. Items are read from the sources of a [CrateIndex], so items
+//! spliced in by `include!` aren't searched.
+
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use proc_macro2::TokenStream;
+use syn::{Attribute, ImplItem, Item, Meta, NestedMeta, TraitItem, Type};
+use syn::parse::Parser;
+use crate::errors::CargoQueryError;
+use crate::r#impl::index::CrateIndex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatternKind {
+    Attribute,
+    Derive,
+}
+
+/// The attribute or derive to search for. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributePattern {
+    kind: PatternKind,
+    path: Vec<String>,
+    arguments: Option<String>,
+}
+
+impl AttributePattern {
+    /// Matches attributes such as `#[test]` or `#[tokio::test]` for `"test"`.
+    pub fn attribute(path: &str) -> Self {
+        AttributePattern { kind: PatternKind::Attribute, path: split_path(path), arguments: None }
+    }
+
+    /// Matches derived traits such as `#[derive(Serialize)]` or `#[derive(serde::Serialize)]` for
+    /// `"Serialize"`.
+    pub fn derive(path: &str) -> Self {
+        AttributePattern { kind: PatternKind::Derive, path: split_path(path), arguments: None }
+    }
+
+    /// Only matches attributes with the given arguments, e.g. `test` for `#[cfg(test)]`. Ignored
+    /// for derives.
+    pub fn arguments(mut self, arguments: &str) -> Self {
+        self.arguments = Some(normalize(arguments));
+        self
+    }
+
+    fn matches(&self, attribute: &Attribute) -> bool {
+        if attribute.path.is_ident("cfg_attr") {
+            return nested_attributes(attribute).iter().any(|nested| self.matches(nested))
+        }
+        match self.kind {
+            PatternKind::Derive => attribute.path.is_ident("derive") && derived_paths(attribute).iter().any(|path| self.matches_path(path)),
+            PatternKind::Attribute => self.matches_path(&attribute.path) && self.matches_arguments(&attribute.tokens),
+        }
+    }
+
+    fn matches_path(&self, path: &syn::Path) -> bool {
+        let segments: Vec<String> = path.segments.iter().map(|segment| segment.ident.to_string()).collect();
+        !self.path.is_empty() && segments.ends_with(&self.path)
+    }
+
+    fn matches_arguments(&self, tokens: &TokenStream) -> bool {
+        let expected = match &self.arguments {
+            Some(expected) => expected,
+            None => return true,
+        };
+        let arguments = match syn::parse2::<proc_macro2::Group>(tokens.clone()) {
+            Ok(group) => group.stream(),
+            Err(_) => tokens.clone(),
+        };
+        if &arguments.to_string() == expected {
+            return true
+        }
+        split_arguments(arguments).iter().any(|argument| argument == expected)
+    }
+}
+
+/// Renders the pattern as it would be written on an item, e.g. `#[derive(Serialize)]`.
+impl Display for AttributePattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let path = self.path.join("::");
+        match (self.kind, &self.arguments) {
+            (PatternKind::Derive, _) => write!(f, "#[derive({path})]"),
+            (PatternKind::Attribute, Some(arguments)) => write!(f, "#[{path}({arguments})]"),
+            (PatternKind::Attribute, None) => write!(f, "#[{path}]"),
+        }
+    }
+}
+
+/// An item carrying a searched attribute.
+#[derive(Debug, Clone)]
+pub struct AttributeMatch {
+    /// Module containing the item, relative to the crate root
+    pub module: Vec<String>,
+    /// Names from the module to the item, e.g. `["Server", "handle"]` for a method of `impl Server`
+    pub path: Vec<String>,
+    /// The kind of the item, e.g. `fn` or `struct`
+    pub kind: &'static str,
+    /// The matching attribute, e.g. `#[derive(Clone, Serialize)]`
+    pub attribute: Attribute,
+    pub file_path: PathBuf,
+}
+
+impl AttributeMatch {
+    /// Path segments of the item, relative to the crate root.
+    pub fn full_path(&self) -> Vec<String> {
+        self.module.iter().chain(&self.path).cloned().collect()
+    }
+}
+
+/// Renders the full path, e.g. `crate::handlers::Server::handle`.
+impl Display for AttributeMatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", std::iter::once("crate".to_string()).chain(self.full_path()).collect::<Vec<_>>().join("::"))
+    }
+}
+
+/// Finds the items of `module` and its descendants which carry an attribute matching `pattern`,
+/// ordered by module path and then by position.
+pub fn find_attributes(index: &CrateIndex, module: &[String], pattern: &AttributePattern) -> Result<Vec<AttributeMatch>, CargoQueryError> {
+    index.module(module).ok_or_else(CargoQueryError::could_not_find_module)?;
+
+    let mut matches = vec![];
+    for indexed_module in index.modules.iter().filter(|indexed_module| indexed_module.path.starts_with(module)) {
+        for item in index.items(indexed_module)? {
+            let mut found = |module: &[String], path: Vec<String>, kind, attributes: &[Attribute]| {
+                if let Some(attribute) = attributes.iter().find(|attribute| pattern.matches(attribute)) {
+                    matches.push(AttributeMatch {
+                        module: module.to_vec(),
+                        path,
+                        kind,
+                        attribute: attribute.clone(),
+                        file_path: indexed_module.file_path.clone(),
+                    });
+                }
+            };
+            let module = indexed_module.path.as_slice();
+            match &item {
+                Item::Impl(i) => {
+                    let segments: Vec<String> = match i.self_ty.as_ref() {
+                        Type::Path(ty) => ty.path.segments.iter().map(|segment| segment.ident.to_string()).collect(),
+                        _ => continue,
+                    };
+                    // Types of other crates, e.g. `impl Display for String`, are named within the impl's module
+                    let (type_module, type_path) = index.resolve_module_prefix(module, &segments)
+                        .filter(|(_, rest)| !rest.is_empty())
+                        .unwrap_or_else(|| (module.to_vec(), segments[segments.len() - 1..].to_vec()));
+                    for member in &i.items {
+                        if let Some((name, kind, attributes)) = impl_member(member) {
+                            found(&type_module, type_path.iter().cloned().chain([name]).collect(), kind, attributes);
+                        }
+                    }
+                },
+                Item::Trait(i) => {
+                    found(module, vec![i.ident.to_string()], "trait", &i.attrs);
+                    for member in &i.items {
+                        if let Some((name, kind, attributes)) = trait_member(member) {
+                            found(module, vec![i.ident.to_string(), name], kind, attributes);
+                        }
+                    }
+                },
+                item => if let Some((name, kind, attributes)) = named_item(item) {
+                    found(module, vec![name], kind, attributes);
+                },
+            }
+        }
+    }
+    Ok(matches)
+}
+
+/// The name, kind and attributes of an item which defines a name, e.g. `("Config", "struct", ..)`.
+pub(crate) fn named_item(item: &Item) -> Option<(String, &'static str, &[Attribute])> {
+    Some(match item {
+        Item::Const(i) => (i.ident.to_string(), "const", &i.attrs),
+        Item::Enum(i) => (i.ident.to_string(), "enum", &i.attrs),
+        Item::ExternCrate(i) => (i.ident.to_string(), "extern crate", &i.attrs),
+        Item::Fn(i) => (i.sig.ident.to_string(), "fn", &i.attrs),
+        Item::Macro(i) => (i.ident.as_ref()?.to_string(), "macro_rules!", &i.attrs),
+        Item::Mod(i) => (i.ident.to_string(), "mod", &i.attrs),
+        Item::Static(i) => (i.ident.to_string(), "static", &i.attrs),
+        Item::Struct(i) => (i.ident.to_string(), "struct", &i.attrs),
+        Item::Trait(i) => (i.ident.to_string(), "trait", &i.attrs),
+        Item::Type(i) => (i.ident.to_string(), "type", &i.attrs),
+        Item::Union(i) => (i.ident.to_string(), "union", &i.attrs),
+        _ => return None,
+    })
+}
+
+pub(crate) fn impl_member(item: &ImplItem) -> Option<(String, &'static str, &[Attribute])> {
+    Some(match item {
+        ImplItem::Const(i) => (i.ident.to_string(), "const", &i.attrs),
+        ImplItem::Method(i) => (i.sig.ident.to_string(), "fn", &i.attrs),
+        ImplItem::Type(i) => (i.ident.to_string(), "type", &i.attrs),
+        _ => return None,
+    })
+}
+
+pub(crate) fn trait_member(item: &TraitItem) -> Option<(String, &'static str, &[Attribute])> {
+    Some(match item {
+        TraitItem::Const(i) => (i.ident.to_string(), "const", &i.attrs),
+        TraitItem::Method(i) => (i.sig.ident.to_string(), "fn", &i.attrs),
+        TraitItem::Type(i) => (i.ident.to_string(), "type", &i.attrs),
+        _ => return None,
+    })
+}
+
+/// The paths listed in `#[derive(...)]`.
+fn derived_paths(attribute: &Attribute) -> Vec<syn::Path> {
+    match attribute.parse_meta() {
+        Ok(Meta::List(list)) => list.nested.into_iter().filter_map(|nested| match nested {
+            NestedMeta::Meta(meta) => Some(meta.path().clone()),
+            NestedMeta::Lit(_) => None,
+        }).collect(),
+        _ => vec![],
+    }
+}
+
+/// The attributes applied by `#[cfg_attr(condition, attribute, ...)]`.
+fn nested_attributes(attribute: &Attribute) -> Vec<Attribute> {
+    let arguments = match syn::parse2::<proc_macro2::Group>(attribute.tokens.clone()) {
+        Ok(group) => group.stream(),
+        Err(_) => return vec![],
+    };
+    split_arguments(arguments).iter().skip(1)
+        .filter_map(|nested| Attribute::parse_outer.parse_str(&format!("#[{nested}]")).ok())
+        .flatten()
+        .collect()
+}
+
+/// Splits arguments at top level commas, normalizing the spacing of each argument.
+fn split_arguments(arguments: TokenStream) -> Vec<String> {
+    let mut split = vec![TokenStream::new()];
+    for token in arguments {
+        match &token {
+            proc_macro2::TokenTree::Punct(punct) if punct.as_char() == ',' => split.push(TokenStream::new()),
+            _ => split.last_mut().expect("split is never empty").extend(std::iter::once(token)),
+        }
+    }
+    split.into_iter().filter(|argument| !argument.is_empty()).map(|argument| argument.to_string()).collect()
+}
+
+/// Normalizes spacing by round-tripping through tokens, e.g. `rename_all="a"` => `rename_all = "a"`.
+fn normalize(tokens: &str) -> String {
+    tokens.parse::<TokenStream>().map(|tokens| tokens.to_string()).unwrap_or_else(|_| tokens.trim().to_string())
+}
+
+fn split_path(path: &str) -> Vec<String> {
+    path.trim().trim_start_matches("::").split("::").map(|segment| segment.trim().to_string())
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use crate::r#impl::attribute_search::{AttributePattern, find_attributes};
+    use crate::r#impl::index::index_crate;
+    use crate::source::InMemory;
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn search(module: &[&str], pattern: AttributePattern) -> Vec<String> {
+        let source_provider: InMemory = [
+            (lib_src_path(), [
+                "pub mod api;",
+                "#[derive(Debug, serde::Serialize)]",
+                "pub struct Config {}",
+                "#[cfg_attr(feature = \"serde\", derive(Serialize))]",
+                "pub enum Mode { Fast }",
+                "#[cfg(test)]",
+                "mod tests {",
+                "    #[test]",
+                "    fn works() {}",
+                "    #[tokio::test]",
+                "    async fn works_async() {}",
+                "}",
+            ].join("\n")),
+            (src_path().join("api.rs"), [
+                "#[serde(rename_all = \"camelCase\", deny_unknown_fields)]",
+                "#[derive(Serialize)]",
+                "pub struct Event {}",
+                "pub struct Server;",
+                "impl Server {",
+                "    #[route(\"/events\")]",
+                "    pub fn handle(&self) {}",
+                "}",
+                "mod health {",
+                "    impl super::Server {",
+                "        #[route(\"/health\")]",
+                "        pub fn health(&self) {}",
+                "    }",
+                "}",
+            ].join("\n")),
+        ].into_iter().collect();
+
+        let index = index_crate(&source_provider, &lib_src_path(), Some(1)).unwrap();
+        let module: Vec<String> = module.iter().map(|segment| segment.to_string()).collect();
+        find_attributes(&index, &module, &pattern).unwrap().iter()
+            .map(|found| format!("{} {found}", found.kind))
+            .collect()
+    }
+
+    #[test]
+    fn find_derives() {
+        assert_eq!(search(&[], AttributePattern::derive("Serialize")), vec![
+            "struct crate::Config", "enum crate::Mode", "struct crate::api::Event",
+        ]);
+        assert_eq!(search(&[], AttributePattern::derive("serde::Serialize")), vec!["struct crate::Config"]);
+        assert_eq!(search(&["api"], AttributePattern::derive("Serialize")), vec!["struct crate::api::Event"]);
+        assert!(search(&[], AttributePattern::derive("Deserialize")).is_empty());
+    }
+
+    #[test]
+    fn find_attributes_with_arguments() {
+        assert_eq!(search(&[], AttributePattern::attribute("test")), vec![
+            "fn crate::tests::works", "fn crate::tests::works_async",
+        ]);
+        assert_eq!(search(&[], AttributePattern::attribute("cfg").arguments("test")), vec!["mod crate::tests"]);
+        assert_eq!(search(&[], AttributePattern::attribute("serde").arguments("rename_all=\"camelCase\"")), vec![
+            "struct crate::api::Event",
+        ]);
+        assert!(search(&[], AttributePattern::attribute("serde").arguments("rename_all = \"snake_case\"")).is_empty());
+        assert_eq!(search(&[], AttributePattern::attribute("route")), vec![
+            "fn crate::api::Server::handle", "fn crate::api::Server::health",
+        ]);
+    }
+}
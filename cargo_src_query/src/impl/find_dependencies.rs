@@ -30,13 +30,21 @@
 // Implementation idea:
 // 1. For relative paths, keep track of the current path and update the path as it continues
 
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
 use syn::{Ident, Item};
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum Definition {
     Struct,
     Fn,
-    Enum
+    Enum,
+    Union,
+    Trait,
+    /// A type alias, `type Name = ...;`
+    Type,
 }
 
 pub fn get_definition<T>() {}
@@ -50,26 +58,66 @@ impl Definition {
                 if def_type == Definition::Struct { Some((s.ident.clone(), item)) } else {None},
             Item::Enum(e) =>
                 if def_type == Definition::Enum { Some((e.ident.clone(), item)) } else {None},
+            Item::Union(u) =>
+                if def_type == Definition::Union { Some((u.ident.clone(), item)) } else {None},
+            Item::Trait(t) =>
+                if def_type == Definition::Trait { Some((t.ident.clone(), item)) } else {None},
+            Item::Type(t) =>
+                if def_type == Definition::Type { Some((t.ident.clone(), item)) } else {None},
             _ => None
         }
     }
 }
 
-pub fn find_definition_in_scope(scope: Vec<Item>, definition_type: Definition, name: Ident) -> Item {
+pub fn find_definition_in_scope(scope: Vec<Item>, definition_types: &[Definition], name: &Ident) -> Option<Item> {
     scope.iter()
-        .filter_map(|item| Definition::get_definition_name(item, definition_type))
-        .find(|(ident, item)| ident.to_string() == name.to_string()).unwrap().1.to_owned()
+        .filter_map(|item| definition_types.iter().find_map(|&definition_type| Definition::get_definition_name(item, definition_type)))
+        .find(|(ident, _)| ident == name).map(|(_, item)| item.to_owned())
+}
+
+/// A crate which re-exports are resolved within.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateLocation {
+    /// The name paths within the crate are reported with: `crate` for the queried crate, and the
+    /// library name for dependencies, e.g. `another_crate`
+    pub name: String,
+    /// The directory containing the crate's `Cargo.toml`
+    pub path: PathBuf,
+}
+
+/// Resolves the name a crate refers to one of its dependencies by, e.g. `renamed` for
+/// `renamed = { package = "original" }`, to the dependency.
+pub trait DependencyLookup {
+    /// The dependency `name` refers to within the crate at `crate_path`, or `None` when the crate
+    /// has no such dependency.
+    fn dependency(&self, crate_path: &Path, name: &str) -> Option<CrateLocation>;
+
+    /// The features enabled for the crate at `crate_path`, which decide the modules behind
+    /// `#[cfg(feature = "...")]` that are followed. `None` when unknown, in which case every
+    /// feature is assumed to be enabled.
+    fn enabled_features(&self, _crate_path: &Path) -> Option<BTreeSet<String>> {
+        None
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use std::path::{Path, PathBuf};
+    use std::sync::Arc;
     use maplit::btreemap;
-    use quote::quote;
-    use syn::{ItemStruct, parse2};
+    use proc_macro2::Span;
+    use quote::{quote, ToTokens};
+    use syn::{Ident, Item, ItemStruct, parse2};
+    use crate::core::{find_definition, Resolver};
+    use crate::errors::ErrorKind;
+    use crate::helpers::test::mock_dependencies::tests::MockDependencies;
     use crate::helpers::test::mock_file::tests::{mock_file_for_path, random_module_contents, file};
+    use crate::r#impl::find_dependencies::{CrateLocation, Definition};
     use crate::r#impl::find_mod::find_mod_in_crate_core;
+    use crate::r#impl::include::IncludeExpanding;
+    use crate::r#impl::trace::Tracer;
+    use crate::source::InMemory;
 
     pub fn crate_path<'a>() -> &'a Path { Path::new("/crate") }
     pub fn src_path() -> PathBuf { crate_path().join("src") }
@@ -90,12 +138,14 @@ mod tests {
         let (_, tokens_b) = random_module_contents();
 
         let path : syn::Path = parse2(quote!(crate::Definition)).unwrap();
-        mock_file_for_path(btreemap! {
+        let source_provider = mock_file_for_path(btreemap! {
             lib_src_path() => file(quote! {
                 mod ambiguous;
                 pub use ambiguous::Definition;
             }),
             src_path().join("ambiguous/mod.rs") => file(quote! {
+                mod module_a;
+                mod module_b;
                 pub use module_a::*; // Is Result in here?
                 pub use module_b::*; // Or in here? Need to check all wild-card exports.
             }),
@@ -109,7 +159,11 @@ mod tests {
         });
 
         let expected: ItemStruct = parse2(quote!(pub struct Definition {})).unwrap();
-        // assert_eq!(expected, )
+        assert_eq!(find_traced(&source_provider, &no_dependencies(), path), (Item::Struct(expected), vec![
+            "crate::Definition".to_string(),
+            "crate::ambiguous::Definition".to_string(),
+            "crate::ambiguous::module_a::Definition".to_string(),
+        ]));
     }
 
     #[test]
@@ -117,13 +171,13 @@ mod tests {
         let (_, tokens_a) = random_module_contents();
 
         let path : syn::Path = parse2(quote!(crate::with_relative_path::Definition)).unwrap();
-        mock_file_for_path(btreemap! {
+        let source_provider = mock_file_for_path(btreemap! {
             lib_src_path() => file(quote! {
                 pub mod with_relative_path;
                 mod with_definition;
             }),
             src_path().join("with_relative_path.rs") => file(quote! {
-                pub use with_definition::Definition;
+                pub use super::with_definition::Definition;
             }),
             src_path().join("with_definition.rs") => file(quote! {
                 #tokens_a
@@ -132,7 +186,10 @@ mod tests {
         });
 
         let expected: ItemStruct = parse2(quote!(pub struct Definition {})).unwrap();
-        // assert_eq!(expected, )
+        assert_eq!(find_traced(&source_provider, &no_dependencies(), path), (Item::Struct(expected), vec![
+            "crate::with_relative_path::Definition".to_string(),
+            "crate::with_definition::Definition".to_string(),
+        ]));
     }
 
     #[test]
@@ -140,13 +197,13 @@ mod tests {
         let (_, tokens_a) = random_module_contents();
 
         let path : syn::Path = parse2(quote!(crate::renamed_export::RenamedDefinition)).unwrap();
-        mock_file_for_path(btreemap! {
+        let source_provider = mock_file_for_path(btreemap! {
             lib_src_path() => file(quote! {
                 pub mod renamed_export;
                 mod with_definition;
             }),
             src_path().join("renamed_export.rs") => file(quote! {
-                pub use with_definition::Definition as RenamedDefinition;
+                pub use crate::with_definition::Definition as RenamedDefinition;
             }),
             src_path().join("with_definition.rs") => file(quote! {
                 #tokens_a
@@ -155,30 +212,162 @@ mod tests {
         });
 
         let expected: ItemStruct = parse2(quote!(pub struct Definition {})).unwrap();
-        // assert_eq!(expected, )
+        assert_eq!(find_traced(&source_provider, &no_dependencies(), path), (Item::Struct(expected), vec![
+            "crate::renamed_export::RenamedDefinition".to_string(),
+            "crate::with_definition::Definition".to_string(),
+        ]));
+    }
+
+    pub fn crate_path_other<'a>() -> &'a Path { Path::new("/another_crate") }
+    pub fn src_path_other() -> PathBuf { crate_path_other().join("src") }
+    pub fn lib_src_path_other() -> PathBuf { src_path_other().join("lib.rs") }
+
+    fn no_dependencies() -> MockDependencies {
+        MockDependencies(btreemap! {})
+    }
+
+    fn another_crate() -> CrateLocation {
+        CrateLocation { name: "another_crate".to_string(), path: crate_path_other().to_path_buf() }
+    }
+
+    fn find_traced(source_provider: &InMemory, dependencies: &MockDependencies, path: syn::Path) -> (Item, Vec<String>) {
+        let mut module: Vec<String> = path.segments.iter().skip(1).map(|s| s.ident.to_string()).collect();
+        let name = Ident::new(&module.pop().unwrap(), Span::call_site());
+        let root = CrateLocation { name: "crate".to_string(), path: crate_path().to_path_buf() };
+        let resolver = Resolver { source_provider, dependencies };
+        let tracer = Tracer::enabled(path.to_token_stream().to_string());
+
+        let definition = find_definition(&resolver, &root, &module, &name, &[Definition::Struct], &tracer).unwrap();
+        (definition.item, tracer.into_trace().provenance)
     }
 
     #[test]
     fn pub_use_with_export_from_another_crate() {
         let (_, tokens_a) = random_module_contents();
 
-        pub fn crate_path_other<'a>() -> &'a Path { Path::new("/crate") }
-        pub fn src_path_other() -> PathBuf { crate_path_other().join("src") }
-        pub fn lib_src_path_other() -> PathBuf { src_path_other().join("lib.rs") }
+        let path : syn::Path = parse2(quote!(crate::Definition)).unwrap();
+        let source_provider = mock_file_for_path(btreemap! {
+            lib_src_path() => file(quote! {
+                pub use another_crate::Definition;
+            }),
+            lib_src_path_other() => file(quote! {
+                #tokens_a
+                pub mod with_relative_path;
+                pub use with_relative_path::Definition;
+            }),
+            src_path_other().join("with_relative_path.rs") => file(quote! {
+                pub struct Definition {}
+            }),
+        });
+        let dependencies = MockDependencies(btreemap! {
+            (crate_path().to_path_buf(), "another_crate".to_string()) => another_crate(),
+        });
 
-        // TODO: Add mocks for crate paths here
+        let expected: ItemStruct = parse2(quote!(pub struct Definition {})).unwrap();
+        assert_eq!(find_traced(&source_provider, &dependencies, path), (Item::Struct(expected), vec![
+            "crate::Definition".to_string(),
+            "another_crate::Definition".to_string(),
+            "another_crate::with_relative_path::Definition".to_string(),
+        ]));
+    }
 
-        let path : syn::Path = parse2(quote!(crate::with_relative_path::Definition)).unwrap();
-        mock_file_for_path(btreemap! {
+    #[test]
+    fn pub_use_with_export_from_renamed_and_aliased_crate() {
+        // `renamed = { package = "another-crate" }` in `Cargo.toml`
+        let path : syn::Path = parse2(quote!(crate::nested::AliasedDefinition)).unwrap();
+        let source_provider = mock_file_for_path(btreemap! {
             lib_src_path() => file(quote! {
-                pub use another_crate::Definition;
+                extern crate renamed as alias;
+                pub mod nested {
+                    mod alias {}
+                    pub use ::alias::Definition as AliasedDefinition;
+                }
+            }),
+            lib_src_path_other() => file(quote! {
+                pub struct Definition {}
             }),
-            lib_src_path_other().join("with_relative_path.rs") => file(quote! {
-                pub struct Definition {};
+        });
+        let dependencies = MockDependencies(btreemap! {
+            (crate_path().to_path_buf(), "renamed".to_string()) => another_crate(),
+        });
+
+        let expected: ItemStruct = parse2(quote!(pub struct Definition {})).unwrap();
+        assert_eq!(find_traced(&source_provider, &dependencies, path), (Item::Struct(expected), vec![
+            "crate::nested::AliasedDefinition".to_string(),
+            "another_crate::Definition".to_string(),
+        ]));
+    }
+
+    fn find(source_provider: &InMemory, module: &[&str], name: &str) -> Result<Item, ErrorKind> {
+        let resolver = Resolver { source_provider, dependencies: &no_dependencies() };
+        let root = CrateLocation { name: "crate".to_string(), path: crate_path().to_path_buf() };
+        let module: Vec<String> = module.iter().map(|segment| segment.to_string()).collect();
+        find_definition(&resolver, &root, &module, &Ident::new(name, Span::call_site()), &[Definition::Struct], &Tracer::disabled())
+            .map(|found| found.item)
+            .map_err(|e| e.kind())
+    }
+
+    #[test]
+    fn glob_reexports_of_each_other_end_the_search() {
+        let source_provider = mock_file_for_path(btreemap! {
+            lib_src_path() => file(quote! {
+                pub use self::a::*;
+                pub use self::b::*;
+                pub mod a { pub use super::*; pub use self::c::*; pub mod c { pub use super::*; } }
+                pub mod b { pub use super::*; pub use self::d::*; pub mod d { pub use crate::*; pub struct Deep {} } }
+            }),
+        });
+
+        assert_eq!(find(&source_provider, &[], "Missing"), Err(ErrorKind::DefinitionNotFound));
+        let expected: ItemStruct = parse2(quote!(pub struct Deep {})).unwrap();
+        assert_eq!(find(&source_provider, &["a"], "Deep"), Ok(Item::Struct(expected)));
+    }
+
+    #[test]
+    fn renamed_reexports_of_each_other_end_the_search() {
+        let source_provider = mock_file_for_path(btreemap! {
+            lib_src_path() => file(quote! {
+                pub use self::First as Second;
+                pub use self::Second as First;
+            }),
+        });
+
+        assert_eq!(find(&source_provider, &[], "First"), Err(ErrorKind::DefinitionNotFound));
+    }
+
+    #[test]
+    fn glob_reexports_of_the_same_definition_are_not_ambiguous() {
+        let source_provider = mock_file_for_path(btreemap! {
+            lib_src_path() => file(quote! {
+                pub use self::a::*;
+                pub use self::b::*;
+                pub mod a { pub use crate::b::Definition; }
+                pub mod b { pub struct Definition {} }
+                pub mod c { pub use crate::d::*; pub use crate::e::*; }
+                pub mod d { pub struct Other {} }
+                pub mod e { pub struct Other {} }
             }),
         });
 
         let expected: ItemStruct = parse2(quote!(pub struct Definition {})).unwrap();
-        // assert_eq!(expected, )
+        assert_eq!(find(&source_provider, &[], "Definition"), Ok(Item::Struct(expected)));
+        assert_eq!(find(&source_provider, &["c"], "Other"), Err(ErrorKind::AmbiguousGlob));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn unexpanded_include_is_only_raised_for_missing_definitions() {
+        let source_provider: InMemory = [
+            (crate_path().join("Cargo.toml"), "[package]\nname = \"example\"\n"),
+            (lib_src_path(), "pub struct Definition {} include!(concat!(env!(\"OUT_DIR\"), \"/generated.rs\"));"),
+        ].into_iter().collect();
+        let source_provider = IncludeExpanding::new(Arc::new(source_provider), "/crate/target", "dev", None);
+        let resolver = Resolver { source_provider: &source_provider, dependencies: &no_dependencies() };
+        let root = CrateLocation { name: "crate".to_string(), path: crate_path().to_path_buf() };
+        let find = |name: &str| find_definition(&resolver, &root, &[], &Ident::new(name, Span::call_site()),
+                                                &[Definition::Struct], &Tracer::disabled());
+
+        let expected: ItemStruct = parse2(quote!(pub struct Definition {})).unwrap();
+        assert_eq!(find("Definition").unwrap().item, Item::Struct(expected));
+        assert_eq!(find("Generated").err().map(|e| e.kind()), Some(ErrorKind::BuildScriptNotRun));
+    }
+}
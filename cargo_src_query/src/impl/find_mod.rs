@@ -51,11 +51,12 @@
 //!
 
 
+use std::collections::BTreeSet;
+use std::fmt::{Debug, Formatter};
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
-use mocktopus::macros::mockable;
-use syn::{Attribute, Item, ItemMod, ItemUse, PathSegment, token, UseTree};
+use syn::{Attribute, Item, ItemMod, ItemUse, Meta, NestedMeta, PathSegment, UseTree};
 use anyhow::Result;
 use log::{debug, error, info, trace};
 use log_derive::logfn;
@@ -70,6 +71,8 @@ use crate::errors::message_identifier::{MODULE_PATH, SOURCE_PATH};
 use crate::errors::{IntoProcMacroError, AddMessages};
 use crate::helpers::module_path::ModulePath;
 use crate::r#impl::external::parse_file_from_path;
+use crate::r#impl::trace::{CandidateKind, Rejection, Tracer};
+use crate::source::SourceProvider;
 
 // TODO: Add visibility level checks at each level of module recursion based on visibility flag
 // TODO: Implement this use case: https://doc.rust-lang.org/reference/items/modules.html#the-path-attribute
@@ -85,23 +88,38 @@ pub struct ModuleContentsTemp {
     accessible_dependencies: Vec<syn::Path>
 }
 
-#[derive(Debug)]
 pub struct FindModuleContext<'a> {
+    source_provider: &'a dyn SourceProvider,
     crate_path: &'a std::path::Path,
     current_directory_path: &'a std::path::Path,
-    pub current_module_path: ModulePath
+    pub current_module_path: ModulePath,
+    features: Option<&'a BTreeSet<String>>,
+    tracer: &'a Tracer
+}
+
+impl Debug for FindModuleContext<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FindModuleContext")
+            .field("crate_path", &self.crate_path)
+            .field("current_directory_path", &self.current_directory_path)
+            .field("current_module_path", &self.current_module_path)
+            .finish()
+    }
 }
 
 impl<'a: 'new, 'new> FindModuleContext<'a> {
     fn current_module(&self) -> Result<Ident, CargoQueryError> {
-        self.current_module_path.get_current_module().ok_or(CargoQueryError::generic_error())
+        self.current_module_path.get_current_module().ok_or_else(CargoQueryError::could_not_find_module)
     }
 
     fn clone_and_update_current_directory_path<>(&self, new_directory_path: &'new std::path::Path) -> FindModuleContext<'new> {
         FindModuleContext {
+            source_provider: self.source_provider,
             crate_path: self.crate_path,
             current_directory_path: new_directory_path,
-            current_module_path: self.current_module_path.clone()
+            current_module_path: self.current_module_path.clone(),
+            features: self.features,
+            tracer: self.tracer
         }
     }
 }
@@ -109,10 +127,19 @@ impl<'a: 'new, 'new> FindModuleContext<'a> {
 
 #[logfn(Trace)]
 // #[logfn_inputs(Info)]
-pub fn find_mod_in_crate(crate_dir_path: &std::path::Path, module_path: ModulePath) -> Result<ModuleContents, CargoQueryError> {
+pub fn find_mod_in_crate(source_provider: &dyn SourceProvider, crate_dir_path: &std::path::Path, module_path: ModulePath) -> Result<ModuleContents, CargoQueryError> {
+    find_mod_in_crate_traced(source_provider, crate_dir_path, module_path, None, &Tracer::disabled())
+}
+
+/// [find_mod_in_crate], recording each candidate tried to `tracer`. Modules behind
+/// `#[cfg(feature = "...")]` are only followed when the feature is in `features`, or when the
+/// enabled features are unknown.
+pub(crate) fn find_mod_in_crate_traced(source_provider: &dyn SourceProvider, crate_dir_path: &std::path::Path,
+                                       module_path: ModulePath, features: Option<&BTreeSet<String>>,
+                                       tracer: &Tracer) -> Result<ModuleContents, CargoQueryError> {
     let cloned_mod_path = module_path.clone();
 
-    find_mod_in_crate_core(crate_dir_path, module_path).map_err(|e|
+    find_mod_in_crate_core(source_provider, crate_dir_path, module_path, features, tracer).map_err(|e|
         e.add_messages(btreemap! {
             // This may be better suited for the top level
             SOURCE_PATH => crate_dir_path.to_str().expect("The source path does not contain valid unicode.").to_string(),
@@ -121,7 +148,9 @@ pub fn find_mod_in_crate(crate_dir_path: &std::path::Path, module_path: ModulePa
     )
 }
 
-pub fn find_mod_in_crate_core(crate_dir_path: &std::path::Path, mut module_path: ModulePath) -> Result<ModuleContents, CargoQueryError> {
+pub(crate) fn find_mod_in_crate_core(source_provider: &dyn SourceProvider, crate_dir_path: &std::path::Path,
+                                     module_path: ModulePath, features: Option<&BTreeSet<String>>,
+                                     tracer: &Tracer) -> Result<ModuleContents, CargoQueryError> {
     // Add `src` to `src_path` since all source files exist under the `src` directory
     // TODO: Need to fact check this rule ...
     let src_path = crate_dir_path.join("src");
@@ -131,7 +160,8 @@ pub fn find_mod_in_crate_core(crate_dir_path: &std::path::Path, mut module_path:
     let lib_path = src_path.join("lib.rs");
     trace!("lib.rs path: {lib_path:#?}");
 
-    let lib_rs = parse_file_from_path(&lib_path).map_err(|e| {
+    tracer.step("crate root");
+    let lib_rs = parse_candidate(source_provider, tracer, &lib_path).map_err(|e| {
         CargoQueryError::could_not_process_lib_rs(e)
     })?;
 
@@ -144,13 +174,19 @@ pub fn find_mod_in_crate_core(crate_dir_path: &std::path::Path, mut module_path:
     // TODO: Implement private edge case. example: `use syn::__private::TokenStream;`
 
     // Find module in file
+    tracer.step(&first_module);
     let modules = get_modules_from_item_scope(lib_rs.items);
-    let module = find_module_from_scope(modules, &first_module).ok_or(CargoQueryError::generic_error())?;
+    let module = find_module_from_scope(modules, &first_module, features, tracer).ok_or_else(||
+        CargoQueryError::could_not_find_module_in_scope(first_module.clone()).at_path_segment(module_path.get_position())
+    )?;
 
     let query_context = FindModuleContext {
+        source_provider,
         crate_path: crate_dir_path,
         current_directory_path: &src_path,
-        current_module_path: module_path
+        current_module_path: module_path,
+        features,
+        tracer
     };
 
     trace!("initial query context: {query_context:#?}");
@@ -160,7 +196,7 @@ pub fn find_mod_in_crate_core(crate_dir_path: &std::path::Path, mut module_path:
 
 fn find_next_module_definition(scope_content: Vec<Item>, query_context: FindModuleContext) -> Result<ModuleContents, CargoQueryError> {
     let modules = get_modules_from_item_scope(scope_content);
-    let module = find_module_from_scope(modules, &query_context.current_module()?).unwrap();
+    let module = find_declared_module(modules, &query_context)?;
 
     recurse_module_hierarchy(module, query_context)
 
@@ -171,7 +207,7 @@ fn find_next_module_definition(scope_content: Vec<Item>, query_context: FindModu
 
 fn find_next_module(scope_content: Vec<Item>, query_context: FindModuleContext) -> Result<ModuleContents, CargoQueryError> {
     let modules = get_modules_from_item_scope(scope_content);
-    let module = find_module_from_scope(modules, &query_context.current_module()?).unwrap();
+    let module = find_declared_module(modules, &query_context)?;
 
     return recurse_module_hierarchy(module.to_owned(), query_context)
 
@@ -205,7 +241,7 @@ fn recurse_dependencies(scope_content: Vec<Item>, query_context: FindModuleConte
 /// [1] https://doc.rust-lang.org/reference/items/modules.html
 fn recurse_module_hierarchy(module: ItemMod,
                             query_context: FindModuleContext) -> Result<ModuleContents, CargoQueryError> {
-    let FindModuleContext { current_directory_path,  .. } = &query_context;
+    let FindModuleContext { source_provider, current_directory_path, tracer, .. } = &query_context;
     let current_module = query_context.current_module()?;
 
     debug!("query context: {query_context:#?}");
@@ -213,6 +249,7 @@ fn recurse_module_hierarchy(module: ItemMod,
     // Module implementation is scoped with it's definition: `mod name {}`
     if let Some(m) = module_content {
         debug!("Module implementation is scoped with it's definition: `mod name {}`", current_module);
+        tracer.candidate(CandidateKind::Inline(current_module.to_string()), None);
         return recurse_inline_mods(m.1, query_context)
     }
 
@@ -220,7 +257,9 @@ fn recurse_module_hierarchy(module: ItemMod,
     // if module.attrs.contains(&Attribute {});
 
     // Module definition refers to a separate implementation: `mod name;`
-    match parse_file_from_path(
+    match parse_candidate(
+        *source_provider,
+        tracer,
         &current_directory_path.join(format!("{}.rs", current_module.to_string()))
     ) {
         // Module implementation exists within a file in the directory
@@ -241,9 +280,10 @@ fn recurse_module_hierarchy(module: ItemMod,
             debug!("Module implementation within mod.rs in sub-directory {} in the current directory: {}/mod.rs",
                 current_module.to_string(),
                 current_directory_path.join(current_module.to_string()).to_str().unwrap());
+            let position = query_context.current_module_path.get_position();
             recurse_mod_rs(query_context.clone_and_update_current_directory_path(
                 &current_directory_path.join(current_module.to_string())
-            ))
+            )).map_err(|e| e.at_path_segment(position))
         }
     }
 }
@@ -252,10 +292,10 @@ fn recurse_module_hierarchy(module: ItemMod,
 /// 1. Determine whether the file is the module being searched for
 /// 2. Parse the module hierarchy to find the next child module
 fn recurse_mod_rs(mut query_context: FindModuleContext) -> Result<ModuleContents, CargoQueryError> {
-    let FindModuleContext { current_directory_path, ref mut current_module_path, .. } = query_context;
+    let FindModuleContext { source_provider, current_directory_path, ref mut current_module_path, features, tracer, .. } = query_context;
     let path_to_mod_rs = current_directory_path.join("mod.rs");
 
-    let mod_rs = parse_file_from_path(&path_to_mod_rs).map_err(
+    let mod_rs = parse_candidate(source_provider, tracer, &path_to_mod_rs).map_err(
         |e| CargoQueryError::could_not_process_file(&path_to_mod_rs, e)
     )?.items;
 
@@ -265,9 +305,12 @@ fn recurse_mod_rs(mut query_context: FindModuleContext) -> Result<ModuleContents
     } else { return Ok(mod_rs) };
 
     // Determine implementation type of next module
+    tracer.step(&next_mod);
     let modules = get_modules_from_item_scope(mod_rs);
 
-    let next_module_content = find_module_from_scope(modules, &next_mod).ok_or(CargoQueryError::generic_error())?;
+    let next_module_content = find_module_from_scope(modules, &next_mod, features, tracer).ok_or_else(||
+        CargoQueryError::could_not_find_module_in_scope(next_mod.clone()).at_path_segment(current_module_path.get_position())
+    )?;
 
     recurse_module_hierarchy(next_module_content, query_context)
 }
@@ -276,23 +319,25 @@ fn recurse_mod_rs(mut query_context: FindModuleContext) -> Result<ModuleContents
 /// 1. Determine whether the file is the module being searched for
 /// 2. To parse nested modules
 fn recurse_file(file: syn::File, mut query_context: FindModuleContext) -> Result<ModuleContents, CargoQueryError> {
-    let FindModuleContext { ref mut current_module_path, .. } = query_context;
+    let FindModuleContext { ref mut current_module_path, features, tracer, .. } = query_context;
     let next_mod: Ident = if let Some(module) = next_module(current_module_path) {
         module
     } else { return Ok(file.items) };
-    let module = find_module_from_items(file.items, &next_mod)?
-        .ok_or(CargoQueryError::could_not_find_module_in_file(&next_mod))?.1;
+    let position = current_module_path.get_position();
+    tracer.step(&next_mod);
+    let module = find_inline_module_from_items(file.items, &next_mod, features, tracer).map_err(|e| e.at_path_segment(position))?;
     recurse_inline_mods(module, query_context)
 }
 
 /// Parses nested modules recursively to find the queried module based on module_path
 fn recurse_inline_mods(module_contents: Vec<Item>, mut query_context: FindModuleContext) -> Result<ModuleContents, CargoQueryError> {
-    let FindModuleContext { ref mut current_module_path, .. } = query_context;
+    let FindModuleContext { ref mut current_module_path, features, tracer, .. } = query_context;
     let next_mod: Ident = if let Some(module) = next_module(current_module_path) {
         module
     } else { return Ok(module_contents) };
-    let module = find_module_from_items(module_contents, &next_mod)?
-        .ok_or(CargoQueryError::could_not_find_module_in_file(&next_mod))?.1;
+    let position = current_module_path.get_position();
+    tracer.step(&next_mod);
+    let module = find_inline_module_from_items(module_contents, &next_mod, features, tracer).map_err(|e| e.at_path_segment(position))?;
     recurse_inline_mods(module, query_context)
 }
 
@@ -305,12 +350,24 @@ fn get_modules_from_item_scope(items: Vec<Item>) -> Vec<ItemMod> {
     }).collect()
 }
 
-/// Finds the definition of a module within a given scope. Otherwise, returns an Error.
-fn find_module_from_items(item_scope: Vec<Item>, module: &Ident) -> Result<Option<(token::Brace, Vec<Item>)>, CargoQueryError>{
+/// Finds the contents of an inline module, `mod name { ... }`, within a given scope. Otherwise,
+/// returns an Error.
+fn find_inline_module_from_items(item_scope: Vec<Item>, module: &Ident, features: Option<&BTreeSet<String>>,
+                                 tracer: &Tracer) -> Result<Vec<Item>, CargoQueryError> {
     let modules = get_modules_from_item_scope(item_scope);
+    let module_definition = find_module_from_scope(modules, module, features, tracer)
+        .ok_or_else(|| CargoQueryError::could_not_find_defined_module(module))?;
 
-    Ok(modules.iter().find(|item| item.ident == module.clone()).ok_or(
-        CargoQueryError::could_not_find_defined_module(module))?.to_owned().content)
+    match module_definition.content {
+        Some((_, items)) => {
+            tracer.candidate(CandidateKind::Inline(module.to_string()), None);
+            Ok(items)
+        },
+        None => {
+            tracer.candidate(CandidateKind::Inline(module.to_string()), Some(Rejection::Missing));
+            Err(CargoQueryError::could_not_find_module_in_file(module))
+        }
+    }
 }
 
 /// Retrieves the next module in the module path and removes it from the path
@@ -321,25 +378,92 @@ fn next_module(current_module_path: &mut ModulePath) -> Option<Ident> {
     next_module
 }
 
-fn find_module_from_scope(modules: Vec<ItemMod>, module_name: &Ident) -> Option<ItemMod> {
+/// Finds the first declaration of `module_name` which is not disabled by a `#[cfg(...)]`
+/// attribute. See [disabled_by_cfg].
+fn find_module_from_scope(modules: Vec<ItemMod>, module_name: &Ident, features: Option<&BTreeSet<String>>,
+                          tracer: &Tracer) -> Option<ItemMod> {
     debug!("Finding module: {} in scope: {:?}", &module_name, &modules);
-    modules.iter().find(|item| item.ident == module_name.clone()).cloned()
+    let declaration = || CandidateKind::Declaration(module_name.to_string());
+    for module in modules.into_iter().filter(|item| item.ident == *module_name) {
+        match disabled_by_cfg(&module.attrs, features) {
+            Some(predicate) => tracer.candidate(declaration(), Some(Rejection::CfgDisabled(predicate))),
+            None => {
+                tracer.candidate(declaration(), None);
+                return Some(module)
+            }
+        }
+    }
+    tracer.candidate(declaration(), Some(Rejection::Missing));
+    None
+}
+
+/// [find_module_from_scope] for the current module of `query_context`. Errors when the module
+/// isn't declared, or only declared behind a disabled `#[cfg(...)]`.
+fn find_declared_module(modules: Vec<ItemMod>, query_context: &FindModuleContext) -> Result<ItemMod, CargoQueryError> {
+    let current_module = query_context.current_module()?;
+    find_module_from_scope(modules, &current_module, query_context.features, query_context.tracer).ok_or_else(||
+        CargoQueryError::could_not_find_module_in_scope(current_module.clone())
+            .at_path_segment(query_context.current_module_path.get_position())
+    )
+}
+
+/// Parses the file at `path`, recording it as a candidate for the current segment.
+fn parse_candidate(source_provider: &dyn SourceProvider, tracer: &Tracer, path: &std::path::Path) -> Result<syn::File> {
+    let file = parse_file_from_path(source_provider, path);
+    let rejection = file.as_ref().err().map(|e| match e.downcast_ref::<syn::Error>() {
+        Some(parse_error) => Rejection::ParseError(parse_error.to_string()),
+        None => Rejection::Missing
+    });
+    tracer.candidate(CandidateKind::File(path.to_path_buf()), rejection);
+    file
+}
+
+/// Returns the predicate of the first `#[cfg(...)]` attribute which is known to be false.
+///
+/// Queries are resolved outside of a compilation, so only `test` and `doctest` are known to be
+/// false, and `feature = "name"` is true when `name` is one of `features`. Every other option,
+/// e.g. `unix`, is assumed to be true, as is every feature when `features` is `None`.
+pub(crate) fn disabled_by_cfg(attrs: &[Attribute], features: Option<&BTreeSet<String>>) -> Option<String> {
+    let enabled = |predicate: &NestedMeta| cfg_enabled(predicate, features);
+    attrs.iter().filter(|attr| attr.path.is_ident("cfg")).find_map(|attr| match attr.parse_meta() {
+        Ok(Meta::List(list)) if !list.nested.iter().all(enabled) => Some(list.nested.to_token_stream().to_string()),
+        _ => None
+    })
+}
+
+fn cfg_enabled(predicate: &NestedMeta, features: Option<&BTreeSet<String>>) -> bool {
+    match predicate {
+        NestedMeta::Meta(Meta::Path(path)) => !(path.is_ident("test") || path.is_ident("doctest")),
+        NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("feature") => match (&name_value.lit, features) {
+            (syn::Lit::Str(feature), Some(features)) => features.contains(&feature.value()),
+            _ => true
+        },
+        NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("not") =>
+            !list.nested.iter().all(|nested| cfg_enabled(nested, features)),
+        NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("all") =>
+            list.nested.iter().all(|nested| cfg_enabled(nested, features)),
+        NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("any") =>
+            list.nested.iter().any(|nested| cfg_enabled(nested, features)),
+        _ => true
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use mocktopus::mocking::{Mockable};
     use test_log::test;
 
     mod helpers {
+        use std::collections::BTreeSet;
         use std::path::{Path, PathBuf};
         use maplit::btreemap;
         use syn::parse2;
         use quote::{quote, ToTokens};
+        use crate::errors::ErrorKind;
         use crate::helpers::module_path::ModulePath;
         use crate::helpers::test::initialize::test::initialize;
         use crate::helpers::test::mock_file::tests::{mock_file_for_path, random_module_contents, file};
-        use crate::r#impl::find_mod::find_mod_in_crate;
+        use crate::r#impl::find_mod::{find_mod_in_crate, find_mod_in_crate_traced};
+        use crate::r#impl::trace::{Candidate, CandidateKind, Rejection, Tracer};
 
         // Constants
         // Paths
@@ -359,9 +483,9 @@ mod test {
             initialize();
             let (expectation, tokens) = random_module_contents();
 
-            mock_file_for_path(btreemap! { lib_src_path() => file(quote! { #tokens }) });
+            let source_provider = mock_file_for_path(btreemap! { lib_src_path() => file(quote! { #tokens }) });
 
-            assert_eq!(find_mod_in_crate(crate_path(), ModulePath::default()).unwrap(), expectation)
+            assert_eq!(find_mod_in_crate(&source_provider, crate_path(), ModulePath::default()).unwrap(), expectation)
         }
 
         #[test]
@@ -369,11 +493,30 @@ mod test {
             initialize();
             let (_, tokens) = random_module_contents();
 
-            mock_file_for_path(btreemap! { lib_src_path() => file(quote! { #tokens }) });
+            let source_provider = mock_file_for_path(btreemap! { lib_src_path() => file(quote! { #tokens }) });
 
             let mod_path: syn::Path = parse2(quote! { foo }).unwrap();
 
-            assert!(matches!(find_mod_in_crate(crate_path(), mod_path.into()), Err(_)))
+            assert!(matches!(find_mod_in_crate(&source_provider, crate_path(), mod_path.into()), Err(_)))
+        }
+
+        #[test]
+        fn DIRECTORY_root_FILE_lib_CONTENTS_missing_nested_module() {
+            initialize();
+            let (_, tokens) = random_module_contents();
+
+            let source_provider = mock_file_for_path(btreemap! {
+                lib_src_path() => file(quote! { mod foo { mod bar { #tokens } } })
+            });
+
+            let mod_path: syn::Path = parse2(quote! { foo::baz::boop }).unwrap();
+            let error = find_mod_in_crate(&source_provider, crate_path(), mod_path.into()).unwrap_err();
+
+            // `baz` is the segment which could not be resolved
+            assert_eq!(error.path_segment(), Some(1));
+            assert_eq!(error.kind(), ErrorKind::ModuleNotFound);
+            assert!(error.to_string().starts_with("error[CSQ0001]: "));
+            assert!(error.to_string().contains("module path: foo::baz::boop"));
         }
 
         #[test]
@@ -382,7 +525,7 @@ mod test {
             let (expectation_foo, tokens_foo) = random_module_contents();
             let (expectation_bar, tokens_bar) = random_module_contents();
 
-            mock_file_for_path(btreemap! {
+            let source_provider = mock_file_for_path(btreemap! {
                 lib_src_path() => file(quote!{
                     mod foo { #tokens_foo }
                     mod bar { #tokens_bar }
@@ -390,10 +533,10 @@ mod test {
             });
 
             let mod_path: syn::Path = parse2(quote! { foo }).unwrap();
-            assert_eq!(find_mod_in_crate(crate_path(), mod_path.into()).unwrap(), expectation_foo);
+            assert_eq!(find_mod_in_crate(&source_provider, crate_path(), mod_path.into()).unwrap(), expectation_foo);
 
             let mod_path: syn::Path = parse2(quote! { bar }).unwrap();
-            assert_eq!(find_mod_in_crate(crate_path(), mod_path.into()).unwrap(), expectation_bar);
+            assert_eq!(find_mod_in_crate(&source_provider, crate_path(), mod_path.into()).unwrap(), expectation_bar);
         }
 
         #[test]
@@ -401,7 +544,7 @@ mod test {
             initialize();
             let (expectation, tokens) = random_module_contents();
 
-            mock_file_for_path(btreemap! {
+            let source_provider = mock_file_for_path(btreemap! {
                 lib_src_path() => file( quote! {
                     mod foo {
                         mod bar {
@@ -412,7 +555,7 @@ mod test {
             });
 
             let mod_path: syn::Path = parse2(quote! { foo::bar }).unwrap();
-            assert_eq!(find_mod_in_crate(crate_path(), mod_path.into()).unwrap(), expectation);
+            assert_eq!(find_mod_in_crate(&source_provider, crate_path(), mod_path.into()).unwrap(), expectation);
         }
 
         #[test]
@@ -445,13 +588,13 @@ mod test {
         fn DIRECTORY_nested_FILE_mod_CONTENT_nested_modules() {
             initialize();
             let (expectation, tokens) = random_module_contents();
-            mock_file_for_path(btreemap! {
+            let source_provider = mock_file_for_path(btreemap! {
                 lib_src_path() => file(quote!( mod foo; )),
                 src_path().join("foo/mod.rs") => file(quote!( #tokens )),
             });
 
             let mod_path: syn::Path = parse2(quote! { foo }).unwrap();
-            assert_eq!(find_mod_in_crate(crate_path(), mod_path.into()).unwrap(), expectation)
+            assert_eq!(find_mod_in_crate(&source_provider, crate_path(), mod_path.into()).unwrap(), expectation)
         }
 
 
@@ -467,14 +610,14 @@ mod test {
         fn DIRECTORY_nested_FILE_adjacent_CONTENT_nested_modules() {
             // initialize();
             let (expectation, tokens) = random_module_contents();
-            mock_file_for_path(btreemap! {
+            let source_provider = mock_file_for_path(btreemap! {
                 lib_src_path() => file(quote!( mod foo; )),
                 src_path().join("foo/mod.rs") => file(quote!( mod bar; )),
                 src_path().join("foo/bar.rs") => file(quote!( #tokens )),
             });
 
             let mod_path: syn::Path = parse2(quote! { foo::bar }).unwrap();
-            assert_eq!(find_mod_in_crate(crate_path(), mod_path.into()).unwrap(), expectation)
+            assert_eq!(find_mod_in_crate(&source_provider, crate_path(), mod_path.into()).unwrap(), expectation)
         }
 
         /// ```text
@@ -492,7 +635,7 @@ mod test {
         fn DIRECTORY_deeply_nested_FILE_adjacent_CONTENT_nested_modules() {
             initialize();
             let (expectation, tokens) = random_module_contents();
-            mock_file_for_path(btreemap! {
+            let source_provider = mock_file_for_path(btreemap! {
                 lib_src_path() => file(quote! { mod foo; }),
                 src_path().join("foo/mod.rs") => file(quote! {
                     mod hoop { }
@@ -514,7 +657,80 @@ mod test {
             });
 
             let mod_path: syn::Path = parse2(quote! { foo::baz::boop::waldo::corge }).unwrap();
-            assert_eq!(find_mod_in_crate(crate_path(), mod_path.into()).unwrap(), expectation)
+            assert_eq!(find_mod_in_crate(&source_provider, crate_path(), mod_path.into()).unwrap(), expectation)
+        }
+
+        #[test]
+        fn DIRECTORY_root_FILE_lib_CONTENTS_cfg_test_module() {
+            initialize();
+            let (expectation, tokens) = random_module_contents();
+            let source_provider = mock_file_for_path(btreemap! {
+                lib_src_path() => file(quote! {
+                    #[cfg(test)]
+                    mod foo { }
+                    #[cfg(not(test))]
+                    mod foo { #tokens }
+                    #[cfg(all(unix, not(doctest), test))]
+                    mod bar { }
+                })
+            });
+
+            let mod_path: syn::Path = parse2(quote! { foo }).unwrap();
+            assert_eq!(find_mod_in_crate(&source_provider, crate_path(), mod_path.into()).unwrap(), expectation);
+
+            let mod_path: syn::Path = parse2(quote! { bar }).unwrap();
+            assert!(find_mod_in_crate(&source_provider, crate_path(), mod_path.into()).is_err());
+        }
+
+        #[test]
+        fn DIRECTORY_root_FILE_lib_CONTENTS_cfg_feature_module() {
+            initialize();
+            let (expectation, tokens) = random_module_contents();
+            let source_provider = mock_file_for_path(btreemap! {
+                lib_src_path() => file(quote! {
+                    #[cfg(feature = "disabled")]
+                    mod foo { }
+                    #[cfg(not(feature = "disabled"))]
+                    mod foo { #tokens }
+                    #[cfg(all(feature = "enabled", feature = "disabled"))]
+                    mod bar { }
+                })
+            });
+            let features = BTreeSet::from(["enabled".to_string()]);
+
+            let mod_path: syn::Path = parse2(quote! { foo }).unwrap();
+            let found = find_mod_in_crate_traced(&source_provider, crate_path(), mod_path.into(), Some(&features), &Tracer::disabled());
+            assert_eq!(found.unwrap(), expectation);
+
+            let mod_path: syn::Path = parse2(quote! { bar }).unwrap();
+            let error = find_mod_in_crate_traced(&source_provider, crate_path(), mod_path.into(), Some(&features), &Tracer::disabled()).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::ModuleNotFound);
+        }
+
+        #[test]
+        fn DIRECTORY_nested_FILE_mod_CONTENT_trace() {
+            initialize();
+            let source_provider = mock_file_for_path(btreemap! {
+                lib_src_path() => file(quote!( mod foo; )),
+                src_path().join("foo/mod.rs") => file(quote!( #[cfg(test)] mod bar { } )),
+            });
+
+            let tracer = Tracer::enabled("foo::bar".to_string());
+            let mod_path: syn::Path = parse2(quote! { foo::bar }).unwrap();
+            assert!(find_mod_in_crate_traced(&source_provider, crate_path(), mod_path.into(), None, &tracer).is_err());
+
+            let trace = tracer.into_trace();
+            let segments: Vec<&str> = trace.steps.iter().map(|step| step.segment.as_str()).collect();
+            assert_eq!(segments, vec!["crate root", "foo", "bar"]);
+            assert_eq!(trace.steps[1].candidates, vec![
+                Candidate { kind: CandidateKind::Declaration("foo".to_string()), rejection: None },
+                Candidate { kind: CandidateKind::File(src_path().join("foo.rs")), rejection: Some(Rejection::Missing) },
+                Candidate { kind: CandidateKind::File(src_path().join("foo/mod.rs")), rejection: None },
+            ]);
+            assert_eq!(trace.steps[2].candidates, vec![
+                Candidate { kind: CandidateKind::Declaration("bar".to_string()), rejection: Some(Rejection::CfgDisabled("test".to_string())) },
+                Candidate { kind: CandidateKind::Declaration("bar".to_string()), rejection: Some(Rejection::Missing) },
+            ]);
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,380 @@
+//! # Generic Parameter Substitution
+//! A query such as `crate::Pair<String, T>` refers to an instantiation of a generic definition.
+//! The definition is found by name, and then the generic arguments of the last path segment
+//! are substituted into its fields, variants and signatures.
+//!
+//! ```text
+//! pub struct Pair<A, B, C = u32> { first: A, second: B, third: C }
+//!
+//! Pair<String, u8>      => pub struct Pair { first: String, second: u8, third: u32 }
+//! Pair<String, _>       => pub struct Pair<B> { first: String, second: B, third: u32 }
+//! Pair<String, T>       => pub struct Pair<T> { first: String, second: T, third: u32 }
+//! Pair<String, _, bool> => pub struct Pair<B> { first: String, second: B, third: bool }
+//! ```
+//!
+//! 1. Arguments are matched to parameters in declaration order. Lifetimes are matched separately
+//!    from type and const parameters, as they are in Rust.
+//! 2. Parameters without an argument fall back to their default, if they have one.
+//! 3. Parameters without an argument or default, and parameters given `_` (or `'_`) are left in
+//!    place.
+//! 4. Arguments which are generic parameters of the caller, such as `T` above, `N` in
+//!    `Buffer<N>` or a lifetime other than `'static`, are declared in place of the parameter
+//!    they're given for.
+//!
+//! Associated types of substituted parameters are rewritten as qualified paths, so `T::Output`
+//! becomes `<u32>::Output`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use proc_macro2::Ident;
+use syn::fold::{self, Fold};
+use syn::{Expr, ExprPath, GenericArgument, GenericParam, Generics, Item, Lifetime, Path, PathArguments, QSelf, Type, TypePath};
+use crate::errors::CargoQueryError;
+
+/// Substitutes the generic `arguments` of a path segment, e.g. `<String, T>`, into `item`. See
+/// the [module documentation](self). `is_parameter` tells whether an argument naming a single
+/// type, e.g. `T`, is a generic parameter of the caller. Items are returned unchanged when there
+/// are no arguments.
+pub fn substitute_generics(mut item: Item, arguments: &PathArguments, is_parameter: &dyn Fn(&Ident) -> bool)
+    -> Result<Item, CargoQueryError> {
+    let name = item_name(&item);
+    let invalid = |reason| CargoQueryError::invalid_generic_arguments(&name, arguments, reason);
+    let generic_arguments = match arguments {
+        PathArguments::None => return Ok(item),
+        PathArguments::AngleBracketed(arguments) => arguments.args.iter().collect::<Vec<_>>(),
+        PathArguments::Parenthesized(_) => return Err(invalid("parenthesized arguments are only supported for `Fn` traits")),
+    };
+    let generics = generics_mut(&mut item).ok_or_else(|| invalid("definition is not generic"))?;
+
+    let substitution = Substitution::new(generics, &generic_arguments, is_parameter).map_err(invalid)?;
+    generics.params = generics.params.clone().into_iter()
+        .filter(|param| !substitution.substitutes(param))
+        .collect();
+
+    let mut item = substitution.clone().fold_item(item);
+    let generics = generics_mut(&mut item).expect("substituting generics doesn't change the kind of an item");
+    // Parameters given the same parameter of the caller, e.g. `Pair<T, T>`, are declared once
+    let mut declared = BTreeSet::new();
+    generics.params = generics.params.clone().into_iter()
+        .map(|param| substitution.redeclare(param))
+        .filter(|param| declared.insert(param_name(param)))
+        .collect();
+    if generics.params.is_empty() {
+        generics.lt_token = None;
+        generics.gt_token = None;
+    }
+    Ok(item)
+}
+
+/// Whether `name` is a primitive type or a type of the standard prelude, which are never
+/// generic parameters of a caller.
+pub(crate) fn is_prelude_type(name: &Ident) -> bool {
+    const PRELUDE_TYPES: [&str; 23] = [
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64",
+        "i128", "isize", "f32", "f64", "String", "Vec", "Box", "Option", "Result", "Self",
+    ];
+    PRELUDE_TYPES.iter().any(|ty| name == ty)
+}
+
+fn param_name(param: &GenericParam) -> String {
+    match param {
+        GenericParam::Lifetime(param) => param.lifetime.to_string(),
+        GenericParam::Type(param) => param.ident.to_string(),
+        GenericParam::Const(param) => param.ident.to_string(),
+    }
+}
+
+fn item_name(item: &Item) -> Ident {
+    match item {
+        Item::Struct(s) => s.ident.clone(),
+        Item::Enum(e) => e.ident.clone(),
+        Item::Union(u) => u.ident.clone(),
+        Item::Fn(f) => f.sig.ident.clone(),
+        Item::Type(t) => t.ident.clone(),
+        Item::Trait(t) => t.ident.clone(),
+        _ => Ident::new("item", proc_macro2::Span::call_site()),
+    }
+}
+
+fn generics_mut(item: &mut Item) -> Option<&mut Generics> {
+    match item {
+        Item::Struct(s) => Some(&mut s.generics),
+        Item::Enum(e) => Some(&mut e.generics),
+        Item::Union(u) => Some(&mut u.generics),
+        Item::Fn(f) => Some(&mut f.sig.generics),
+        Item::Type(t) => Some(&mut t.generics),
+        Item::Trait(t) => Some(&mut t.generics),
+        _ => None,
+    }
+}
+
+/// Generic parameters mapped to the arguments which replace them.
+#[derive(Clone, Default)]
+struct Substitution {
+    types: BTreeMap<Ident, Type>,
+    consts: BTreeMap<Ident, Expr>,
+    lifetimes: BTreeMap<Ident, Lifetime>,
+    /// Type and const parameters given a generic parameter of the caller, which is declared in
+    /// their place
+    redeclared: BTreeMap<Ident, Ident>,
+    /// Lifetime parameters given a lifetime of the caller
+    redeclared_lifetimes: BTreeSet<Ident>,
+}
+
+impl Substitution {
+    fn new(generics: &Generics, arguments: &[&GenericArgument], is_parameter: &dyn Fn(&Ident) -> bool)
+        -> Result<Self, &'static str> {
+        let mut lifetime_arguments = arguments.iter().filter_map(|arg| match arg {
+            GenericArgument::Lifetime(lifetime) => Some(lifetime),
+            _ => None,
+        });
+        let mut other_arguments = arguments.iter().filter(|arg| !matches!(arg, GenericArgument::Lifetime(_)));
+
+        let mut substitution = Substitution::default();
+        for param in &generics.params {
+            match param {
+                GenericParam::Lifetime(param) => match lifetime_arguments.next() {
+                    Some(lifetime) if lifetime.ident != "_" => {
+                        substitution.lifetimes.insert(param.lifetime.ident.clone(), lifetime.clone());
+                        if lifetime.ident != "static" {
+                            substitution.redeclared_lifetimes.insert(param.lifetime.ident.clone());
+                        }
+                    },
+                    _ => {},
+                },
+                GenericParam::Type(param) => match other_arguments.next() {
+                    Some(GenericArgument::Type(Type::Infer(_))) => {},
+                    Some(GenericArgument::Type(ty)) => {
+                        if let Some(argument) = type_ident(ty).filter(|argument| is_parameter(argument)) {
+                            substitution.redeclared.insert(param.ident.clone(), argument.clone());
+                        }
+                        substitution.types.insert(param.ident.clone(), ty.clone());
+                    },
+                    Some(_) => return Err("expected a type argument"),
+                    // Defaults may refer to earlier parameters, e.g. `struct Pair<A, B = A>`
+                    None => if let Some(default) = &param.default {
+                        let default = substitution.clone().fold_type(default.clone());
+                        substitution.types.insert(param.ident.clone(), default);
+                    },
+                },
+                GenericParam::Const(param) => match other_arguments.next() {
+                    Some(GenericArgument::Type(Type::Infer(_))) => {},
+                    Some(GenericArgument::Const(expr)) => { substitution.consts.insert(param.ident.clone(), expr.clone()); },
+                    // `Array<N>` is parsed as a type argument, since `N` could name a type
+                    Some(GenericArgument::Type(Type::Path(TypePath { qself: None, path }))) => {
+                        if let Some(argument) = path.get_ident().filter(|argument| is_parameter(argument)) {
+                            substitution.redeclared.insert(param.ident.clone(), argument.clone());
+                        }
+                        let expr = Expr::Path(ExprPath { attrs: vec![], qself: None, path: path.clone() });
+                        substitution.consts.insert(param.ident.clone(), expr);
+                    },
+                    Some(_) => return Err("expected a const argument"),
+                    None => if let Some(default) = &param.default {
+                        let default = substitution.clone().fold_expr(default.clone());
+                        substitution.consts.insert(param.ident.clone(), default);
+                    },
+                },
+            }
+        }
+
+        if lifetime_arguments.next().is_some() || other_arguments.next().is_some() {
+            return Err("too many generic arguments")
+        }
+        Ok(substitution)
+    }
+
+    /// Whether `param` is replaced by its argument, rather than left in place or declared again.
+    fn substitutes(&self, param: &GenericParam) -> bool {
+        match param {
+            GenericParam::Lifetime(param) => self.lifetimes.contains_key(&param.lifetime.ident)
+                && !self.redeclared_lifetimes.contains(&param.lifetime.ident),
+            GenericParam::Type(param) => self.types.contains_key(&param.ident) && !self.redeclared.contains_key(&param.ident),
+            GenericParam::Const(param) => self.consts.contains_key(&param.ident) && !self.redeclared.contains_key(&param.ident),
+        }
+    }
+
+    /// Renames a type or const parameter given a parameter of the caller after that parameter,
+    /// which has no default. Lifetime parameters are renamed while folding.
+    fn redeclare(&self, param: GenericParam) -> GenericParam {
+        match param {
+            GenericParam::Type(mut param) => {
+                if let Some(argument) = self.redeclared.get(&param.ident) {
+                    param.ident = argument.clone();
+                    param.eq_token = None;
+                    param.default = None;
+                }
+                GenericParam::Type(param)
+            },
+            GenericParam::Const(mut param) => {
+                if let Some(argument) = self.redeclared.get(&param.ident) {
+                    param.ident = argument.clone();
+                    param.eq_token = None;
+                    param.default = None;
+                }
+                GenericParam::Const(param)
+            },
+            param => param,
+        }
+    }
+
+    /// Rewrites `T::Assoc` as `<Substituted>::Assoc` when `T` is substituted.
+    fn qualify(&mut self, qself: &Option<QSelf>, path: &Path) -> Option<(QSelf, Path)> {
+        let first = path.segments.first()?;
+        if qself.is_some() || path.leading_colon.is_some() || path.segments.len() < 2 || !first.arguments.is_empty() {
+            return None
+        }
+        let ty = self.types.get(&first.ident)?.clone();
+        let qself = QSelf {
+            lt_token: Default::default(),
+            ty: Box::new(ty),
+            position: 0,
+            as_token: None,
+            gt_token: Default::default(),
+        };
+        let path = Path {
+            leading_colon: Some(Default::default()),
+            segments: path.segments.iter().skip(1).cloned().map(|segment| self.fold_path_segment(segment)).collect(),
+        };
+        Some((qself, path))
+    }
+}
+
+/// The type named by a path of a single identifier, e.g. `T`.
+fn type_ident(ty: &Type) -> Option<&Ident> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => path.get_ident(),
+        _ => None,
+    }
+}
+
+impl Fold for Substitution {
+    fn fold_type(&mut self, ty: Type) -> Type {
+        if let Type::Path(TypePath { qself, path }) = &ty {
+            if let Some(substituted) = path.get_ident().filter(|_| qself.is_none()).and_then(|ident| self.types.get(ident)) {
+                return substituted.clone()
+            }
+            if let Some((qself, path)) = self.qualify(qself, path) {
+                return Type::Path(TypePath { qself: Some(qself), path })
+            }
+        }
+        fold::fold_type(self, ty)
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        if let Expr::Path(ExprPath { attrs, qself, path }) = &expr {
+            if let Some(substituted) = path.get_ident().filter(|_| qself.is_none()).and_then(|ident| self.consts.get(ident)) {
+                return substituted.clone()
+            }
+            if let Some((qself, path)) = self.qualify(qself, path) {
+                return Expr::Path(ExprPath { attrs: attrs.clone(), qself: Some(qself), path })
+            }
+        }
+        fold::fold_expr(self, expr)
+    }
+
+    fn fold_generic_argument(&mut self, argument: GenericArgument) -> GenericArgument {
+        // Const parameters used as arguments, e.g. `[u8; N]` in `Buffer<N>`, are parsed as types
+        if let GenericArgument::Type(Type::Path(TypePath { qself: None, path })) = &argument {
+            if let Some(substituted) = path.get_ident().and_then(|ident| self.consts.get(ident)) {
+                return GenericArgument::Const(substituted.clone())
+            }
+        }
+        fold::fold_generic_argument(self, argument)
+    }
+
+    fn fold_lifetime(&mut self, lifetime: Lifetime) -> Lifetime {
+        self.lifetimes.get(&lifetime.ident).cloned().unwrap_or(lifetime)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::{quote, ToTokens};
+    use proc_macro2::Ident;
+    use syn::{Item, parse2, PathArguments};
+    use crate::r#impl::generics::substitute_generics;
+
+    fn arguments(path: proc_macro2::TokenStream) -> PathArguments {
+        let path: syn::Path = parse2(path).unwrap();
+        path.segments.last().unwrap().arguments.clone()
+    }
+
+    /// Treats single uppercase letters as generic parameters of the caller.
+    fn is_parameter(name: &Ident) -> bool {
+        name.to_string().len() == 1
+    }
+
+    fn substitute(item: proc_macro2::TokenStream, path: proc_macro2::TokenStream) -> String {
+        substitute_generics(parse2(item).unwrap(), &arguments(path), &is_parameter).unwrap().to_token_stream().to_string()
+    }
+
+    fn expected(item: proc_macro2::TokenStream) -> String {
+        parse2::<Item>(item).unwrap().to_token_stream().to_string()
+    }
+
+    #[test]
+    fn substitutes_fields_and_respects_defaults() {
+        let item = substitute(
+            quote!(pub struct Pair<'a, A, B = Vec<A>, C> where C: Clone { first: &'a A, second: B, third: C }),
+            quote!(Pair<'static, String>)
+        );
+        assert_eq!(item, expected(
+            quote!(pub struct Pair<C> where C: Clone { first: &'static String, second: Vec<String>, third: C })
+        ));
+    }
+
+    #[test]
+    fn leaves_inferred_parameters_in_place() {
+        let item = substitute(
+            quote!(pub enum Either<L, R> { Left(L), Right { value: R } }),
+            quote!(Either<_, u32>)
+        );
+        assert_eq!(item, expected(quote!(pub enum Either<L> { Left(L), Right { value: u32 } })));
+    }
+
+    #[test]
+    fn substitutes_signatures_consts_and_associated_types() {
+        let item = substitute(
+            quote!(pub fn convert<T: Iterator, const N: usize>(items: [T; N]) -> Vec<T::Item> { todo!() }),
+            quote!(convert::<std::vec::IntoIter<u8>, 4>)
+        );
+        assert_eq!(item, expected(
+            quote!(pub fn convert(items: [std::vec::IntoIter<u8>; 4]) -> Vec<<std::vec::IntoIter<u8>>::Item> { todo!() })
+        ));
+    }
+
+    #[test]
+    fn too_many_arguments_is_an_error() {
+        let item: Item = parse2(quote!(pub struct Wrapper<T>(T);)).unwrap();
+        assert!(substitute_generics(item, &arguments(quote!(Wrapper<u8, u16>)), &is_parameter).is_err());
+    }
+
+    #[test]
+    fn declares_parameters_of_the_caller() {
+        let item = substitute(
+            quote!(pub struct Pair<A, B: Clone> { first: A, second: B }),
+            quote!(Pair<String, T>)
+        );
+        assert_eq!(item, expected(quote!(pub struct Pair<T: Clone> { first: String, second: T })));
+
+        let item = substitute(
+            quote!(pub struct Pair<'a, 'b, A, B> where A: 'a { first: &'a A, second: &'b B }),
+            quote!(Pair<'static, 'x, T, T>)
+        );
+        assert_eq!(item, expected(quote!(pub struct Pair<'x, T> where T: 'static { first: &'static T, second: &'x T })));
+    }
+
+    #[test]
+    fn declares_const_parameters_of_the_caller() {
+        let item = substitute(
+            quote!(pub struct Buffer<const SIZE: usize = 8> { data: [u8; SIZE] }),
+            quote!(Buffer<N>)
+        );
+        assert_eq!(item, expected(quote!(pub struct Buffer<const N: usize> { data: [u8; N] })));
+
+        let item = substitute(
+            quote!(pub struct Buffer<const SIZE: usize> { data: [u8; SIZE] }),
+            quote!(Buffer<LENGTH>)
+        );
+        assert_eq!(item, expected(quote!(pub struct Buffer { data: [u8; LENGTH] })));
+    }
+}
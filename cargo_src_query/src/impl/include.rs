@@ -0,0 +1,368 @@
+//! # `include!`
+//! Items can be spliced into a module from another file with `include!`, which is commonly used
+//! for code generated by a build script:
+//!
+//! ```text
+//! // build.rs
+//! std::fs::write(Path::new(&env::var("OUT_DIR")?).join("hello.rs"), "pub fn message() {}")?;
+//!
+//! // src/helpers.rs
+//! include!(concat!(env!("OUT_DIR"), "/hello.rs"));    =>    pub fn message() {}
+//! ```
+//!
+//! [IncludeExpanding] evaluates the argument of `include!` items whenever a file is parsed, and
+//! replaces them with the items of the included file. String literals, `concat!` and `env!` can
+//! be evaluated. Relative paths are resolved from the directory of the including file, like
+//! `rustc` does.
+//!
+//! `OUT_DIR` is only set while cargo compiles a package, so when resolving queries it is
+//! discovered from the build directory of the package in the target directory:
+//!
+//! ```text
+//! target/debug/build/
+//! ├── macro-core-1a2b3c4d5e6f7a8b/        the compiled build script
+//! │   └── build-script-build
+//! └── macro-core-8b7a6f5e4d3c2b1a/        where the build script ran
+//!     ├── output
+//!     └── out/                            OUT_DIR
+//!         └── hello.rs
+//! ```
+//!
+//! When a package was built with several configurations, the most recently built `out`
+//! directory is used. When it has not been built yet, expansion fails with
+//! [ErrorKind::BuildScriptNotRun](crate::errors::ErrorKind::BuildScriptNotRun).
+//!
+//! An `include!` which can't be expanded doesn't fail the rest of the file. It is kept in place
+//! with the error recorded in an [UNEXPANDED_ATTRIBUTE], and the error is only raised by lookups
+//! which don't find a definition in the module containing it. See [unexpanded_include].
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::Result;
+use log::debug;
+use quote::quote;
+use syn::{Attribute, Expr, Item, ItemMacro, Lit, Meta, NestedMeta};
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+use crate::errors::{CargoQueryError, ErrorKind};
+use crate::source::SourceProvider;
+
+/// Records why an `include!` was not expanded, e.g.
+/// `#[cargo_src_query_unexpanded(code = "CSQ0015", message = "...")]`.
+pub const UNEXPANDED_ATTRIBUTE: &str = "cargo_src_query_unexpanded";
+
+/// `include!` within included files is expanded up to this depth, which bounds files which
+/// include themselves.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Expands `include!` items in files parsed from a base provider. See the
+/// [module documentation](self).
+pub struct IncludeExpanding {
+    base: Arc<dyn SourceProvider>,
+    target_directory: PathBuf,
+    /// Name of the directory of the profile within the target directory, e.g. `debug`
+    profile_directory: String,
+    target: Option<String>,
+}
+
+impl IncludeExpanding {
+    /// `profile` is the cargo profile whose build directory `OUT_DIR` is discovered in, e.g. `dev`
+    /// or `release`, and `target` the target triple passed to cargo, if any.
+    pub fn new(base: Arc<dyn SourceProvider>, target_directory: impl Into<PathBuf>, profile: &str, target: Option<String>) -> Self {
+        let profile_directory = match profile {
+            "dev" | "test" => "debug",
+            "bench" => "release",
+            profile => profile,
+        };
+        IncludeExpanding { base, target_directory: target_directory.into(), profile_directory: profile_directory.to_string(), target }
+    }
+
+    fn expand(&self, file_path: &Path, items: Vec<Item>, depth: usize) -> Vec<Item> {
+        let mut expanded = Vec::with_capacity(items.len());
+        for item in items {
+            match item {
+                Item::Macro(mut m) if m.ident.is_none() && is_macro(&m.mac.path, "include") => {
+                    match self.expand_include(file_path, &m, depth) {
+                        Ok(items) => expanded.extend(items),
+                        Err(e) => {
+                            debug!("Could not expand `include!` in {}: {e}", file_path.display());
+                            m.attrs.push(unexpanded_attribute(&e));
+                            expanded.push(Item::Macro(m));
+                        }
+                    }
+                },
+                Item::Mod(mut m) => {
+                    if let Some((brace, items)) = m.content.take() {
+                        m.content = Some((brace, self.expand(file_path, items, depth)));
+                    }
+                    expanded.push(Item::Mod(m));
+                },
+                item => expanded.push(item),
+            }
+        }
+        expanded
+    }
+
+    fn expand_include(&self, file_path: &Path, m: &ItemMacro, depth: usize) -> Result<Vec<Item>, CargoQueryError> {
+        let included = self.include_path(file_path, &m.mac.tokens)?;
+        if depth == MAX_INCLUDE_DEPTH {
+            return Err(CargoQueryError::could_not_evaluate_include(&m.mac.tokens, "`include!` is nested too deeply"))
+        }
+        debug!("Expanding `include!` in {} with {}", file_path.display(), included.display());
+        let file = self.base.parse_file(&included)
+            .map_err(|e| CargoQueryError::could_not_process_file(&included, e))?;
+        Ok(self.expand(&included, file.items, depth + 1))
+    }
+
+    fn include_path(&self, file_path: &Path, tokens: &proc_macro2::TokenStream) -> Result<PathBuf, CargoQueryError> {
+        let argument: Expr = syn::parse2(tokens.clone())
+            .map_err(|_| CargoQueryError::could_not_evaluate_include(tokens, "expected a single path"))?;
+        let path = PathBuf::from(self.evaluate(file_path, &argument)?);
+        Ok(match path.is_absolute() {
+            true => path,
+            false => file_path.parent().unwrap_or(Path::new("")).join(path),
+        })
+    }
+
+    /// Evaluates string literals, `concat!(...)` and `env!(...)` to a string.
+    fn evaluate(&self, file_path: &Path, expression: &Expr) -> Result<String, CargoQueryError> {
+        let unsupported = || CargoQueryError::could_not_evaluate_include(
+            expression, "only string literals, `concat!` and `env!` can be evaluated"
+        );
+        match expression {
+            Expr::Lit(literal) => match &literal.lit {
+                Lit::Str(string) => Ok(string.value()),
+                _ => Err(unsupported()),
+            },
+            Expr::Macro(m) => {
+                let arguments = Punctuated::<Expr, Comma>::parse_terminated.parse2(m.mac.tokens.clone())
+                    .map_err(|_| unsupported())?;
+                if is_macro(&m.mac.path, "concat") {
+                    arguments.iter().map(|argument| self.evaluate(file_path, argument)).collect()
+                } else if is_macro(&m.mac.path, "env") {
+                    match arguments.first() {
+                        Some(Expr::Lit(literal)) => match &literal.lit {
+                            Lit::Str(variable) => self.environment_variable(file_path, &variable.value()),
+                            _ => Err(unsupported()),
+                        },
+                        _ => Err(unsupported()),
+                    }
+                } else {
+                    Err(unsupported())
+                }
+            },
+            _ => Err(unsupported()),
+        }
+    }
+
+    /// Cargo's variables of the package containing `file_path` are derived from its location, and
+    /// every other variable is read from the environment. Like cargo's, the derived paths are
+    /// absolute, so they aren't resolved relative to the including file.
+    fn environment_variable(&self, file_path: &Path, variable: &str) -> Result<String, CargoQueryError> {
+        let package_directory = || self.package_directory(file_path)
+            .ok_or_else(|| CargoQueryError::file_not_in_crate(file_path));
+        let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().to_string();
+        match variable {
+            "CARGO_MANIFEST_DIR" => Ok(absolute(package_directory()?)),
+            "OUT_DIR" => Ok(absolute(&self.out_dir(package_directory()?)?)),
+            variable => std::env::var(variable)
+                .map_err(|e| CargoQueryError::could_not_read_environment_variable(variable, e)),
+        }
+    }
+
+    /// The closest ancestor of `file_path` with a `Cargo.toml`.
+    fn package_directory<'a>(&self, file_path: &'a Path) -> Option<&'a Path> {
+        file_path.ancestors().skip(1).find(|directory| self.base.read_source(&directory.join("Cargo.toml")).is_ok())
+    }
+
+    fn out_dir(&self, package_directory: &Path) -> Result<PathBuf, CargoQueryError> {
+        // Cargo is compiling the package right now, e.g. in a procedural macro used by the package
+        if std::env::var_os("CARGO_MANIFEST_DIR").is_some_and(|directory| Path::new(&directory) == package_directory) {
+            if let Some(out_dir) = std::env::var_os("OUT_DIR") {
+                return Ok(PathBuf::from(out_dir))
+            }
+        }
+
+        let manifest = self.base.read_source(&package_directory.join("Cargo.toml"))
+            .map_err(|e| CargoQueryError::could_not_process_file(&package_directory.join("Cargo.toml"), e))?;
+        let package = package_name(&manifest).unwrap_or_else(|| {
+            package_directory.file_name().unwrap_or_default().to_string_lossy().to_string()
+        });
+
+        let mut build_directories = vec![];
+        if let Some(target) = &self.target {
+            build_directories.push(self.target_directory.join(target).join(&self.profile_directory).join("build"));
+        }
+        build_directories.push(self.target_directory.join(&self.profile_directory).join("build"));
+        build_directories.iter()
+            .find_map(|build_directory| newest_out_dir(build_directory, &package))
+            .ok_or_else(|| CargoQueryError::build_script_not_run(&package, build_directories.last().unwrap()))
+    }
+}
+
+impl SourceProvider for IncludeExpanding {
+    fn read_source(&self, path: &Path) -> Result<String> {
+        self.base.read_source(path)
+    }
+
+    fn parse_file(&self, path: &Path) -> Result<syn::File> {
+        let mut file = self.base.parse_file(path)?;
+        file.items = self.expand(path, file.items, 0);
+        Ok(file)
+    }
+}
+
+fn unexpanded_attribute(error: &CargoQueryError) -> Attribute {
+    let (code, message) = (error.kind().code(), error.top_level_message());
+    let notes = error.indexed_messages().iter()
+        .flat_map(|(index, messages)| messages.iter().map(move |message| quote!(note(#index, #message))));
+    let name = proc_macro2::Ident::new(UNEXPANDED_ATTRIBUTE, proc_macro2::Span::call_site());
+    syn::parse_quote!(#[#name(code = #code, message = #message #(, #notes)*)])
+}
+
+/// The error recorded for the first `include!` within `items` which could not be expanded.
+/// Lookups which don't find what they look for in `items` raise it, since it may have been
+/// in the included file.
+pub(crate) fn unexpanded_include(items: &[Item]) -> Option<CargoQueryError> {
+    items.iter().find_map(|item| match item {
+        Item::Macro(m) => m.attrs.iter().find(|attr| attr.path.is_ident(UNEXPANDED_ATTRIBUTE)),
+        _ => None,
+    }).map(|attr| {
+        let string = |nested: &NestedMeta| match nested {
+            NestedMeta::Lit(Lit::Str(value)) => Some(value.value()),
+            _ => None,
+        };
+        let mut code = None;
+        let mut message = String::new();
+        let mut notes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        if let Ok(Meta::List(list)) = attr.parse_meta() {
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::NameValue(name_value)) => {
+                        let Lit::Str(value) = name_value.lit else { continue };
+                        if name_value.path.is_ident("code") { code = ErrorKind::from_code(&value.value()) }
+                        if name_value.path.is_ident("message") { message = value.value() }
+                    },
+                    NestedMeta::Meta(Meta::List(note)) if note.path.is_ident("note") => {
+                        let note: Vec<Option<String>> = note.nested.iter().map(string).collect();
+                        if let [Some(index), Some(message)] = note.as_slice() {
+                            notes.entry(index.clone()).or_default().push(message.clone());
+                        }
+                    },
+                    _ => {}
+                }
+            }
+        }
+        CargoQueryError::convert_error(code.unwrap_or(ErrorKind::Internal), message, notes)
+    })
+}
+
+/// Whether `path` names the built-in macro `name`, e.g. `include` or `std::include`.
+fn is_macro(path: &syn::Path, name: &str) -> bool {
+    path.segments.last().is_some_and(|segment| segment.ident == name)
+        && (path.segments.len() == 1 || matches!(path.segments[0].ident.to_string().as_str(), "std" | "core"))
+}
+
+/// Reads `name = "..."` from the `[package]` table of a `Cargo.toml`.
+fn package_name(manifest: &str) -> Option<String> {
+    let mut in_package = false;
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+            continue
+        }
+        let Some((key, value)) = line.split_once('=') else { continue };
+        if in_package && key.trim() == "name" {
+            return Some(value.trim().trim_matches('"').to_string())
+        }
+    }
+    None
+}
+
+/// The most recently built `out` directory of `package` within a `target/<profile>/build`
+/// directory, which contains a directory named `<package>-<hash>` per configuration.
+fn newest_out_dir(build_directory: &Path, package: &str) -> Option<PathBuf> {
+    let prefix = format!("{package}-");
+    std::fs::read_dir(build_directory).ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .is_some_and(|hash| !hash.is_empty() && hash.chars().all(|c| c.is_ascii_hexdigit())))
+        .map(|entry| entry.path().join("out"))
+        .filter(|out_dir| out_dir.is_dir())
+        .max_by_key(|out_dir| out_dir.metadata().and_then(|metadata| metadata.modified()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use crate::errors::ErrorKind;
+    use crate::r#impl::include::{IncludeExpanding, unexpanded_include};
+    use crate::source::{InMemory, SourceProvider};
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn item_names(file: &syn::File) -> Vec<String> {
+        file.items.iter().filter_map(|item| match item {
+            syn::Item::Fn(f) => Some(f.sig.ident.to_string()),
+            syn::Item::Mod(m) => Some(format!("mod {}", m.ident)),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn literal_includes_are_expanded() {
+        let source_provider: InMemory = [
+            (lib_src_path(), "pub fn first() {} include!(\"generated/second.rs\"); mod inline { std::include!(\"generated/second.rs\"); }"),
+            (src_path().join("generated/second.rs"), "pub fn second() {}"),
+        ].into_iter().collect();
+        let expanding = IncludeExpanding::new(Arc::new(source_provider), "/example/target", "dev", None);
+
+        let file = expanding.parse_file(&lib_src_path()).unwrap();
+        assert_eq!(item_names(&file), vec!["first", "second", "mod inline"]);
+        let syn::Item::Mod(inline) = &file.items[2] else { panic!("Expected an inline module") };
+        assert!(matches!(&inline.content.as_ref().unwrap().1[..], [syn::Item::Fn(_)]));
+    }
+
+    #[test]
+    fn out_dir_is_discovered_from_build_directory() {
+        let target_directory = std::env::temp_dir().join(format!("cargo_src_query_include_{}", std::process::id()));
+        let out_dir = target_directory.join("debug/build/example-0123456789abcdef/out");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        std::fs::create_dir_all(target_directory.join("debug/build/example-fedcba9876543210")).unwrap();
+        std::fs::create_dir_all(target_directory.join("debug/build/example-macros-0123456789abcdef/out")).unwrap();
+
+        let source_provider: InMemory = [
+            (crate_path().join("Cargo.toml"), "[package]\nname = \"example\"\n".to_string()),
+            (lib_src_path(), "include!(concat!(env!(\"OUT_DIR\"), \"/hello.rs\"));".to_string()),
+            (out_dir.join("hello.rs"), "pub fn message() {}".to_string()),
+        ].into_iter().collect();
+        let expanding = IncludeExpanding::new(Arc::new(source_provider), &target_directory, "dev", None);
+        let file = expanding.parse_file(&lib_src_path());
+        std::fs::remove_dir_all(&target_directory).unwrap();
+
+        assert_eq!(item_names(&file.unwrap()), vec!["message"]);
+    }
+
+    #[test]
+    fn missing_build_script_output_is_reported() {
+        let source_provider: InMemory = [
+            (crate_path().join("Cargo.toml"), "[package]\nname = \"example\"\n"),
+            (lib_src_path(), "pub fn first() {} include!(concat!(env!(\"OUT_DIR\"), \"/hello.rs\"));"),
+        ].into_iter().collect();
+        let expanding = IncludeExpanding::new(Arc::new(source_provider), "/example/target", "release", None);
+
+        // The rest of the file is still parsed, and the failure is kept for lookups
+        let file = expanding.parse_file(&lib_src_path()).unwrap();
+        assert_eq!(item_names(&file), vec!["first"]);
+        assert!(matches!(&file.items[1], syn::Item::Macro(_)));
+        let error = unexpanded_include(&file.items).unwrap();
+        assert_eq!(error.kind(), ErrorKind::BuildScriptNotRun);
+        assert!(error.to_string().contains("/example/target/release/build"));
+    }
+}
@@ -0,0 +1,400 @@
+//! # Crate Index
+//! Indexing walks the entire module hierarchy of a crate, starting from its root file, and
+//! records every module (inline or file based) along with the file which implements it.
+//!
+//! File reads and `syn::parse_file` calls for modules at the same depth don't depend on each
+//! other, so discovery runs breadth first: each level of the module hierarchy is read and parsed
+//! in parallel on a [rayon] thread pool, and the next level is only scheduled once the current
+//! level has finished. Results are collected in declaration order and then sorted by module path,
+//! so the index is identical regardless of the number of threads used.
+//!
+//! `syn` data structures are not `Send`, so workers only report what they discovered. Items of an
+//! indexed module are parsed on demand with [CrateIndex::items].
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use log::{debug, trace};
+use rayon::prelude::*;
+use syn::{Attribute, Item, ItemMod, Lit, Meta, Visibility};
+use crate::errors::CargoQueryError;
+use crate::r#impl::recurse_export::resolve_path_prefix;
+use crate::source::SourceProvider;
+
+/// How a module is implemented. See [find_mod](crate::r#impl::find_mod) for examples of each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ModuleKind {
+    /// The crate root, usually `lib.rs`
+    Root,
+    /// `mod name { ... }`
+    Inline,
+    /// `mod name;` implemented in `name.rs`
+    File,
+    /// `mod name;` implemented in `name/mod.rs`
+    ModRs,
+    /// `#[path = "..."] mod name;`
+    PathAttribute,
+}
+
+/// Visibility of an item, detached from the `syn` syntax tree.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ItemVisibility {
+    /// `pub`
+    Public,
+    /// `pub(crate)`
+    Crate,
+    /// `pub(super)`, `pub(self)` or `pub(in path)`
+    Restricted(String),
+    /// No visibility modifier
+    Private,
+}
+
+impl From<&Visibility> for ItemVisibility {
+    fn from(visibility: &Visibility) -> Self {
+        match visibility {
+            Visibility::Public(_) => ItemVisibility::Public,
+            Visibility::Crate(_) => ItemVisibility::Crate,
+            Visibility::Restricted(r) => {
+                let path = r.path.segments.iter().map(|s| s.ident.to_string()).collect::<Vec<_>>().join("::");
+                if path == "crate" { ItemVisibility::Crate } else { ItemVisibility::Restricted(path) }
+            },
+            Visibility::Inherited => ItemVisibility::Private,
+        }
+    }
+}
+
+/// A module discovered while indexing a crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedModule {
+    /// Module path segments, relative to the crate root. The root module has no segments.
+    pub path: Vec<String>,
+    pub kind: ModuleKind,
+    pub visibility: ItemVisibility,
+    /// File which contains the module's implementation. For inline modules, this is the file
+    /// the module is nested in.
+    pub file_path: PathBuf,
+    /// Names of the inline modules between the top of `file_path` and this module.
+    pub inline_path: Vec<String>,
+}
+
+impl IndexedModule {
+    /// Renders the module path as `crate::path::to::module`.
+    pub fn display_path(&self) -> String {
+        std::iter::once("crate").chain(self.path.iter().map(String::as_str)).collect::<Vec<_>>().join("::")
+    }
+}
+
+/// A module declaration (`mod name;`) which could not be mapped to a readable file.
+#[derive(Debug)]
+pub struct IndexFailure {
+    pub path: Vec<String>,
+    pub error: CargoQueryError,
+}
+
+/// Every module of a crate. See the [module documentation](self).
+#[derive(Debug)]
+pub struct CrateIndex {
+    pub root_file: PathBuf,
+    /// Modules sorted by module path
+    pub modules: Vec<IndexedModule>,
+    /// Module declarations which could not be resolved, sorted by module path
+    pub failures: Vec<IndexFailure>,
+    sources: BTreeMap<PathBuf, Arc<str>>,
+}
+
+impl CrateIndex {
+    /// Returns the indexed module at the given path, relative to the crate root.
+    pub fn module(&self, path: &[String]) -> Option<&IndexedModule> {
+        self.modules.binary_search_by(|m| m.path.as_slice().cmp(path)).ok().map(|i| &self.modules[i])
+    }
+
+    /// Returns the source text of an indexed file.
+    pub fn source(&self, file_path: &Path) -> Option<&str> {
+        self.sources.get(file_path).map(|s| s.as_ref())
+    }
+
+    /// Every file read while indexing, in path order.
+    pub fn files(&self) -> impl Iterator<Item=&Path> {
+        self.sources.keys().map(PathBuf::as_path)
+    }
+
+    /// Parses the items defined within an indexed module.
+    pub fn items(&self, module: &IndexedModule) -> Result<Vec<Item>, CargoQueryError> {
+        let source = self.source(&module.file_path)
+            .ok_or_else(|| CargoQueryError::could_not_find_module_from_path(&module.file_path))?;
+        let file = syn::parse_file(source)
+            .map_err(|e| CargoQueryError::could_not_process_file(&module.file_path, e.into()))?;
+        let mut items = file.items;
+        for name in &module.inline_path {
+            items = items.into_iter().find_map(|item| match item {
+                Item::Mod(ItemMod { ident, content: Some((_, content)), .. }) if ident == name => Some(content),
+                _ => None
+            }).ok_or_else(|| CargoQueryError::could_not_find_module_from_path(&module.file_path))?;
+        }
+        Ok(items)
+    }
+
+    /// Resolves a path used within `module` to the deepest module of this crate it names, e.g.
+    /// `super::a::b::Name` to `a::b` and the remaining `Name`. `None` when the path starts with
+    /// another crate.
+    pub fn resolve_module_prefix(&self, module: &[String], segments: &[String]) -> Option<(Vec<String>, Vec<String>)> {
+        let (mut target, rest) = resolve_path_prefix(module, segments)?;
+        let prefixed = matches!(segments.first().map(String::as_str), Some("crate" | "self" | "super"));
+        let mut resolved_module = false;
+        let mut rest = rest.into_iter().peekable();
+        while let Some(segment) = rest.peek() {
+            let mut child = target.clone();
+            child.push(segment.clone());
+            if self.module(&child).is_none() { break }
+            target = child;
+            resolved_module = true;
+            rest.next();
+        }
+        let rest: Vec<String> = rest.collect();
+        // `other_crate::Name` doesn't start with a module of this crate
+        if !prefixed && !resolved_module && rest.len() > 1 {
+            return None
+        }
+        Some((target, rest))
+    }
+}
+
+/// A file which needs to be read to continue discovering modules.
+struct FileTask {
+    path: Vec<String>,
+    visibility: ItemVisibility,
+    /// Files which may implement the module, in order of precedence.
+    candidates: Vec<(PathBuf, ModuleKind)>,
+}
+
+/// Everything discovered within a single file.
+struct FileDiscovery {
+    modules: Vec<IndexedModule>,
+    source: Option<(PathBuf, Arc<str>)>,
+    children: Vec<FileTask>,
+    failures: Vec<IndexFailure>,
+}
+
+/// Indexes the crate whose root module is implemented in `root_file`. When `threads` is `None`,
+/// rayon's global thread pool is used.
+pub fn index_crate(source_provider: &dyn SourceProvider, root_file: &Path, threads: Option<usize>)
+    -> Result<CrateIndex, CargoQueryError> {
+    with_thread_pool(threads, || index_crate_core(source_provider, root_file))?
+}
+
+/// Indexes several crates at once. Crates are indexed in parallel with each other, in addition to
+/// the parallelism within each crate.
+pub fn index_crates<K: Ord + Send>(source_provider: &dyn SourceProvider, roots: Vec<(K, PathBuf)>, threads: Option<usize>)
+    -> Result<BTreeMap<K, Result<CrateIndex, CargoQueryError>>, CargoQueryError> {
+    with_thread_pool(threads, || roots.into_par_iter()
+        .map(|(key, root_file)| (key, index_crate_core(source_provider, &root_file)))
+        .collect())
+}
+
+/// Runs `operation` on a thread pool with at most `threads` threads.
+pub(crate) fn with_thread_pool<T: Send>(threads: Option<usize>, operation: impl FnOnce() -> T + Send)
+    -> Result<T, CargoQueryError> {
+    match threads {
+        None => Ok(operation()),
+        Some(threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|e| CargoQueryError::could_not_build_thread_pool(threads, e))
+            .map(|pool| pool.install(operation))
+    }
+}
+
+fn index_crate_core(source_provider: &dyn SourceProvider, root_file: &Path) -> Result<CrateIndex, CargoQueryError> {
+    let mut frontier = vec![FileTask {
+        path: vec![],
+        visibility: ItemVisibility::Public,
+        candidates: vec![(root_file.to_path_buf(), ModuleKind::Root)],
+    }];
+    let mut modules = vec![];
+    let mut failures = vec![];
+    let mut sources = BTreeMap::new();
+
+    while !frontier.is_empty() {
+        trace!("indexing {} files in parallel", frontier.len());
+        let discoveries: Vec<FileDiscovery> = frontier.into_par_iter()
+            .map(|task| discover_file(source_provider, task))
+            .collect();
+
+        frontier = vec![];
+        for discovery in discoveries {
+            modules.extend(discovery.modules);
+            failures.extend(discovery.failures);
+            sources.extend(discovery.source);
+            frontier.extend(discovery.children);
+        }
+    }
+
+    // The crate root must exist for the index to be meaningful
+    if let Some(root) = failures.iter().position(|f| f.path.is_empty()) {
+        return Err(failures.swap_remove(root).error)
+    }
+
+    modules.sort_by(|a, b| a.path.cmp(&b.path));
+    failures.sort_by(|a, b| a.path.cmp(&b.path));
+    debug!("indexed {} modules in {} files", modules.len(), sources.len());
+    Ok(CrateIndex { root_file: root_file.to_path_buf(), modules, failures, sources })
+}
+
+/// Reads and parses a single file, reporting the modules it implements and the files which need
+/// to be read next.
+fn discover_file(source_provider: &dyn SourceProvider, task: FileTask) -> FileDiscovery {
+    let FileTask { path, visibility, candidates } = task;
+    let mut discovery = FileDiscovery { modules: vec![], source: None, children: vec![], failures: vec![] };
+
+    let mut last_error = None;
+    for (file_path, kind) in candidates {
+        let source = match source_provider.read_source(&file_path) {
+            Ok(source) => source,
+            Err(e) => {
+                last_error = Some(CargoQueryError::could_not_process_file(&file_path, e));
+                continue
+            }
+        };
+        let file = match syn::parse_file(&source) {
+            Ok(file) => file,
+            Err(e) => {
+                discovery.failures.push(IndexFailure {
+                    path, error: CargoQueryError::could_not_process_file(&file_path, e.into())
+                });
+                return discovery
+            }
+        };
+
+        // Child modules of `lib.rs`, `mod.rs` and `#[path]` files are found next to them, while
+        // children of `name.rs` are found in the `name/` directory.
+        let parent_directory = file_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let child_directory = match kind {
+            ModuleKind::File => parent_directory.join(path.last().cloned().unwrap_or_default()),
+            _ => parent_directory.clone(),
+        };
+
+        let module = IndexedModule { path, kind, visibility, file_path: file_path.clone(), inline_path: vec![] };
+        discover_items(&file.items, &module, &parent_directory, &child_directory, &mut discovery);
+        discovery.modules.push(module);
+        discovery.source = Some((file_path, source.into()));
+        return discovery
+    }
+
+    discovery.failures.push(IndexFailure {
+        path,
+        error: last_error.unwrap_or_else(CargoQueryError::could_not_find_module),
+    });
+    discovery
+}
+
+/// Records the inline modules and module declarations within `items`, which implement `parent`.
+fn discover_items(items: &[Item], parent: &IndexedModule, file_directory: &Path, child_directory: &Path,
+                  discovery: &mut FileDiscovery) {
+    for module in items.iter().filter_map(|item| if let Item::Mod(m) = item { Some(m) } else { None }) {
+        let name = module.ident.to_string();
+        let mut path = parent.path.clone();
+        path.push(name.clone());
+        let visibility = ItemVisibility::from(&module.vis);
+
+        match &module.content {
+            Some((_, content)) => {
+                let mut inline_path = parent.inline_path.clone();
+                inline_path.push(name.clone());
+                let inline = IndexedModule {
+                    path, kind: ModuleKind::Inline, visibility, file_path: parent.file_path.clone(), inline_path
+                };
+                discover_items(content, &inline, &child_directory.join(&name), &child_directory.join(&name), discovery);
+                discovery.modules.push(inline);
+            },
+            None => {
+                let candidates = match path_attribute(&module.attrs) {
+                    // Outside of inline modules, `#[path]` is relative to the declaring file
+                    Some(path_attribute) if parent.inline_path.is_empty() =>
+                        vec![(file_directory.join(path_attribute), ModuleKind::PathAttribute)],
+                    Some(path_attribute) =>
+                        vec![(child_directory.join(path_attribute), ModuleKind::PathAttribute)],
+                    None => vec![
+                        (child_directory.join(format!("{name}.rs")), ModuleKind::File),
+                        (child_directory.join(&name).join("mod.rs"), ModuleKind::ModRs),
+                    ],
+                };
+                discovery.children.push(FileTask { path, visibility, candidates });
+            }
+        }
+    }
+}
+
+/// Returns the value of a `#[path = "..."]` attribute.
+pub(crate) fn path_attribute(attrs: &[Attribute]) -> Option<String> {
+    attrs.iter().filter(|a| a.path.is_ident("path")).find_map(|a| match a.parse_meta() {
+        Ok(Meta::NameValue(meta)) => match meta.lit {
+            Lit::Str(s) => Some(s.value()),
+            _ => None
+        },
+        _ => None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use maplit::btreemap;
+    use quote::quote;
+    use crate::helpers::test::mock_file::tests::{file, mock_file_for_path};
+    use crate::r#impl::index::{index_crate, ItemVisibility, ModuleKind};
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn module_paths(threads: Option<usize>) -> Vec<(String, ModuleKind)> {
+        let source_provider = mock_file_for_path(btreemap! {
+            lib_src_path() => file(quote! {
+                pub mod foo;
+                mod bar { pub mod baz; }
+                #[path = "renamed.rs"]
+                mod qux;
+            }),
+            src_path().join("foo/mod.rs") => file(quote! { mod corge; }),
+            src_path().join("foo/corge.rs") => file(quote! { mod waldo { } }),
+            src_path().join("bar/baz.rs") => file(quote! { }),
+            src_path().join("renamed.rs") => file(quote! { }),
+        });
+
+        let index = index_crate(&source_provider, &lib_src_path(), threads).unwrap();
+        assert!(index.failures.is_empty(), "{:#?}", index.failures);
+        index.modules.iter().map(|m| (m.display_path(), m.kind)).collect()
+    }
+
+    #[test]
+    fn index_discovers_every_module_kind() {
+        assert_eq!(module_paths(Some(1)), vec![
+            ("crate".to_string(), ModuleKind::Root),
+            ("crate::bar".to_string(), ModuleKind::Inline),
+            ("crate::bar::baz".to_string(), ModuleKind::File),
+            ("crate::foo".to_string(), ModuleKind::ModRs),
+            ("crate::foo::corge".to_string(), ModuleKind::File),
+            ("crate::foo::corge::waldo".to_string(), ModuleKind::Inline),
+            ("crate::qux".to_string(), ModuleKind::PathAttribute),
+        ]);
+    }
+
+    #[test]
+    fn index_is_independent_of_thread_count() {
+        assert_eq!(module_paths(Some(1)), module_paths(Some(8)));
+        assert_eq!(module_paths(Some(1)), module_paths(None));
+    }
+
+    #[test]
+    fn index_reports_missing_module_files() {
+        let source_provider = mock_file_for_path(btreemap! {
+            lib_src_path() => file(quote! { pub mod missing; }),
+        });
+
+        let index = index_crate(&source_provider, &lib_src_path(), None).unwrap();
+        assert_eq!(index.modules.len(), 1);
+        assert_eq!(index.modules[0].visibility, ItemVisibility::Public);
+        assert_eq!(index.failures.len(), 1);
+        assert_eq!(index.failures[0].path, vec!["missing".to_string()]);
+    }
+}
@@ -0,0 +1,287 @@
+//! # Incremental Invalidation
+//! Long-running processes, such as the [daemon](crate::daemon) or an IDE, keep sources cached
+//! between queries. When a workspace source changes, only the parts of the cache which depend on
+//! that file should be thrown away.
+//!
+//! A [DependencyGraph] records which modules are implemented by which files, and which modules
+//! re-export items from other modules. Changing `foo/bar.rs` then invalidates:
+//! 1. `crate::foo::bar`, along with any inline modules nested in `foo/bar.rs`
+//! 2. Every module with a `pub use` which points into one of the above
+//!
+//! When the `mod` or `use` declarations of a file change, the module hierarchy itself may have
+//! changed, so the subtrees of the modules in that file are invalidated as well and the graph
+//! should be rebuilt from a fresh [CrateIndex].
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use log::{debug, trace, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use quote::ToTokens;
+use syn::Item;
+use crate::r#impl::index::CrateIndex;
+use crate::r#impl::recurse_export::{get_reexports_from_item_scope, resolve_path_prefix, UsePath};
+use crate::source::{Cached, SourceProvider};
+
+type ModulePathSegments = Vec<String>;
+
+/// Which modules depend on which files. See the [module documentation](self).
+#[derive(Debug, Default, Clone)]
+pub struct DependencyGraph {
+    /// Modules implemented within each file, including inline modules
+    file_modules: BTreeMap<PathBuf, BTreeSet<ModulePathSegments>>,
+    /// Modules which re-export items from each module
+    reexporters: BTreeMap<ModulePathSegments, BTreeSet<ModulePathSegments>>,
+    /// `mod` and `use` declarations of each file, used to detect changes to the module hierarchy
+    declarations: BTreeMap<PathBuf, Vec<String>>,
+}
+
+/// The result of a file change.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Invalidation {
+    pub files: BTreeSet<PathBuf>,
+    /// Modules whose contents may have changed, relative to the crate root
+    pub modules: BTreeSet<ModulePathSegments>,
+    /// The module hierarchy may have changed, so the [DependencyGraph] should be rebuilt
+    pub structural: bool,
+}
+
+impl DependencyGraph {
+    pub fn from_index(index: &CrateIndex) -> Self {
+        let mut graph = DependencyGraph::default();
+        let known_modules: BTreeSet<&[String]> = index.modules.iter().map(|m| m.path.as_slice()).collect();
+
+        for module in &index.modules {
+            graph.file_modules.entry(module.file_path.clone()).or_default().insert(module.path.clone());
+
+            let items = match index.items(module) {
+                Ok(items) => items,
+                Err(e) => { warn!("skipping re-exports of {}: {}", module.display_path(), e.top_level_message()); continue }
+            };
+            for reexport in get_reexports_from_item_scope(&items) {
+                if let Some(target) = reexport_target(&known_modules, &module.path, &reexport) {
+                    trace!("{} re-exports from crate::{}", module.display_path(), target.join("::"));
+                    graph.reexporters.entry(target).or_default().insert(module.path.clone());
+                }
+            }
+        }
+
+        for file_path in index.files() {
+            if let Some(file) = index.source(file_path).and_then(|source| syn::parse_file(source).ok()) {
+                graph.declarations.insert(file_path.to_path_buf(), declarations(&file.items));
+            }
+        }
+        graph
+    }
+
+    /// Modules implemented within `file_path`, including inline modules.
+    pub fn modules_in_file(&self, file_path: &Path) -> BTreeSet<ModulePathSegments> {
+        self.file_modules.get(file_path).cloned().unwrap_or_default()
+    }
+
+    /// Every module which needs to be invalidated when `file_path` changes. When `structural`
+    /// is set, the subtrees below the modules in `file_path` are included.
+    pub fn affected_modules(&self, file_path: &Path, structural: bool) -> BTreeSet<ModulePathSegments> {
+        let mut affected = self.modules_in_file(file_path);
+        if structural {
+            let roots = affected.clone();
+            affected.extend(self.file_modules.values().flatten()
+                .filter(|m| roots.iter().any(|root| m.starts_with(root)))
+                .cloned());
+        }
+
+        // Follow re-exports transitively: a re-export of a re-export is affected as well
+        let mut unvisited: Vec<ModulePathSegments> = affected.iter().cloned().collect();
+        while let Some(module) = unvisited.pop() {
+            for reexporter in self.reexporters.get(&module).into_iter().flatten() {
+                if affected.insert(reexporter.clone()) {
+                    unvisited.push(reexporter.clone());
+                }
+            }
+        }
+        affected
+    }
+}
+
+/// The module a re-export points into, if it points into another module of this crate.
+fn reexport_target(known_modules: &BTreeSet<&[String]>, module: &[String], reexport: &UsePath) -> Option<ModulePathSegments> {
+    let (mut target, rest) = resolve_path_prefix(module, &reexport.segments)?;
+    for segment in rest {
+        target.push(segment);
+        if !known_modules.contains(target.as_slice()) {
+            target.pop();
+            break
+        }
+    }
+    (target != module).then_some(target)
+}
+
+/// Renders the `mod` and `use` declarations of a file, including those in inline modules.
+fn declarations(items: &[Item]) -> Vec<String> {
+    items.iter().flat_map(|item| match item {
+        Item::Mod(m) => match &m.content {
+            Some((_, content)) => {
+                let mut nested = vec![format!("mod {} {{", m.ident)];
+                nested.extend(declarations(content));
+                nested.push("}".to_string());
+                nested
+            },
+            None => vec![m.to_token_stream().to_string()],
+        },
+        Item::Use(u) => vec![u.to_token_stream().to_string()],
+        _ => vec![],
+    }).collect()
+}
+
+/// Invalidates a [Cached] source provider based on a [DependencyGraph].
+pub struct Invalidator {
+    sources: Arc<Cached>,
+    graph: RwLock<DependencyGraph>,
+}
+
+impl Invalidator {
+    pub fn new(sources: Arc<Cached>, graph: DependencyGraph) -> Self {
+        Invalidator { sources, graph: RwLock::new(graph) }
+    }
+
+    /// Replaces the graph, e.g. after a structural [Invalidation].
+    pub fn set_graph(&self, graph: DependencyGraph) {
+        *self.graph.write().expect("Dependency graph is poisoned") = graph;
+    }
+
+    /// Drops `file_path` from the source cache and reports every module affected by the change.
+    pub fn invalidate_file(&self, file_path: &Path) -> Invalidation {
+        self.sources.invalidate(file_path);
+
+        let graph = self.graph.read().expect("Dependency graph is poisoned");
+        let current_declarations = self.sources.read_source(file_path).ok()
+            .and_then(|source| syn::parse_file(&source).ok())
+            .map(|file| declarations(&file.items));
+        let structural = graph.declarations.contains_key(file_path)
+            && current_declarations.as_ref() != graph.declarations.get(file_path);
+
+        let invalidation = Invalidation {
+            files: BTreeSet::from([file_path.to_path_buf()]),
+            modules: graph.affected_modules(file_path, structural),
+            structural,
+        };
+        debug!("invalidated {file_path:?}: {invalidation:?}");
+        invalidation
+    }
+
+    /// Invalidates every Rust source file changed by a file system event. Created files are
+    /// structural changes, since they may implement a module which couldn't be found before.
+    pub fn invalidate_event(&self, event: &Event) -> Vec<Invalidation> {
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+            return vec![]
+        }
+        let created = matches!(event.kind, EventKind::Create(_));
+        event.paths.iter().filter(|p| p.extension().is_some_and(|e| e == "rs")).map(|path| {
+            let mut invalidation = self.invalidate_file(path);
+            invalidation.structural |= created;
+            invalidation
+        }).collect()
+    }
+
+    /// Watches `directory` recursively and invalidates every Rust source file which changes.
+    /// Invalidations are reported to `on_invalidation`. Watching stops when the returned watcher
+    /// is dropped.
+    pub fn watch(self: &Arc<Self>, directory: &Path, on_invalidation: impl Fn(Invalidation) + Send + 'static)
+        -> notify::Result<RecommendedWatcher> {
+        let invalidator = self.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) => invalidator.invalidate_event(&event).into_iter().for_each(&on_invalidation),
+            Err(e) => warn!("file watcher error: {e}"),
+        })?;
+        watcher.watch(directory, RecursiveMode::Recursive)?;
+        Ok(watcher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use maplit::btreemap;
+    use notify::{Event, EventKind};
+    use notify::event::CreateKind;
+    use quote::quote;
+    use crate::helpers::test::mock_file::tests::{file, mock_file_for_path};
+    use crate::r#impl::index::index_crate;
+    use crate::r#impl::invalidation::{DependencyGraph, Invalidator};
+    use crate::source::{Cached, Overlay, SourceProvider};
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn modules(paths: &[&str]) -> BTreeSet<Vec<String>> {
+        paths.iter().map(|p| p.split("::").filter(|s| !s.is_empty()).map(String::from).collect()).collect()
+    }
+
+    fn invalidator() -> (Arc<Overlay>, Invalidator) {
+        let source_provider = mock_file_for_path(btreemap! {
+            lib_src_path() => file(quote! {
+                pub mod foo;
+                pub mod api { pub use crate::foo::bar::Bar; }
+                pub mod prelude { pub use crate::api::*; }
+                pub mod unrelated { pub use crate::foo::Foo; }
+            }),
+            src_path().join("foo/mod.rs") => file(quote! { pub mod bar; pub struct Foo {} }),
+            src_path().join("foo/bar.rs") => file(quote! { pub struct Bar {} mod inline {} pub mod baz; }),
+            src_path().join("foo/bar/baz.rs") => file(quote! { }),
+        });
+        let overlay = Arc::new(Overlay::new(Arc::new(source_provider)));
+        let sources = Arc::new(Cached::new(overlay.clone()));
+
+        let index = index_crate(sources.as_ref(), &lib_src_path(), None).unwrap();
+        (overlay, Invalidator::new(sources, DependencyGraph::from_index(&index)))
+    }
+
+    #[test]
+    fn changing_a_file_invalidates_its_modules_and_reexports() {
+        let (overlay, invalidator) = invalidator();
+        let bar = src_path().join("foo/bar.rs");
+        overlay.set_buffer(&bar, "pub struct Bar { field: u32 } mod inline {} pub mod baz;");
+
+        let invalidation = invalidator.invalidate_file(&bar);
+        assert!(!invalidation.structural);
+        assert_eq!(invalidation.modules, modules(&["foo::bar", "foo::bar::inline", "api", "prelude"]));
+    }
+
+    #[test]
+    fn changing_module_declarations_invalidates_subtrees() {
+        let (overlay, invalidator) = invalidator();
+        let bar = src_path().join("foo/bar.rs");
+        overlay.set_buffer(&bar, "pub struct Bar {}");
+
+        let invalidation = invalidator.invalidate_file(&bar);
+        assert!(invalidation.structural);
+        assert_eq!(invalidation.modules, modules(&["foo::bar", "foo::bar::inline", "foo::bar::baz", "api", "prelude"]));
+    }
+
+    #[test]
+    fn invalidated_files_are_read_again() {
+        let (overlay, invalidator) = invalidator();
+        let foo = src_path().join("foo/mod.rs");
+        overlay.set_buffer(&foo, "pub mod bar; pub struct Foo { field: u32 }");
+
+        let invalidation = invalidator.invalidate_file(&foo);
+        assert_eq!(invalidation.modules, modules(&["foo", "unrelated"]));
+        assert_eq!(invalidator.sources.read_source(&foo).unwrap(), "pub mod bar; pub struct Foo { field: u32 }");
+    }
+
+    #[test]
+    fn creating_a_file_is_structural() {
+        let (overlay, invalidator) = invalidator();
+        let qux = src_path().join("foo/qux.rs");
+        overlay.set_buffer(&qux, "pub struct Qux {}");
+
+        let event = Event::new(EventKind::Create(CreateKind::File)).add_path(qux.clone());
+        let invalidations = invalidator.invalidate_event(&event);
+        assert_eq!(invalidations.len(), 1);
+        assert!(invalidations[0].structural);
+        assert_eq!(invalidations[0].files, BTreeSet::from([qux]));
+    }
+}
@@ -0,0 +1,300 @@
+//! # Reverse Lookup
+//! Maps a file location, such as `src/foo/bar.rs:42:10`, back to the module implemented by the
+//! file and the innermost item enclosing the position:
+//!
+//! ```text
+//! // src/foo/bar.rs, declared in `src/foo/mod.rs` as `mod bar;`
+//! pub struct Baz;
+//!
+//! mod inner {
+//!     impl super::Baz {
+//!         pub fn method(&self) {}     // src/foo/bar.rs:5:25 => crate::foo::bar::Baz::method
+//!     }
+//! }
+//! ```
+//!
+//! Files are mapped to modules with a [CrateIndex], so `#[path]` remappings are accounted for.
+//! When a file implements several modules, the shortest module path is used. Inline modules
+//! containing the position are appended to the file's module path. Members of impls are named
+//! after the module of the implemented type, which may differ from the module of the impl.
+//!
+//! Lines are 1-based, and columns are 1-based character offsets, as reported by editors and
+//! `rustc`. Positions need source locations of parsed files, which requires the `span-locations`
+//! feature and are only available outside of procedural macros, e.g. in the command line tooling
+//! or the daemon. The feature is always enabled for this crate's unit tests. Files can be looked
+//! up without a position regardless.
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::errors::CargoQueryError;
+use crate::r#impl::index::CrateIndex;
+
+/// A file path with an optional line and column, e.g. `src/lib.rs:42:10`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file_path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl SourceLocation {
+    pub fn new(file_path: impl Into<PathBuf>, line: Option<usize>, column: Option<usize>) -> Self {
+        SourceLocation { file_path: file_path.into(), line, column }
+    }
+}
+
+/// Parses `path`, `path:line` and `path:line:column`.
+impl FromStr for SourceLocation {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(location: &str) -> Result<Self, Self::Err> {
+        let mut parts = location.rsplitn(3, ':').collect::<Vec<_>>();
+        parts.reverse();
+        let numeric = |part: &&str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+        Ok(match parts.as_slice() {
+            [path, line, column] if numeric(line) && numeric(column) =>
+                SourceLocation::new(*path, Some(line.parse()?), Some(column.parse()?)),
+            [path, line, column] if numeric(column) =>
+                SourceLocation::new(format!("{path}:{line}"), Some(column.parse()?), None),
+            [path, line] if numeric(line) => SourceLocation::new(*path, Some(line.parse()?), None),
+            _ => SourceLocation::new(location, None, None),
+        })
+    }
+}
+
+/// The innermost item enclosing a position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnclosingItem {
+    /// Module `path` is relative to: the innermost module containing the position, or the module
+    /// of the implemented type for impls and their members
+    pub module: Vec<String>,
+    /// Names from `module` to the item, e.g. `["Baz", "method"]` for a method of `impl Baz`
+    pub path: Vec<String>,
+    /// The kind of the innermost item, e.g. `fn` or `struct`
+    pub kind: &'static str,
+}
+
+/// The result of a reverse lookup. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReverseLookup {
+    /// Module implemented by the file, relative to the crate root
+    pub file_module: Vec<String>,
+    /// Innermost module containing the position. Differs from `file_module` within inline modules.
+    pub module: Vec<String>,
+    /// `None` when no line was given, or the position is not within an item
+    pub item: Option<EnclosingItem>,
+}
+
+impl ReverseLookup {
+    /// Path segments of the innermost item, or of the module when there is no item.
+    pub fn path(&self) -> Vec<String> {
+        match &self.item {
+            Some(item) => item.module.iter().chain(&item.path).cloned().collect(),
+            None => self.module.clone(),
+        }
+    }
+}
+
+/// Renders the full path, e.g. `crate::foo::bar::Baz::method`.
+impl Display for ReverseLookup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", std::iter::once("crate".to_string()).chain(self.path()).collect::<Vec<_>>().join("::"))
+    }
+}
+
+/// Finds the module implemented by `location.file_path`, and the item enclosing its line and
+/// column. Relative paths are resolved from `crate_path`, the directory containing `Cargo.toml`.
+pub fn locate(index: &CrateIndex, crate_path: &Path, location: &SourceLocation) -> Result<ReverseLookup, CargoQueryError> {
+    let file_path = crate_path.join(&location.file_path);
+    let file_module = index.modules.iter()
+        .filter(|module| module.file_path == file_path && module.inline_path.is_empty())
+        .min_by(|a, b| a.path.len().cmp(&b.path.len()).then_with(|| a.path.cmp(&b.path)))
+        .ok_or_else(|| CargoQueryError::file_not_in_crate(&file_path))?;
+
+    let lookup = ReverseLookup { file_module: file_module.path.clone(), module: file_module.path.clone(), item: None };
+    match location.line {
+        None => Ok(lookup),
+        Some(line) => {
+            let source = index.source(&file_path).ok_or_else(|| CargoQueryError::file_not_in_crate(&file_path))?;
+            let file = syn::parse_file(source).map_err(|e| CargoQueryError::could_not_process_file(&file_path, e.into()))?;
+            position::locate_position(index, file.items, line, location.column, lookup)
+        }
+    }
+}
+
+#[cfg(not(any(test, feature = "span-locations")))]
+mod position {
+    use syn::Item;
+    use crate::errors::CargoQueryError;
+    use crate::r#impl::index::CrateIndex;
+    use crate::r#impl::locate::ReverseLookup;
+
+    pub fn locate_position(_: &CrateIndex, _: Vec<Item>, _: usize, _: Option<usize>, _: ReverseLookup) -> Result<ReverseLookup, CargoQueryError> {
+        Err(CargoQueryError::feature_disabled("span-locations", "Finding the item at a line and column needs source locations."))
+    }
+}
+
+// `proc-macro2/span-locations` is enabled by a dev-dependency for unit tests
+#[cfg(any(test, feature = "span-locations"))]
+mod position {
+    use proc_macro2::LineColumn;
+    use syn::spanned::Spanned;
+    use syn::{ImplItem, Item, TraitItem, Type};
+    use crate::errors::CargoQueryError;
+    use crate::r#impl::index::CrateIndex;
+    use crate::r#impl::locate::{EnclosingItem, ReverseLookup};
+
+    /// Descends into the inline modules containing the position, and then finds the item
+    /// enclosing it.
+    pub fn locate_position(index: &CrateIndex, mut items: Vec<Item>, line: usize, column: Option<usize>, mut lookup: ReverseLookup)
+        -> Result<ReverseLookup, CargoQueryError> {
+        // `proc_macro2` columns are 0-based
+        let position = LineColumn { line, column: column.map_or(0, |column| column.saturating_sub(1)) };
+        loop {
+            let item = match items.into_iter().find(|item| contains(item.span(), position, column.is_some())) {
+                Some(item) => item,
+                None => return Ok(lookup),
+            };
+            match item {
+                Item::Mod(module) if module.content.is_some() => {
+                    lookup.module.push(module.ident.to_string());
+                    items = module.content.map(|(_, items)| items).unwrap_or_default();
+                },
+                item => {
+                    lookup.item = enclosing_item(index, &lookup.module, &item, position, column.is_some());
+                    return Ok(lookup)
+                }
+            }
+        }
+    }
+
+    /// Whether `position` is within `span`. Without a column, any position on the line matches.
+    fn contains(span: proc_macro2::Span, position: LineColumn, has_column: bool) -> bool {
+        let (start, end) = (span.start(), span.end());
+        if !has_column {
+            return start.line <= position.line && position.line <= end.line
+        }
+        (start.line, start.column) <= (position.line, position.column) && (position.line, position.column) < (end.line, end.column)
+    }
+
+    fn enclosing_item(index: &CrateIndex, module: &[String], item: &Item, position: LineColumn, has_column: bool) -> Option<EnclosingItem> {
+        let named = |name: &dyn ToString, kind| Some(EnclosingItem { module: module.to_vec(), path: vec![name.to_string()], kind });
+        match item {
+            Item::Const(i) => named(&i.ident, "const"),
+            Item::Enum(i) => named(&i.ident, "enum"),
+            Item::ExternCrate(i) => named(&i.ident, "extern crate"),
+            Item::Fn(i) => named(&i.sig.ident, "fn"),
+            Item::Macro(i) => named(i.ident.as_ref()?, "macro_rules!"),
+            Item::Mod(i) => named(&i.ident, "mod"),
+            Item::Static(i) => named(&i.ident, "static"),
+            Item::Struct(i) => named(&i.ident, "struct"),
+            Item::Type(i) => named(&i.ident, "type"),
+            Item::Union(i) => named(&i.ident, "union"),
+            Item::Trait(i) => {
+                let member = i.items.iter()
+                    .find(|member| contains(member.span(), position, has_column))
+                    .and_then(trait_item_name);
+                Some(with_member(module.to_vec(), vec![i.ident.to_string()], "trait", member))
+            },
+            Item::Impl(i) => {
+                let segments: Vec<String> = match i.self_ty.as_ref() {
+                    Type::Path(ty) => ty.path.segments.iter().map(|segment| segment.ident.to_string()).collect(),
+                    _ => return None,
+                };
+                // Types of other crates, e.g. `impl Display for String`, are named within the impl's module
+                let (type_module, type_path) = index.resolve_module_prefix(module, &segments)
+                    .filter(|(_, rest)| !rest.is_empty())
+                    .unwrap_or_else(|| (module.to_vec(), segments[segments.len() - 1..].to_vec()));
+                let member = i.items.iter()
+                    .find(|member| contains(member.span(), position, has_column))
+                    .and_then(impl_item_name);
+                Some(with_member(type_module, type_path, "impl", member))
+            },
+            _ => None,
+        }
+    }
+
+    fn with_member(module: Vec<String>, mut path: Vec<String>, parent_kind: &'static str,
+                   member: Option<(String, &'static str)>) -> EnclosingItem {
+        match member {
+            Some((name, kind)) => {
+                path.push(name);
+                EnclosingItem { module, path, kind }
+            },
+            None => EnclosingItem { module, path, kind: parent_kind },
+        }
+    }
+
+    fn impl_item_name(item: &ImplItem) -> Option<(String, &'static str)> {
+        match item {
+            ImplItem::Const(i) => Some((i.ident.to_string(), "const")),
+            ImplItem::Method(i) => Some((i.sig.ident.to_string(), "fn")),
+            ImplItem::Type(i) => Some((i.ident.to_string(), "type")),
+            _ => None,
+        }
+    }
+
+    fn trait_item_name(item: &TraitItem) -> Option<(String, &'static str)> {
+        match item {
+            TraitItem::Const(i) => Some((i.ident.to_string(), "const")),
+            TraitItem::Method(i) => Some((i.sig.ident.to_string(), "fn")),
+            TraitItem::Type(i) => Some((i.ident.to_string(), "type")),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use crate::r#impl::index::index_crate;
+    use crate::r#impl::locate::{locate, SourceLocation};
+    use crate::source::InMemory;
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn lookup(location: &str) -> String {
+        let source_provider: InMemory = [
+            (lib_src_path(), "pub mod foo;\n#[path = \"renamed.rs\"]\nmod qux;\n"),
+            (src_path().join("foo/mod.rs"), "mod bar;\n"),
+            (src_path().join("foo/bar.rs"), [
+                "pub struct Baz;",
+                "",
+                "mod inner {",
+                "    impl super::Baz {",
+                "        pub fn method(&self) {}",
+                "    }",
+                "}",
+            ].join("\n").as_str()),
+            (src_path().join("renamed.rs"), "fn renamed() {}\n"),
+        ].into_iter().collect();
+
+        let index = index_crate(&source_provider, &lib_src_path(), Some(1)).unwrap();
+        locate(&index, crate_path(), &location.parse().unwrap()).unwrap().to_string()
+    }
+
+    #[test]
+    fn parse_source_locations() {
+        assert_eq!("src/lib.rs:42:10".parse(), Ok(SourceLocation::new("src/lib.rs", Some(42), Some(10))));
+        assert_eq!("src/lib.rs:42".parse(), Ok(SourceLocation::new("src/lib.rs", Some(42), None)));
+        assert_eq!("src/lib.rs".parse(), Ok(SourceLocation::new("src/lib.rs", None, None)));
+    }
+
+    #[test]
+    fn locate_modules() {
+        assert_eq!(lookup("src/foo/bar.rs"), "crate::foo::bar");
+        assert_eq!(lookup("src/renamed.rs"), "crate::qux");
+    }
+
+    #[test]
+    fn locate_items() {
+        assert_eq!(lookup("src/foo/bar.rs:1:5"), "crate::foo::bar::Baz");
+        assert_eq!(lookup("src/foo/bar.rs:2"), "crate::foo::bar");
+        assert_eq!(lookup("src/foo/bar.rs:4"), "crate::foo::bar::Baz");
+        assert_eq!(lookup("src/foo/bar.rs:5:20"), "crate::foo::bar::Baz::method");
+        assert_eq!(lookup("src/renamed.rs:1"), "crate::qux::renamed");
+    }
+}
@@ -1,5 +1,20 @@
+pub mod associated;
+pub mod attribute_search;
 pub mod find_mod;
 pub mod find_dependencies;
-mod recurse_export;
+pub mod generics;
+pub mod include;
+pub mod index;
+pub mod invalidation;
+pub mod locate;
+pub mod module_graph;
+pub mod path_pattern;
+pub mod public_api;
+pub mod public_path;
+pub mod recurse_export;
+pub mod references;
+pub mod trace;
+pub mod type_alias;
+pub mod verbatim;
 mod recurse_mod;
 mod external;
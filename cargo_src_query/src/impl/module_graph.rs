@@ -0,0 +1,328 @@
+//! # Module Graphs
+//! Renders the module hierarchy of a [CrateIndex] as [Graphviz DOT](https://graphviz.org) or
+//! [Mermaid](https://mermaid.js.org) text. Each module is labelled with how it is implemented and
+//! its visibility, and declarations which could not be resolved to a file are shown as well:
+//!
+//! ```text
+//! // lib.rs
+//! pub mod shapes;                     graph TD
+//! mod util { }                            m0["example<br/>crate root · lib.rs"]
+//! pub use shapes::Circle;                 m1["shapes<br/>shapes/mod.rs · pub"]
+//!                                         m0 --> m1
+//! // shapes/mod.rs                        m2["util<br/>inline · private"]
+//! pub struct Circle;                      m0 --> m2
+//!                                         m0 -. "pub use shapes::Circle" .-> m1
+//! ```
+//!
+//! Items and re-export edges are optional, see [GraphOptions]. A re-export points at the item it
+//! names when items are rendered, and at the module it names otherwise. Re-exports of other
+//! crates are not rendered.
+
+use std::fmt::Write;
+use std::str::FromStr;
+use syn::{Item, Visibility};
+use crate::r#impl::index::{CrateIndex, IndexedModule, ItemVisibility, ModuleKind};
+use crate::r#impl::recurse_export::flatten_use_tree;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT, e.g. for `dot -Tsvg`
+    Dot,
+    /// Mermaid flowchart, which renders in Markdown on most code hosts
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err(format!("unknown graph format `{s}`, expected `dot` or `mermaid`")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GraphOptions {
+    /// Render the items defined in each module, e.g. `struct Circle`
+    pub items: bool,
+    /// Render an edge for each `use` declaration which is visible outside of its module
+    pub reexports: bool,
+}
+
+struct Node {
+    id: String,
+    label: Vec<String>,
+    shape: Shape,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Module,
+    Item,
+    Unresolved,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EdgeKind {
+    /// A module to its child module
+    Child,
+    /// A module to an item it defines
+    Defines,
+    /// A `use` declaration, labelled with the declaration
+    Reexport,
+}
+
+struct Edge {
+    from: String,
+    to: String,
+    kind: EdgeKind,
+    label: Option<String>,
+}
+
+/// Renders the module hierarchy of `index`. `crate_name` labels the crate root.
+pub fn module_graph(index: &CrateIndex, crate_name: &str, format: GraphFormat, options: GraphOptions) -> String {
+    let (nodes, edges) = graph(index, crate_name, options);
+    match format {
+        GraphFormat::Dot => render_dot(crate_name, &nodes, &edges),
+        GraphFormat::Mermaid => render_mermaid(&nodes, &edges),
+    }
+}
+
+fn module_id(index: &CrateIndex, path: &[String]) -> Option<String> {
+    index.modules.binary_search_by(|m| m.path.as_slice().cmp(path)).ok().map(|i| format!("m{i}"))
+}
+
+fn graph(index: &CrateIndex, crate_name: &str, options: GraphOptions) -> (Vec<Node>, Vec<Edge>) {
+    let mut nodes = vec![];
+    let mut edges = vec![];
+    let parsed: Vec<Vec<Item>> = index.modules.iter()
+        .map(|module| match options.items || options.reexports {
+            true => index.items(module).unwrap_or_default(),
+            false => vec![],
+        })
+        .collect();
+
+    for (i, module) in index.modules.iter().enumerate() {
+        let id = format!("m{i}");
+        let name = module.path.last().map_or(crate_name, String::as_str);
+        nodes.push(Node { id: id.clone(), label: vec![name.to_string(), module_description(index, module)], shape: Shape::Module });
+        if let Some((_, parent)) = module.path.split_last() {
+            edges.extend(module_id(index, parent).map(|from| Edge { from, to: id.clone(), kind: EdgeKind::Child, label: None }));
+        }
+
+        if options.items {
+            for (j, (kind, name, visibility)) in parsed[i].iter().filter_map(describe_item).enumerate() {
+                let item_id = format!("{id}_i{j}");
+                nodes.push(Node { id: item_id.clone(), label: vec![format!("{kind} {name}"), visibility_label(&visibility)], shape: Shape::Item });
+                edges.push(Edge { from: id.clone(), to: item_id, kind: EdgeKind::Defines, label: None });
+            }
+        }
+    }
+
+    for (i, failure) in index.failures.iter().enumerate() {
+        let id = format!("u{i}");
+        let name = failure.path.last().cloned().unwrap_or_default();
+        nodes.push(Node { id: id.clone(), label: vec![name, "unresolved".to_string()], shape: Shape::Unresolved });
+        if let Some((_, parent)) = failure.path.split_last() {
+            edges.extend(module_id(index, parent).map(|from| Edge { from, to: id, kind: EdgeKind::Child, label: None }));
+        }
+    }
+
+    if options.reexports {
+        for (i, module) in index.modules.iter().enumerate() {
+            for item in &parsed[i] {
+                let reexport = match item {
+                    Item::Use(u) if !matches!(u.vis, Visibility::Inherited) => u,
+                    _ => continue,
+                };
+                for path in flatten_use_tree(&reexport.tree) {
+                    let (target, rest) = match index.resolve_module_prefix(&module.path, &path.segments) {
+                        Some(resolved) => resolved,
+                        None => continue,
+                    };
+                    let target_id = match rest.as_slice() {
+                        [name] if options.items => item_id(index, &parsed, &target, name),
+                        _ => None,
+                    }.or_else(|| module_id(index, &target));
+                    let mut label = format!("{} use {}", visibility_label(&ItemVisibility::from(&reexport.vis)), path.segments.join("::"));
+                    if path.glob { label.push_str("::*") }
+                    if let Some(rename) = &path.rename { label.push_str(&format!(" as {rename}")) }
+                    edges.extend(target_id.map(|to| Edge { from: format!("m{i}"), to, kind: EdgeKind::Reexport, label: Some(label) }));
+                }
+            }
+        }
+    }
+
+    (nodes, edges)
+}
+
+/// Id of the node of the item `name` defined in the module at `path`.
+fn item_id(index: &CrateIndex, parsed: &[Vec<Item>], path: &[String], name: &str) -> Option<String> {
+    let i = index.modules.binary_search_by(|m| m.path.as_slice().cmp(path)).ok()?;
+    let j = parsed[i].iter().filter_map(describe_item).position(|(_, item_name, _)| item_name == name)?;
+    Some(format!("m{i}_i{j}"))
+}
+
+/// How a module is implemented and its visibility, e.g. `shapes/mod.rs · pub`.
+fn module_description(index: &CrateIndex, module: &IndexedModule) -> String {
+    let file_name = |levels: usize| {
+        let components: Vec<_> = module.file_path.components().rev().take(levels).collect();
+        components.into_iter().rev().map(|c| c.as_os_str().to_string_lossy().to_string()).collect::<Vec<_>>().join("/")
+    };
+    let kind = match module.kind {
+        ModuleKind::Root => format!("crate root · {}", file_name(1)),
+        ModuleKind::Inline => "inline".to_string(),
+        ModuleKind::File => file_name(1),
+        ModuleKind::ModRs => file_name(2),
+        ModuleKind::PathAttribute => {
+            let root_directory = index.root_file.parent().unwrap_or(&index.root_file);
+            let file = module.file_path.strip_prefix(root_directory).unwrap_or(&module.file_path);
+            format!("#[path] {}", file.display())
+        },
+    };
+    match module.kind {
+        ModuleKind::Root => kind,
+        _ => format!("{kind} · {}", visibility_label(&module.visibility)),
+    }
+}
+
+fn visibility_label(visibility: &ItemVisibility) -> String {
+    match visibility {
+        ItemVisibility::Public => "pub".to_string(),
+        ItemVisibility::Crate => "pub(crate)".to_string(),
+        ItemVisibility::Restricted(path) if path == "self" || path == "super" => format!("pub({path})"),
+        ItemVisibility::Restricted(path) => format!("pub(in {path})"),
+        ItemVisibility::Private => "private".to_string(),
+    }
+}
+
+/// The kind, name and visibility of a named item other than modules and `use` declarations.
+fn describe_item(item: &Item) -> Option<(&'static str, String, ItemVisibility)> {
+    let (kind, ident, vis) = match item {
+        Item::Const(i) => ("const", &i.ident, &i.vis),
+        Item::Enum(i) => ("enum", &i.ident, &i.vis),
+        Item::Fn(i) => ("fn", &i.sig.ident, &i.vis),
+        Item::Macro(i) => return i.ident.as_ref().map(|ident| ("macro_rules!", ident.to_string(), ItemVisibility::Private)),
+        Item::Static(i) => ("static", &i.ident, &i.vis),
+        Item::Struct(i) => ("struct", &i.ident, &i.vis),
+        Item::Trait(i) => ("trait", &i.ident, &i.vis),
+        Item::TraitAlias(i) => ("trait", &i.ident, &i.vis),
+        Item::Type(i) => ("type", &i.ident, &i.vis),
+        Item::Union(i) => ("union", &i.ident, &i.vis),
+        _ => return None,
+    };
+    Some((kind, ident.to_string(), ItemVisibility::from(vis)))
+}
+
+fn render_dot(crate_name: &str, nodes: &[Node], edges: &[Edge]) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut dot = format!("digraph \"{}\" {{\n    node [shape=box];\n", escape(crate_name));
+    for node in nodes {
+        let label = node.label.iter().map(|line| escape(line)).collect::<Vec<_>>().join("\\n");
+        let attributes = match node.shape {
+            Shape::Module => "",
+            Shape::Item => ", shape=ellipse",
+            Shape::Unresolved => ", style=dashed, color=red",
+        };
+        writeln!(dot, "    {} [label=\"{label}\"{attributes}];", node.id).unwrap();
+    }
+    for edge in edges {
+        let mut attributes = match edge.kind {
+            EdgeKind::Child => vec![],
+            EdgeKind::Defines => vec!["arrowhead=none".to_string()],
+            EdgeKind::Reexport => vec!["style=dashed".to_string()],
+        };
+        if let Some(label) = &edge.label {
+            attributes.push(format!("label=\"{}\"", escape(label)));
+        }
+        match attributes.is_empty() {
+            true => writeln!(dot, "    {} -> {};", edge.from, edge.to).unwrap(),
+            false => writeln!(dot, "    {} -> {} [{}];", edge.from, edge.to, attributes.join(", ")).unwrap(),
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn render_mermaid(nodes: &[Node], edges: &[Edge]) -> String {
+    let escape = |s: &str| s.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;");
+    let mut mermaid = "graph TD\n".to_string();
+    for node in nodes {
+        let label = node.label.iter().map(|line| escape(line)).collect::<Vec<_>>().join("<br/>");
+        match node.shape {
+            Shape::Module => writeln!(mermaid, "    {}[\"{label}\"]", node.id).unwrap(),
+            Shape::Item => writeln!(mermaid, "    {}([\"{label}\"])", node.id).unwrap(),
+            Shape::Unresolved => writeln!(mermaid, "    {}{{{{\"{label}\"}}}}", node.id).unwrap(),
+        }
+    }
+    for edge in edges {
+        match (edge.kind, &edge.label) {
+            (EdgeKind::Child, _) => writeln!(mermaid, "    {} --> {}", edge.from, edge.to).unwrap(),
+            (EdgeKind::Defines, _) => writeln!(mermaid, "    {} --- {}", edge.from, edge.to).unwrap(),
+            (EdgeKind::Reexport, Some(label)) => writeln!(mermaid, "    {} -. \"{}\" .-> {}", edge.from, escape(label), edge.to).unwrap(),
+            (EdgeKind::Reexport, None) => writeln!(mermaid, "    {} -.-> {}", edge.from, edge.to).unwrap(),
+        }
+    }
+    mermaid
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use crate::r#impl::index::index_crate;
+    use crate::r#impl::module_graph::{GraphFormat, GraphOptions, module_graph};
+    use crate::source::InMemory;
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn source_provider() -> InMemory {
+        [
+            (lib_src_path(), "pub mod shapes; mod util { } #[path = \"other/name.rs\"] pub(crate) mod renamed; mod missing; pub use shapes::Circle;"),
+            (src_path().join("shapes/mod.rs"), "pub struct Circle; fn area() {}"),
+            (src_path().join("other/name.rs"), ""),
+        ].into_iter().collect()
+    }
+
+    #[test]
+    fn module_graph_as_mermaid() {
+        let index = index_crate(&source_provider(), &lib_src_path(), Some(1)).unwrap();
+        let graph = module_graph(&index, "example", GraphFormat::Mermaid, GraphOptions { items: true, reexports: true });
+
+        assert_eq!(graph, [
+            "graph TD",
+            "    m0[\"example<br/>crate root · lib.rs\"]",
+            "    m1[\"renamed<br/>#[path] other/name.rs · pub(crate)\"]",
+            "    m2[\"shapes<br/>shapes/mod.rs · pub\"]",
+            "    m2_i0([\"struct Circle<br/>pub\"])",
+            "    m2_i1([\"fn area<br/>private\"])",
+            "    m3[\"util<br/>inline · private\"]",
+            "    u0{{\"missing<br/>unresolved\"}}",
+            "    m0 --> m1",
+            "    m0 --> m2",
+            "    m2 --- m2_i0",
+            "    m2 --- m2_i1",
+            "    m0 --> m3",
+            "    m0 --> u0",
+            "    m0 -. \"pub use shapes::Circle\" .-> m2_i0",
+            "",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn module_graph_as_dot() {
+        let index = index_crate(&source_provider(), &lib_src_path(), Some(1)).unwrap();
+        let graph = module_graph(&index, "example", GraphFormat::Dot, GraphOptions { items: false, reexports: true });
+
+        assert!(graph.starts_with("digraph \"example\" {\n    node [shape=box];\n    m0 [label=\"example\\ncrate root · lib.rs\"];\n"));
+        assert!(graph.contains("    m2 [label=\"shapes\\nshapes/mod.rs · pub\"];\n"));
+        assert!(graph.contains("    u0 [label=\"missing\\nunresolved\", style=dashed, color=red];\n"));
+        assert!(graph.contains("    m0 -> m2;\n"));
+        assert!(graph.contains("    m0 -> m2 [style=dashed, label=\"pub use shapes::Circle\"];\n"));
+        assert!(graph.ends_with("}\n"));
+    }
+}
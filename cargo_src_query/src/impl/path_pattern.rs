@@ -0,0 +1,250 @@
+//! # Path Patterns
+//! A [Query] names exactly one module or definition. A [PathPattern] names any number of them,
+//! and is matched against every module and definition of a crate:
+//!
+//! | Pattern                             | Matches                                                |
+//! |-------------------------------------|--------------------------------------------------------|
+//! | `crate::**::handlers::*`            | Everything directly within any module named `handlers` |
+//! | `crate::api::{v1,v2}::*Request`     | Names ending in `Request` within `api::v1` or `api::v2` |
+//! | `struct crate::**::*Config`         | Structs ending in `Config`, anywhere in the crate      |
+//! | `fn handler_?`                      | Fns such as `handler_a` within the crate root          |
+//!
+//! Each segment is matched against one segment of a path: `*` matches any number of characters,
+//! `?` matches a single character and `{a,b}` matches either alternative, e.g. `{Get,Put}*`.
+//! `**` matches any number of segments, including none. The `crate::` prefix is optional.
+//!
+//! A pattern can start with the kind of definition to match: `mod`, `struct`, `enum`, `union`,
+//! `trait`, `fn`, `type`, `const`, `static` or `macro`. Definitions are matched where they are
+//! defined, and re-exports aren't followed.
+//!
+//! [Query]: crate::Query
+
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+use syn::Item;
+use crate::errors::CargoQueryError;
+use crate::r#impl::attribute_search::named_item;
+use crate::r#impl::index::CrateIndex;
+
+const KINDS: [(&str, &str); 10] = [
+    ("mod", "mod"), ("struct", "struct"), ("enum", "enum"), ("union", "union"), ("trait", "trait"),
+    ("fn", "fn"), ("type", "type"), ("const", "const"), ("static", "static"), ("macro", "macro_rules!"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SegmentPattern {
+    /// `**`
+    AnySegments,
+    /// `name`, `*Request` or `{v1,v2}`
+    Alternatives(Vec<String>),
+}
+
+/// A pattern matching module and definition paths. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPattern {
+    /// Kind as reported by [PathMatch::kind], e.g. `macro_rules!` for `macro`
+    kind: Option<&'static str>,
+    segments: Vec<SegmentPattern>,
+}
+
+impl PathPattern {
+    /// Whether the path of a definition of `kind`, relative to the crate root, matches.
+    pub fn matches(&self, path: &[String], kind: &str) -> bool {
+        self.kind.is_none_or(|expected| expected == kind) && matches_segments(&self.segments, path)
+    }
+}
+
+impl FromStr for PathPattern {
+    type Err = CargoQueryError;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| CargoQueryError::invalid_path_pattern(pattern, reason);
+        let kind = pattern.trim().split_once(char::is_whitespace)
+            .and_then(|(name, path)| KINDS.iter().find(|(kind, _)| *kind == name).map(|(_, kind)| (*kind, path.trim())));
+        let (kind, path) = match kind {
+            Some((kind, path)) => (Some(kind), path),
+            None => (None, pattern.trim()),
+        };
+        let path = path.strip_prefix("crate::").unwrap_or(path);
+        if path.is_empty() || path == "crate" {
+            return Err(invalid("the pattern must name at least one segment"))
+        }
+
+        let segments = path.split("::").map(|segment| match segment.trim() {
+            "**" => Ok(SegmentPattern::AnySegments),
+            segment => {
+                let valid = |alternative: &String| !alternative.is_empty()
+                    && alternative.chars().all(|c| c == '_' || c == '*' || c == '?' || c.is_alphanumeric());
+                match expand_braces(segment) {
+                    Some(alternatives) if alternatives.iter().all(valid) => Ok(SegmentPattern::Alternatives(alternatives)),
+                    _ => Err(invalid(&format!("`{segment}` is not a valid segment"))),
+                }
+            }
+        }).collect::<Result<Vec<_>, _>>()?;
+        Ok(PathPattern { kind, segments })
+    }
+}
+
+/// Renders the pattern in its canonical form, e.g. `struct crate::**::{a,b}::*Config`.
+impl Display for PathPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(kind) = self.kind {
+            let name = KINDS.iter().find(|(_, k)| *k == kind).map_or(kind, |(name, _)| name);
+            write!(f, "{name} ")?;
+        }
+        let segments = self.segments.iter().map(|segment| match segment {
+            SegmentPattern::AnySegments => "**".to_string(),
+            SegmentPattern::Alternatives(alternatives) if alternatives.len() == 1 => alternatives[0].clone(),
+            SegmentPattern::Alternatives(alternatives) => format!("{{{}}}", alternatives.join(",")),
+        });
+        write!(f, "{}", std::iter::once("crate".to_string()).chain(segments).collect::<Vec<_>>().join("::"))
+    }
+}
+
+/// A module or definition matching a [PathPattern].
+#[derive(Debug, Clone)]
+pub struct PathMatch {
+    /// Path segments relative to the crate root
+    pub path: Vec<String>,
+    /// The kind of the definition, e.g. `fn`, `struct` or `mod`
+    pub kind: &'static str,
+    /// The definition, or `None` for modules
+    pub item: Option<Item>,
+    /// File containing the definition, or implementing the module
+    pub file_path: PathBuf,
+}
+
+/// Renders the full path, e.g. `crate::api::v1::CreateRequest`.
+impl Display for PathMatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", std::iter::once("crate").chain(self.path.iter().map(String::as_str)).collect::<Vec<_>>().join("::"))
+    }
+}
+
+/// Finds every module and definition of an indexed crate matching `pattern`, ordered by path.
+pub fn find_paths(index: &CrateIndex, pattern: &PathPattern) -> Result<Vec<PathMatch>, CargoQueryError> {
+    let mut matches = vec![];
+    for module in &index.modules {
+        if !module.path.is_empty() && pattern.matches(&module.path, "mod") {
+            matches.push(PathMatch { path: module.path.clone(), kind: "mod", item: None, file_path: module.file_path.clone() });
+        }
+        for item in index.items(module)? {
+            // Modules are matched through the index, so that `mod name;` declarations aren't
+            // matched in addition to the module
+            if matches!(item, Item::Mod(_)) {
+                continue
+            }
+            let (name, kind, _) = match named_item(&item) {
+                Some(named) => named,
+                None => continue,
+            };
+            let path: Vec<String> = module.path.iter().cloned().chain(std::iter::once(name)).collect();
+            if pattern.matches(&path, kind) {
+                matches.push(PathMatch { path, kind, item: Some(item), file_path: module.file_path.clone() });
+            }
+        }
+    }
+    matches.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.kind.cmp(b.kind)));
+    Ok(matches)
+}
+
+/// `{Get,Put}*Request` => `["Get*Request", "Put*Request"]`. `None` when braces are unbalanced
+/// or nested.
+fn expand_braces(segment: &str) -> Option<Vec<String>> {
+    let open = match segment.find('{') {
+        Some(open) => open,
+        None => return (!segment.contains('}')).then(|| vec![segment.to_string()]),
+    };
+    let close = open + segment[open..].find('}')?;
+    let (prefix, alternatives, suffix) = (&segment[..open], &segment[open + 1..close], &segment[close + 1..]);
+    if alternatives.contains('{') {
+        return None
+    }
+    let suffixes = expand_braces(suffix)?;
+    Some(alternatives.split(',')
+        .flat_map(|alternative| suffixes.iter().map(move |suffix| format!("{prefix}{}{suffix}", alternative.trim())))
+        .collect())
+}
+
+fn matches_segments(pattern: &[SegmentPattern], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((SegmentPattern::AnySegments, rest)) => {
+            matches_segments(rest, path) || (!path.is_empty() && matches_segments(pattern, &path[1..]))
+        },
+        Some((SegmentPattern::Alternatives(alternatives), rest)) => match path.split_first() {
+            Some((segment, path)) => {
+                let segment: Vec<char> = segment.chars().collect();
+                alternatives.iter().any(|alternative| matches_glob(&alternative.chars().collect::<Vec<_>>(), &segment))
+                    && matches_segments(rest, path)
+            },
+            None => false,
+        },
+    }
+}
+
+/// Matches a single segment, where `*` matches any number of characters and `?` matches one.
+fn matches_glob(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some(('*', pattern_rest)), _) => matches_glob(pattern_rest, name) || (!name.is_empty() && matches_glob(pattern, &name[1..])),
+        (Some(('?', pattern_rest)), Some((_, name_rest))) => matches_glob(pattern_rest, name_rest),
+        (Some((expected, pattern_rest)), Some((actual, name_rest))) if expected == actual => matches_glob(pattern_rest, name_rest),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use crate::errors::ErrorKind;
+    use crate::r#impl::index::index_crate;
+    use crate::r#impl::path_pattern::{find_paths, PathPattern};
+    use crate::source::InMemory;
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn find(pattern: &str) -> Vec<String> {
+        let source_provider: InMemory = [
+            (lib_src_path(), "pub mod api;\nmod server;\npub struct AppConfig {}\nfn handler_a() {}\nfn handler_bc() {}\n"),
+            (src_path().join("api/mod.rs"), "pub mod v1;\npub mod v2 { pub struct DeleteRequest; }\npub mod v3 { pub struct StaleRequest; }\n"),
+            (src_path().join("api/v1.rs"), "pub struct CreateRequest {}\npub struct Response {}\npub fn CreateRequest() {}\n"),
+            (src_path().join("server.rs"), "pub mod handlers { pub fn index() {} pub struct Index; }\npub struct ServerConfig {}\n"),
+        ].into_iter().collect();
+
+        let index = index_crate(&source_provider, &lib_src_path(), Some(1)).unwrap();
+        find_paths(&index, &pattern.parse().unwrap()).unwrap().iter()
+            .map(|found| format!("{} {found}", found.kind))
+            .collect()
+    }
+
+    #[test]
+    fn match_recursive_wildcards_and_alternatives() {
+        assert_eq!(find("crate::**::handlers::*"), vec!["struct crate::server::handlers::Index", "fn crate::server::handlers::index"]);
+        assert_eq!(find("crate::api::{v1,v2}::*Request"), vec![
+            "fn crate::api::v1::CreateRequest", "struct crate::api::v1::CreateRequest", "struct crate::api::v2::DeleteRequest",
+        ]);
+        assert_eq!(find("struct crate::**::*Config"), vec!["struct crate::AppConfig", "struct crate::server::ServerConfig"]);
+        assert_eq!(find("handler_?"), vec!["fn crate::handler_a"]);
+        assert_eq!(find("mod **"), vec![
+            "mod crate::api", "mod crate::api::v1", "mod crate::api::v2", "mod crate::api::v3",
+            "mod crate::server", "mod crate::server::handlers",
+        ]);
+    }
+
+    #[test]
+    fn parse_and_display_patterns() {
+        let pattern: PathPattern = "macro  api::{ v1, v2 }::*".parse().unwrap();
+        assert_eq!(pattern.to_string(), "macro crate::api::{v1,v2}::*");
+
+        let invalid = |pattern: &str| pattern.parse::<PathPattern>().unwrap_err().kind();
+        assert_eq!(invalid("class crate::*"), ErrorKind::ParseFailure);
+        assert_eq!(invalid("crate::"), ErrorKind::ParseFailure);
+        assert_eq!(invalid("crate::a-b"), ErrorKind::ParseFailure);
+        assert_eq!(invalid("crate::{a,{b}}"), ErrorKind::ParseFailure);
+        assert_eq!("{Get,Put}*Request".parse::<PathPattern>().unwrap().to_string(), "crate::{Get*Request,Put*Request}");
+    }
+}
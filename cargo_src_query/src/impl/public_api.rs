@@ -0,0 +1,520 @@
+//! # Public API
+//! Lists every publicly reachable definition of a crate with its canonical path (see
+//! [public_path](crate::r#impl::public_path)) and a normalized signature, which keeps only what
+//! other crates can observe: attributes other than `#[derive]` and `#[non_exhaustive]`, function
+//! bodies, private fields and values of constants are dropped.
+//!
+//! Two listings, e.g. of two versions of a dependency, can be compared with [diff_api]. Each
+//! change is classified by the version bump it requires under
+//! [Cargo's SemVer rules](https://doc.rust-lang.org/cargo/reference/semver.html):
+//!
+//! ```text
+//! major: removed `shapes::Square`: pub struct Square { pub side : f64 }
+//! major: changed `area`
+//!   - pub fn area (shape : & Shape) -> f64
+//!   + pub fn area (shape : & Shape , precision : u8) -> f64
+//! minor: added `shapes::Circle`: pub struct Circle { pub radius : f64 , .. }
+//! required version bump: major
+//! ```
+//!
+//! Generic parameters are compared one by one, so adding a parameter with a default, e.g.
+//! `struct Map<K, V, S = RandomState>`, is a minor change. Only the definitions themselves are
+//! compared, so changes of inherent and trait implementations are not detected.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use quote::{quote, ToTokens};
+use syn::{Attribute, Fields, GenericParam, Generics, Item, Meta, NestedMeta, TraitItem};
+use crate::r#impl::index::CrateIndex;
+use crate::r#impl::public_path::all_public_paths;
+
+/// A publicly reachable definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiItem {
+    /// Canonical path, relative to the crate root
+    pub path: Vec<String>,
+    /// Every other public path of the definition
+    pub aliases: Vec<Vec<String>>,
+    pub kind: &'static str,
+    /// e.g. `pub struct Circle { pub radius : f64 , .. }`, where `..` stands for private fields
+    pub signature: String,
+    shape: Shape,
+}
+
+/// The parts of a signature which are compared separately by [diff_api].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Shape {
+    /// Everything but the members, e.g. `struct Circle < T > where T : Copy`
+    header: String,
+    /// `header` without the generic parameters, e.g. `struct Circle where T : Copy`
+    bare_header: String,
+    /// Generic parameters without and with their default, e.g. `T` and `u32` for `T = u32`
+    generics: Vec<(String, Option<String>)>,
+    /// Fields, variants, trait items and derives, mapped to whether adding them is a minor change
+    members: BTreeMap<String, bool>,
+    /// Has private fields or is `#[non_exhaustive]`
+    extensible: bool,
+}
+
+impl ApiItem {
+    pub fn paths(&self) -> impl Iterator<Item=&Vec<String>> {
+        std::iter::once(&self.path).chain(self.aliases.iter())
+    }
+}
+
+/// Every publicly reachable definition of the crate, sorted by canonical path.
+pub fn public_api(index: &CrateIndex) -> Vec<ApiItem> {
+    let mut items_by_module = BTreeMap::new();
+    let mut api: Vec<ApiItem> = all_public_paths(index).into_iter().filter_map(|((module, name), mut paths)| {
+        let items = items_by_module.entry(module.clone()).or_insert_with(|| index.module(&module)
+            .and_then(|m| index.items(m).ok())
+            .unwrap_or_default());
+        let (kind, shape) = items.iter().find_map(|item| normalize(item, &name))?;
+        let path = paths.remove(0);
+        Some(ApiItem { path, aliases: paths, kind, signature: shape.render(kind), shape })
+    }).collect();
+    api.sort_by(|a, b| a.path.cmp(&b.path));
+    api
+}
+
+impl Shape {
+    fn render(&self, kind: &str) -> String {
+        let derives: Vec<&str> = self.members.keys().filter_map(|m| m.strip_prefix("derive ")).collect();
+        let mut rendered = match derives.is_empty() {
+            true => String::new(),
+            false => format!("#[derive({})] ", derives.join(", ")),
+        };
+        rendered.push_str("pub ");
+        rendered.push_str(&self.header);
+
+        let members: Vec<&str> = self.members.keys().filter(|m| !m.starts_with("derive ")).map(String::as_str).collect();
+        if matches!(kind, "struct" | "enum" | "union" | "trait") {
+            let mut body = members;
+            if self.extensible { body.push("..") }
+            match body.is_empty() {
+                true => rendered.push_str(" { }"),
+                false => rendered.push_str(&format!(" {{ {} }}", body.join(" , "))),
+            }
+        }
+        rendered
+    }
+}
+
+fn tokens(tokens: impl ToTokens) -> String {
+    tokens.into_token_stream().to_string()
+}
+
+/// Returns the derived traits of an item and whether it is `#[non_exhaustive]`.
+fn observable_attributes(attrs: &[Attribute]) -> (Vec<String>, bool) {
+    let mut derives = vec![];
+    let mut non_exhaustive = false;
+    for attr in attrs {
+        match attr.parse_meta() {
+            Ok(Meta::Path(path)) if path.is_ident("non_exhaustive") => non_exhaustive = true,
+            Ok(Meta::List(list)) if list.path.is_ident("derive") => derives.extend(list.nested.iter().filter_map(|nested| match nested {
+                NestedMeta::Meta(Meta::Path(path)) => path.segments.last().map(|s| format!("derive {}", s.ident)),
+                _ => None,
+            })),
+            _ => {}
+        }
+    }
+    (derives, non_exhaustive)
+}
+
+/// Public fields as members, and whether there are private fields.
+fn field_members(fields: &Fields) -> (Vec<String>, bool) {
+    let mut members = vec![];
+    let mut private = false;
+    for (i, field) in fields.iter().enumerate() {
+        if !matches!(field.vis, syn::Visibility::Public(_)) {
+            private = true;
+            continue
+        }
+        let ty = &field.ty;
+        match &field.ident {
+            Some(ident) => members.push(tokens(quote!(pub #ident: #ty))),
+            None => members.push(format!("pub {i} : {}", tokens(ty))),
+        }
+    }
+    (members, private)
+}
+
+/// The kind and [Shape] of `item`, if it is the definition `name`.
+fn normalize(item: &Item, name: &str) -> Option<(&'static str, Shape)> {
+    let shape = |header: String, members: Vec<(String, bool)>, extensible: bool| Shape {
+        bare_header: header.clone(),
+        header,
+        generics: vec![],
+        members: members.into_iter().collect(),
+        extensible,
+    };
+    let generic_shape = |header: String, bare_header: String, generics: &Generics, members: Vec<(String, bool)>, extensible: bool| Shape {
+        bare_header,
+        generics: generic_parameters(generics),
+        ..shape(header, members, extensible)
+    };
+    let with_derives = |attrs: &[Attribute], mut members: Vec<(String, bool)>| {
+        members.extend(observable_attributes(attrs).0.into_iter().map(|derive| (derive, true)));
+        members
+    };
+
+    match item {
+        Item::Struct(i) if i.ident == name => {
+            let (ident, generics, where_clause) = (&i.ident, &i.generics, &i.generics.where_clause);
+            let (fields, private) = field_members(&i.fields);
+            let extensible = private || observable_attributes(&i.attrs).1;
+            let members = with_derives(&i.attrs, fields.into_iter().map(|f| (f, extensible)).collect());
+            Some(("struct", generic_shape(tokens(quote!(struct #ident #generics #where_clause)),
+                                          tokens(quote!(struct #ident #where_clause)), generics, members, extensible)))
+        },
+        Item::Union(i) if i.ident == name => {
+            let (ident, generics, where_clause) = (&i.ident, &i.generics, &i.generics.where_clause);
+            let (fields, private) = field_members(&Fields::Named(i.fields.clone()));
+            let members = with_derives(&i.attrs, fields.into_iter().map(|f| (f, private)).collect());
+            Some(("union", generic_shape(tokens(quote!(union #ident #generics #where_clause)),
+                                         tokens(quote!(union #ident #where_clause)), generics, members, private)))
+        },
+        Item::Enum(i) if i.ident == name => {
+            let (ident, generics, where_clause) = (&i.ident, &i.generics, &i.generics.where_clause);
+            let non_exhaustive = observable_attributes(&i.attrs).1;
+            let variants = i.variants.iter().map(|variant| {
+                let mut variant = variant.clone();
+                variant.attrs.clear();
+                variant.fields.iter_mut().for_each(|field| field.attrs.clear());
+                (tokens(variant), non_exhaustive)
+            }).collect();
+            let members = with_derives(&i.attrs, variants);
+            Some(("enum", generic_shape(tokens(quote!(enum #ident #generics #where_clause)),
+                                        tokens(quote!(enum #ident #where_clause)), generics, members, non_exhaustive)))
+        },
+        Item::Fn(i) if i.sig.ident == name => {
+            let mut sig = i.sig.clone();
+            sig.inputs.iter_mut().for_each(|input| match input {
+                syn::FnArg::Receiver(receiver) => receiver.attrs.clear(),
+                syn::FnArg::Typed(typed) => typed.attrs.clear(),
+            });
+            Some(("fn", shape(tokens(sig), vec![], false)))
+        },
+        Item::Trait(i) if i.ident == name => {
+            let (unsafety, auto_token, ident, generics) = (&i.unsafety, &i.auto_token, &i.ident, &i.generics);
+            let (colon_token, supertraits, where_clause) = (&i.colon_token, &i.supertraits, &i.generics.where_clause);
+            let members = i.items.iter().filter_map(|item| match item {
+                TraitItem::Method(m) => Some((tokens(&m.sig), m.default.is_some())),
+                TraitItem::Const(c) => {
+                    let (ident, ty) = (&c.ident, &c.ty);
+                    Some((tokens(quote!(const #ident: #ty)), c.default.is_some()))
+                },
+                TraitItem::Type(t) => {
+                    let (ident, generics, colon_token, bounds) = (&t.ident, &t.generics, &t.colon_token, &t.bounds);
+                    Some((tokens(quote!(type #ident #generics #colon_token #bounds)), t.default.is_some()))
+                },
+                _ => None,
+            }).map(|(member, provided)| match provided {
+                true => (format!("{member} {{ .. }}"), true),
+                false => (member, false),
+            }).collect();
+            let header = tokens(quote!(#unsafety #auto_token trait #ident #generics #colon_token #supertraits #where_clause));
+            let bare_header = tokens(quote!(#unsafety #auto_token trait #ident #colon_token #supertraits #where_clause));
+            Some(("trait", generic_shape(header, bare_header, generics, members, false)))
+        },
+        Item::TraitAlias(i) if i.ident == name => {
+            let (ident, generics, bounds, where_clause) = (&i.ident, &i.generics, &i.bounds, &i.generics.where_clause);
+            Some(("trait", shape(tokens(quote!(trait #ident #generics = #bounds #where_clause)), vec![], false)))
+        },
+        Item::Type(i) if i.ident == name => {
+            let (ident, generics, ty, where_clause) = (&i.ident, &i.generics, &i.ty, &i.generics.where_clause);
+            Some(("type", generic_shape(tokens(quote!(type #ident #generics #where_clause = #ty)),
+                                        tokens(quote!(type #ident #where_clause = #ty)), generics, vec![], false)))
+        },
+        Item::Const(i) if i.ident == name => {
+            let (ident, ty) = (&i.ident, &i.ty);
+            Some(("const", shape(tokens(quote!(const #ident: #ty)), vec![], false)))
+        },
+        Item::Static(i) if i.ident == name => {
+            let (mutability, ident, ty) = (&i.mutability, &i.ident, &i.ty);
+            Some(("static", shape(tokens(quote!(static #mutability #ident: #ty)), vec![], false)))
+        },
+        _ => None,
+    }
+}
+
+/// Each generic parameter without attributes and default, and its default.
+fn generic_parameters(generics: &Generics) -> Vec<(String, Option<String>)> {
+    generics.params.iter().map(|param| {
+        let mut param = param.clone();
+        let default = match &mut param {
+            GenericParam::Type(t) => {
+                t.attrs.clear();
+                t.eq_token = None;
+                t.default.take().map(tokens)
+            },
+            GenericParam::Const(c) => {
+                c.attrs.clear();
+                c.eq_token = None;
+                c.default.take().map(tokens)
+            },
+            GenericParam::Lifetime(l) => {
+                l.attrs.clear();
+                None
+            },
+        };
+        (tokens(param), default)
+    }).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// The version bump a change requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Patch,
+    Minor,
+    Major,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiChange {
+    /// Public path of the changed definition, relative to the crate root
+    pub path: Vec<String>,
+    pub kind: ChangeKind,
+    pub severity: Severity,
+    /// Signature in the old version
+    pub before: Option<String>,
+    /// Signature in the new version
+    pub after: Option<String>,
+}
+
+/// Changes between two listings of a crate's public API, see [diff_api].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApiDiff {
+    /// Sorted from most to least severe, and then by path
+    pub changes: Vec<ApiChange>,
+}
+
+impl ApiDiff {
+    /// The version bump required by all changes. [Severity::Patch] when nothing changed.
+    pub fn severity(&self) -> Severity {
+        self.changes.iter().map(|change| change.severity).max().unwrap_or(Severity::Patch)
+    }
+}
+
+/// Compares two listings of a crate's public API, as returned by [public_api]. A definition
+/// which is reachable through several paths is compared once, at its canonical path. A path
+/// which is added or removed while the definition stays reachable is reported on its own.
+pub fn diff_api(old: &[ApiItem], new: &[ApiItem]) -> ApiDiff {
+    let (old_paths, new_paths) = (by_path(old), by_path(new));
+    let all_paths: BTreeSet<&Vec<String>> = old_paths.keys().chain(new_paths.keys()).collect();
+
+    let mut changes = vec![];
+    for path in all_paths {
+        let change = match (old_paths.get(path), new_paths.get(path)) {
+            // A definition which disappears entirely is reported once, at its canonical path
+            (Some(old), None) if old.paths().any(|p| new_paths.contains_key(p)) || &old.path == path =>
+                Some((ChangeKind::Removed, Severity::Major)),
+            (None, Some(new)) if new.paths().any(|p| old_paths.contains_key(p)) || &new.path == path =>
+                Some((ChangeKind::Added, Severity::Minor)),
+            (Some(old), Some(new)) if &new.path == path && old.signature != new.signature =>
+                Some((ChangeKind::Changed, change_severity(old, new))),
+            _ => None,
+        };
+        if let Some((kind, severity)) = change {
+            changes.push(ApiChange {
+                path: path.clone(),
+                kind,
+                severity,
+                before: old_paths.get(path).map(|item| item.signature.clone()),
+                after: new_paths.get(path).map(|item| item.signature.clone()),
+            });
+        }
+    }
+    changes.sort_by(|a, b| b.severity.cmp(&a.severity).then_with(|| a.path.cmp(&b.path)));
+    ApiDiff { changes }
+}
+
+fn by_path(api: &[ApiItem]) -> BTreeMap<Vec<String>, &ApiItem> {
+    api.iter().flat_map(|item| item.paths().map(move |path| (path.clone(), item))).collect()
+}
+
+fn change_severity(old: &ApiItem, new: &ApiItem) -> Severity {
+    let (old_shape, new_shape) = (&old.shape, &new.shape);
+    if old.kind != new.kind || old_shape.bare_header != new_shape.bare_header || (!old_shape.extensible && new_shape.extensible) {
+        return Severity::Major
+    }
+    // Parameters can only be added after the existing ones, and only with a default
+    match new_shape.generics.strip_prefix(old_shape.generics.as_slice()) {
+        Some(added) if added.iter().all(|(_, default)| default.is_some()) => {},
+        _ => return Severity::Major,
+    }
+    if old_shape.members.keys().any(|member| !new_shape.members.contains_key(member)) {
+        return Severity::Major
+    }
+    let added_major = new_shape.members.iter()
+        .any(|(member, minor)| !minor && !old_shape.members.contains_key(member));
+    match added_major {
+        true => Severity::Major,
+        // Added members which can't break users, or the last private field was removed
+        false => Severity::Minor,
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Patch => write!(f, "patch"),
+            Severity::Minor => write!(f, "minor"),
+            Severity::Major => write!(f, "major"),
+        }
+    }
+}
+
+impl Display for ApiDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            let path = change.path.join("::");
+            match (change.kind, &change.before, &change.after) {
+                (ChangeKind::Added, _, Some(after)) => writeln!(f, "{}: added `{path}`: {after}", change.severity)?,
+                (ChangeKind::Removed, Some(before), _) => writeln!(f, "{}: removed `{path}`: {before}", change.severity)?,
+                (_, before, after) => {
+                    writeln!(f, "{}: changed `{path}`", change.severity)?;
+                    if let Some(before) = before { writeln!(f, "  - {before}")? }
+                    if let Some(after) = after { writeln!(f, "  + {after}")? }
+                },
+            }
+        }
+        write!(f, "required version bump: {}", self.severity())
+    }
+}
+
+/// Returns the unpacked sources of `name` at `version` in the local registry cache of
+/// `cargo_home`, e.g. `~/.cargo/registry/src/index.crates.io-6f17d22bba15001f/serde-1.0.188`.
+pub fn registry_crate_path(cargo_home: &Path, name: &str, version: &str) -> Option<PathBuf> {
+    let directory_name = format!("{name}-{version}");
+    let mut registries: Vec<PathBuf> = std::fs::read_dir(cargo_home.join("registry").join("src")).ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    registries.sort();
+    registries.into_iter().map(|registry| registry.join(&directory_name)).find(|path| path.is_dir())
+}
+
+/// `$CARGO_HOME`, or `~/.cargo` when it is unset.
+pub fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME").map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use crate::r#impl::index::index_crate;
+    use crate::r#impl::public_api::{ApiItem, ChangeKind, diff_api, public_api, registry_crate_path, Severity};
+    use crate::source::InMemory;
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn api(source: &str) -> Vec<ApiItem> {
+        let source_provider: InMemory = [(lib_src_path(), source)].into_iter().collect();
+        public_api(&index_crate(&source_provider, &lib_src_path(), Some(1)).unwrap())
+    }
+
+    #[test]
+    fn public_api_is_normalized() {
+        let api = api("
+            /// Documentation is not part of the API
+            #[derive(Clone, Debug)]
+            pub struct Circle { pub radius: f64, area: f64 }
+            pub fn area(circle: &Circle) -> f64 { circle.radius * circle.radius * 3.14 }
+            pub trait Shape { fn area(&self) -> f64; fn name(&self) -> String { String::new() } }
+            pub const PI: f64 = 3.14;
+            mod private { pub struct Hidden; }
+            pub mod shapes { pub use crate::Circle; }
+        ");
+
+        let listing: Vec<String> = api.iter().map(|item| format!("{}: {}", item.path.join("::"), item.signature)).collect();
+        assert_eq!(listing, vec![
+            "Circle: #[derive(Clone, Debug)] pub struct Circle { pub radius : f64 , .. }",
+            "PI: pub const PI : f64",
+            "Shape: pub trait Shape { fn area (& self) -> f64 , fn name (& self) -> String { .. } }",
+            "area: pub fn area (circle : & Circle) -> f64",
+        ]);
+        assert_eq!(api[0].aliases, vec![vec!["shapes".to_string(), "Circle".to_string()]]);
+    }
+
+    #[test]
+    fn diff_classifies_changes() {
+        let old = api("
+            pub struct Circle { pub radius: f64, area: f64 }
+            pub struct Square { pub side: f64 }
+            pub enum Color { Red }
+            pub trait Shape { fn area(&self) -> f64; }
+            pub fn area() {}
+        ");
+        let new = api("
+            pub struct Circle { pub radius: f64, pub center: (f64, f64), area: f64 }
+            pub struct Square { pub side: f64, pub rotation: f64 }
+            #[non_exhaustive] pub enum Color { Red, Green }
+            pub trait Shape { fn area(&self) -> f64; fn name(&self) -> String { String::new() } }
+            pub fn perimeter() {}
+        ");
+
+        let diff = diff_api(&old, &new);
+        let changes: Vec<(String, ChangeKind, Severity)> = diff.changes.iter()
+            .map(|change| (change.path.join("::"), change.kind, change.severity))
+            .collect();
+        assert_eq!(changes, vec![
+            ("Color".to_string(), ChangeKind::Changed, Severity::Major),
+            ("Square".to_string(), ChangeKind::Changed, Severity::Major),
+            ("area".to_string(), ChangeKind::Removed, Severity::Major),
+            ("Circle".to_string(), ChangeKind::Changed, Severity::Minor),
+            ("Shape".to_string(), ChangeKind::Changed, Severity::Minor),
+            ("perimeter".to_string(), ChangeKind::Added, Severity::Minor),
+        ]);
+        assert_eq!(diff.severity(), Severity::Major);
+        assert!(diff.to_string().ends_with("required version bump: major"));
+        assert_eq!(diff_api(&old, &old).severity(), Severity::Patch);
+    }
+
+    #[test]
+    fn diff_compares_generic_parameters() {
+        let old = api("
+            pub struct Map<K, V> { pub entries: Vec<(K, V)> }
+            pub struct Set<T> { pub items: Vec<T> }
+            pub struct Pair<A, B = u32> { pub first: A, pub second: B }
+        ");
+        let new = api("
+            pub struct Map<K, V, S = std::collections::hash_map::RandomState> { pub entries: Vec<(K, V)> }
+            pub struct Set<T, S> { pub items: Vec<T> }
+            pub struct Pair<A, B = u64> { pub first: A, pub second: B }
+        ");
+
+        let changes: Vec<(String, Severity)> = diff_api(&old, &new).changes.iter()
+            .map(|change| (change.path.join("::"), change.severity))
+            .collect();
+        assert_eq!(changes, vec![
+            ("Pair".to_string(), Severity::Major),
+            ("Set".to_string(), Severity::Major),
+            ("Map".to_string(), Severity::Minor),
+        ]);
+    }
+
+    #[test]
+    fn registry_crates_are_found_by_version() {
+        let cargo_home = std::env::temp_dir().join(format!("cargo_src_query_cargo_home_{}", std::process::id()));
+        let crate_path = cargo_home.join("registry/src/index.crates.io-0000000000000000/example-1.2.3");
+        std::fs::create_dir_all(&crate_path).unwrap();
+
+        let found = registry_crate_path(&cargo_home, "example", "1.2.3");
+        let missing = registry_crate_path(&cargo_home, "example", "1.2.4");
+        std::fs::remove_dir_all(&cargo_home).unwrap();
+
+        assert_eq!(found, Some(crate_path));
+        assert_eq!(missing, None);
+    }
+}
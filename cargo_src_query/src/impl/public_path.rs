@@ -0,0 +1,280 @@
+//! # Public Paths
+//! A definition can be reachable from outside of its crate through several paths, e.g. through the
+//! modules it is defined in and through `pub use` re-exports:
+//!
+//! ```text
+//! // lib.rs
+//! pub mod shapes;
+//! pub use shapes::circle::Circle;
+//! pub mod prelude { pub use crate::shapes::*; }
+//!
+//! // shapes/mod.rs
+//! pub mod circle;             // pub struct Circle;
+//!
+//! crate::shapes::circle::Circle  =>  crate::Circle                 (canonical)
+//!                                    crate::prelude::circle::Circle
+//!                                    crate::shapes::circle::Circle
+//! ```
+//!
+//! Like rustdoc, the canonical path is the shortest one. Paths of equal length are ordered
+//! alphabetically. Only `pub` modules, items and re-exports are followed, so `pub(crate)` items
+//! are not publicly reachable. Re-exports of other crates are not followed.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::cell::RefCell;
+use syn::{Item, Visibility};
+use crate::r#impl::index::{CrateIndex, ItemVisibility};
+use crate::r#impl::recurse_export::flatten_use_tree;
+
+type ModulePathSegments = Vec<String>;
+
+/// The module a definition is in, and its name.
+pub type DefinitionKey = (ModulePathSegments, String);
+
+/// Paths longer than this are not considered, which bounds the paths through cyclic glob
+/// re-exports such as `pub use super::*;`.
+const MAX_PATH_LENGTH: usize = 16;
+
+/// Every public path to the definition `name` within `module` (both relative to the crate
+/// root), ranked from canonical to longest. `module` and `name` may refer to a re-export, in
+/// which case the re-exported definition is used. Empty when the definition is not publicly
+/// reachable, and `None` when it does not exist.
+pub fn public_paths(index: &CrateIndex, module: &[String], name: &str) -> Option<Vec<ModulePathSegments>> {
+    let resolver = Resolver { index, items: Default::default() };
+    let target = resolver.resolve_item(module, name, 0)?;
+    Some(reachable_definitions(&resolver).remove(&target).unwrap_or_default())
+}
+
+/// Every publicly reachable definition of the crate, with its public paths ranked from
+/// canonical to longest.
+pub fn all_public_paths(index: &CrateIndex) -> BTreeMap<DefinitionKey, Vec<ModulePathSegments>> {
+    reachable_definitions(&Resolver { index, items: Default::default() })
+}
+
+/// Walks the crate breadth first from its root, so that each module is first reached through
+/// its shortest path. Every module is expanded once, at that path. Other paths a module is
+/// reached through are collected, and extended to its `pub mod` children afterwards, without
+/// expanding the module again. This keeps cyclic glob re-exports such as `pub use super::*;`
+/// from multiplying the paths which are walked.
+fn reachable_definitions(resolver: &Resolver) -> BTreeMap<DefinitionKey, Vec<ModulePathSegments>> {
+    let index = resolver.index;
+    let mut reached: BTreeMap<ModulePathSegments, BTreeSet<ModulePathSegments>> = BTreeMap::new();
+    // The module containing `pub use path::to::Item as Binding`, `Binding` and the definition
+    let mut item_reexports: Vec<(ModulePathSegments, String, DefinitionKey)> = vec![];
+    let mut unvisited = VecDeque::from([(vec![], vec![])]);
+    while let Some((module, public_path)) = unvisited.pop_front() {
+        if public_path.len() >= MAX_PATH_LENGTH {
+            continue
+        }
+        let expanded = reached.contains_key(&module);
+        reached.entry(module.clone()).or_default().insert(public_path.clone());
+        if expanded {
+            continue
+        }
+        let with_segment = |segment: &str| public_path.iter().cloned().chain([segment.to_string()]).collect::<Vec<_>>();
+
+        // `pub mod name`
+        for child in index.modules.iter().filter(|m| m.path.len() == module.len() + 1 && m.path.starts_with(&module)) {
+            if child.visibility == ItemVisibility::Public {
+                unvisited.push_back((child.path.clone(), with_segment(child.path.last().unwrap())));
+            }
+        }
+
+        // `pub use path::to::name` and `pub use path::to::*`
+        let reexports = resolver.with_items(&module, |items| items.iter().filter_map(|item| match item {
+            Item::Use(u) if matches!(u.vis, Visibility::Public(_)) => Some(flatten_use_tree(&u.tree)),
+            _ => None,
+        }).flatten().collect::<Vec<_>>());
+        for reexport in reexports {
+            let (target_module, rest) = match index.resolve_module_prefix(&module, &reexport.segments) {
+                Some(resolved) => resolved,
+                None => continue,
+            };
+            let binding = reexport.binding().unwrap_or_default().to_string();
+            match (reexport.glob, rest.as_slice()) {
+                // Globs don't lengthen the path, so the target is walked before longer paths
+                (true, []) => unvisited.push_front((target_module, public_path.clone())),
+                (false, []) => unvisited.push_back((target_module, with_segment(&binding))),
+                (false, [item]) => if let Some(target) = resolver.resolve_item(&target_module, item, 0) {
+                    item_reexports.push((module.clone(), binding, target));
+                },
+                _ => {}
+            }
+        }
+    }
+
+    // Parents come before their children
+    let mut modules: Vec<&ModulePathSegments> = index.modules.iter().map(|m| &m.path).collect();
+    modules.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    modules.dedup();
+    let mut module_paths: BTreeMap<ModulePathSegments, BTreeSet<ModulePathSegments>> = BTreeMap::new();
+    for module in modules {
+        let mut paths = reached.get(module).cloned().unwrap_or_default();
+        let public = index.modules.iter().any(|m| &m.path == module && m.visibility == ItemVisibility::Public);
+        if let (true, Some((name, parent))) = (public, module.split_last()) {
+            paths.extend(module_paths.get(parent).into_iter().flatten()
+                .map(|path| path.iter().chain([name]).cloned().collect::<Vec<_>>())
+                .filter(|path| path.len() < MAX_PATH_LENGTH));
+        }
+        if !paths.is_empty() {
+            module_paths.insert(module.clone(), paths);
+        }
+    }
+
+    let mut definitions_by_key: BTreeMap<DefinitionKey, BTreeSet<ModulePathSegments>> = BTreeMap::new();
+    let with_name = |paths: &BTreeSet<ModulePathSegments>, name: &str| paths.iter()
+        .map(|path| path.iter().cloned().chain([name.to_string()]).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    for (module, paths) in &module_paths {
+        for (definition, visibility) in resolver.with_items(module, definitions) {
+            if visibility == ItemVisibility::Public {
+                let reachable = with_name(paths, &definition);
+                definitions_by_key.entry((module.clone(), definition)).or_default().extend(reachable);
+            }
+        }
+    }
+    for (module, binding, target) in item_reexports {
+        if let Some(paths) = module_paths.get(&module) {
+            definitions_by_key.entry(target).or_default().extend(with_name(paths, &binding));
+        }
+    }
+
+    definitions_by_key.into_iter().map(|(key, paths)| {
+        let mut paths: Vec<ModulePathSegments> = paths.into_iter().collect();
+        paths.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        (key, paths)
+    }).collect()
+}
+
+/// Names and visibilities of the definitions within `items`.
+pub(crate) fn definitions(items: &[Item]) -> Vec<(String, ItemVisibility)> {
+    items.iter().filter_map(|item| {
+        let (ident, vis) = match item {
+            Item::Const(i) => (&i.ident, &i.vis),
+            Item::Enum(i) => (&i.ident, &i.vis),
+            Item::Fn(i) => (&i.sig.ident, &i.vis),
+            Item::Static(i) => (&i.ident, &i.vis),
+            Item::Struct(i) => (&i.ident, &i.vis),
+            Item::Trait(i) => (&i.ident, &i.vis),
+            Item::TraitAlias(i) => (&i.ident, &i.vis),
+            Item::Type(i) => (&i.ident, &i.vis),
+            Item::Union(i) => (&i.ident, &i.vis),
+            _ => return None,
+        };
+        Some((ident.to_string(), ItemVisibility::from(vis)))
+    }).collect()
+}
+
+/// Resolves paths within a crate, parsing the items of each module at most once.
+struct Resolver<'a> {
+    index: &'a CrateIndex,
+    items: RefCell<BTreeMap<ModulePathSegments, Vec<Item>>>,
+}
+
+impl Resolver<'_> {
+    fn with_items<T>(&self, module: &[String], f: impl FnOnce(&[Item]) -> T) -> T {
+        let mut items = self.items.borrow_mut();
+        let items = items.entry(module.to_vec()).or_insert_with(|| self.index.module(module)
+            .and_then(|m| self.index.items(m).ok())
+            .unwrap_or_default());
+        f(items)
+    }
+
+    /// Follows `use` declarations, including private ones, until the definition `name` within
+    /// `module` is found.
+    fn resolve_item(&self, module: &[String], name: &str, depth: usize) -> Option<DefinitionKey> {
+        if depth >= MAX_PATH_LENGTH {
+            return None
+        }
+        if self.with_items(module, definitions).iter().any(|(definition, _)| definition == name) {
+            return Some((module.to_vec(), name.to_string()))
+        }
+
+        let uses = self.with_items(module, |items| items.iter().filter_map(|item| match item {
+            Item::Use(u) => Some(flatten_use_tree(&u.tree)),
+            _ => None,
+        }).flatten().collect::<Vec<_>>());
+        // Named imports shadow glob imports
+        let named = uses.iter().filter(|u| u.binding() == Some(name)).find_map(|u| {
+            match self.index.resolve_module_prefix(module, &u.segments)? {
+                (target, rest) if rest.len() == 1 => self.resolve_item(&target, &rest[0], depth + 1),
+                _ => None,
+            }
+        });
+        named.or_else(|| uses.iter().filter(|u| u.glob).find_map(|u| {
+            match self.index.resolve_module_prefix(module, &u.segments)? {
+                (target, rest) if rest.is_empty() => self.resolve_item(&target, name, depth + 1),
+                _ => None,
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use crate::r#impl::index::index_crate;
+    use crate::r#impl::public_path::public_paths;
+    use crate::source::InMemory;
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn paths(module: &str, name: &str) -> Option<Vec<String>> {
+        let source_provider: InMemory = [
+            (lib_src_path(), "
+                pub mod shapes;
+                pub use shapes::circle::Circle;
+                pub mod prelude { pub use crate::shapes::*; }
+                mod private { pub struct Hidden; }
+                pub(crate) use private::Hidden;
+                pub use self::internal::Renamed as Public;
+                mod internal { pub use super::shapes::circle::Radius as Renamed; }
+            "),
+            (src_path().join("shapes/mod.rs"), "pub mod circle; pub use super::*;"),
+            (src_path().join("shapes/circle.rs"), "pub struct Circle; pub struct Radius; pub(crate) struct Internal;"),
+        ].into_iter().collect();
+
+        let index = index_crate(&source_provider, &lib_src_path(), Some(1)).unwrap();
+        let module: Vec<String> = module.split("::").filter(|s| !s.is_empty()).map(String::from).collect();
+        public_paths(&index, &module, name).map(|paths| paths.into_iter().map(|path| path.join("::")).collect())
+    }
+
+    #[test]
+    fn shortest_public_path_is_canonical() {
+        let paths = paths("shapes::circle", "Circle").unwrap();
+        assert_eq!(paths.first().map(String::as_str), Some("Circle"));
+        assert!(paths.contains(&"shapes::circle::Circle".to_string()));
+        assert!(paths.contains(&"prelude::circle::Circle".to_string()));
+    }
+
+    #[test]
+    fn renamed_reexports_are_followed() {
+        assert_eq!(paths("", "Public").unwrap().first().map(String::as_str), Some("Public"));
+        assert_eq!(paths("shapes::circle", "Radius").unwrap().first().map(String::as_str), Some("Public"));
+    }
+
+    #[test]
+    fn cyclic_glob_reexports_are_walked_once() {
+        let modules = ["a", "b", "c", "d", "e", "f"];
+        let lib: String = modules.iter()
+            .map(|module| format!("pub mod {module} {{ pub use crate::*; pub use super::*; pub struct {}; }}\n", module.to_uppercase()))
+            .collect();
+        let source_provider: InMemory = [(lib_src_path(), lib)].into_iter().collect();
+
+        let index = index_crate(&source_provider, &lib_src_path(), Some(1)).unwrap();
+        let paths = public_paths(&index, &["a".to_string()], "A").unwrap();
+        assert_eq!(paths.first().map(|path| path.join("::")), Some("a::A".to_string()));
+        // `a::A`, and `x::a::A` through the glob of the crate root in every module `x`
+        assert_eq!(paths.len(), 1 + modules.len());
+    }
+
+    #[test]
+    fn restricted_items_are_not_publicly_reachable() {
+        assert_eq!(paths("shapes::circle", "Internal"), Some(vec![]));
+        assert_eq!(paths("private", "Hidden"), Some(vec![]));
+        assert_eq!(paths("", "Hidden"), Some(vec![]));
+        assert_eq!(paths("", "Missing"), None);
+    }
+}
@@ -30,12 +30,12 @@ fn recurse_export_path<'a>(tree: &UseTree, mut current_module_path: ModulePath)
         // ex: `use crate::module::submodule::...`
         UseTree::Path(p) => // continue iterating through path
             if p.ident == next_module { recurse_export_path(&p.tree, current_module_path) }
-            else { Err(CargoQueryError::generic_error()) },
+            else { Err(CargoQueryError::export_does_not_match(&next_module)) },
 
         // A single export leaf module.
         // ex: `use crate::module::submodule::leaf_module;`
         UseTree::Name(p) =>  if p.ident == next_module { unimplemented!() // navigate to definition
-        } else { Err(CargoQueryError::generic_error()) },
+        } else { Err(CargoQueryError::export_does_not_match(&next_module)) },
 
         // A single export leaf module which has been renamed.
         // ex: `use crate::module::submodule::leaf_module as renamed_leaf_module;`
@@ -62,11 +62,11 @@ fn recurse_export_path<'a>(tree: &UseTree, mut current_module_path: ModulePath)
             //     .collect();
             // match forked_export_paths.len() {
             //     // No path is valid
-            //     0 => Err(CargoQueryError::generic_error()),
+            //     0 => Err(CargoQueryError::export_does_not_match(&next_module)),
             //     // A single path is valid
             //     1 => Ok(()),
             //     // More than 1 path is valid, which does not make sense
-            //     _ => Err(CargoQueryError::generic_error())
+            //     _ => Err(CargoQueryError::ambiguous_glob_export(&next_module, candidates))
             // }
             Ok(())
         }
@@ -83,22 +83,124 @@ fn find_export(scope_content: Vec<Item>, query_context: FindModuleContext) -> Re
     recurse_export_path(&export.tree, query_context.current_module_path)
 }
 
+/// A single path imported by a `use` declaration. Groups are flattened, so
+/// `use a::{b, c::*};` becomes `a::b` and `a::c::*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsePath {
+    pub segments: Vec<String>,
+    /// `use a::b as c;`
+    pub rename: Option<String>,
+    /// `use a::b::*;`
+    pub glob: bool,
+}
+
+impl UsePath {
+    /// The name the imported path is bound to in the importing module. `None` for globs.
+    pub fn binding(&self) -> Option<&str> {
+        if self.glob { return None }
+        self.rename.as_deref().or_else(|| self.segments.last().map(String::as_str))
+    }
+}
+
+/// Flattens a `use` tree into every path it imports.
+pub fn flatten_use_tree(tree: &UseTree) -> Vec<UsePath> {
+    let mut paths = vec![];
+    flatten_use_tree_into(tree, &mut vec![], &mut paths);
+    paths
+}
+
+fn flatten_use_tree_into(tree: &UseTree, prefix: &mut Vec<String>, paths: &mut Vec<UsePath>) {
+    match tree {
+        UseTree::Path(p) => {
+            prefix.push(p.ident.to_string());
+            flatten_use_tree_into(&p.tree, prefix, paths);
+            prefix.pop();
+        },
+        UseTree::Name(n) => paths.push(UsePath {
+            segments: prefix.iter().cloned().chain(std::iter::once(n.ident.to_string())).collect(),
+            rename: None,
+            glob: false,
+        }),
+        UseTree::Rename(r) => paths.push(UsePath {
+            segments: prefix.iter().cloned().chain(std::iter::once(r.ident.to_string())).collect(),
+            rename: Some(r.rename.to_string()),
+            glob: false,
+        }),
+        UseTree::Glob(_) => paths.push(UsePath { segments: prefix.clone(), rename: None, glob: true }),
+        UseTree::Group(g) => g.items.iter().for_each(|tree| flatten_use_tree_into(tree, prefix, paths)),
+    }
+}
+
+/// Resolves the `crate`, `self` and `super` prefixes of a path used within `module` (segments
+/// relative to the crate root). Returns the module the path is resolved from and the remaining
+/// segments. Paths starting with another crate's name are resolved relative to `module`, so
+/// callers need to check that the first remaining segment exists.
+pub fn resolve_path_prefix(module: &[String], segments: &[String]) -> Option<(Vec<String>, Vec<String>)> {
+    let mut base = module.to_vec();
+    let mut rest = segments;
+    match rest.first().map(String::as_str) {
+        Some("crate") => { base.clear(); rest = &rest[1..] },
+        Some("self") => rest = &rest[1..],
+        Some("super") => while rest.first().map(String::as_str) == Some("super") {
+            base.pop()?;
+            rest = &rest[1..];
+        },
+        _ => {}
+    }
+    Some((base, rest.to_vec()))
+}
+
+/// Every `use` declaration in `items` which is visible outside of its module, i.e. re-exports.
+pub fn get_reexports_from_item_scope(items: &[Item]) -> Vec<UsePath> {
+    items.iter().filter_map(|item| match item {
+        Item::Use(u) if !matches!(u.vis, syn::Visibility::Inherited) => Some(flatten_use_tree(&u.tree)),
+        _ => None
+    }).flatten().collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::{Path, PathBuf};
     use maplit::btreemap;
     use quote::quote;
-    use syn::parse2;
+    use syn::{ItemUse, parse2};
     use crate::helpers::test::initialize::test::initialize;
+    use crate::r#impl::recurse_export::{flatten_use_tree, resolve_path_prefix};
     use crate::helpers::test::mock_file::tests::{mock_file_for_path, random_module_contents, file};
 
     pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
     pub fn src_path() -> PathBuf { crate_path().join("src") }
     pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
 
+    #[test]
+    fn flatten_nested_use_groups() {
+        let item: ItemUse = parse2(quote!(pub use crate::a::{b, c::{d as e, *}};)).unwrap();
+        let paths: Vec<(String, Option<String>, bool)> = flatten_use_tree(&item.tree).into_iter()
+            .map(|p| (p.segments.join("::"), p.rename, p.glob))
+            .collect();
+
+        assert_eq!(paths, vec![
+            ("crate::a::b".to_string(), None, false),
+            ("crate::a::c::d".to_string(), Some("e".to_string()), false),
+            ("crate::a::c".to_string(), None, true),
+        ]);
+    }
+
+    #[test]
+    fn resolve_relative_path_prefixes() {
+        let module = vec!["foo".to_string(), "bar".to_string()];
+        let segments = |path: &str| path.split("::").map(String::from).collect::<Vec<_>>();
+
+        assert_eq!(resolve_path_prefix(&module, &segments("crate::baz")), Some((vec![], segments("baz"))));
+        assert_eq!(resolve_path_prefix(&module, &segments("self::baz")), Some((module.clone(), segments("baz"))));
+        assert_eq!(resolve_path_prefix(&module, &segments("super::super::baz")), Some((vec![], segments("baz"))));
+        assert_eq!(resolve_path_prefix(&module, &segments("super::super::super::baz")), None);
+    }
+
     fn leaf_module() {
         initialize();
-        let (expectation, tokens) = random_module_contents();
+        let (_, tokens_foo) = random_module_contents();
+        let (_, tokens_bar) = random_module_contents();
 
         mock_file_for_path(btreemap! {
             lib_src_path() => file(quote!{
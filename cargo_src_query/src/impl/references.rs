@@ -0,0 +1,487 @@
+//! # References
+//! The reverse of dependency analysis: given a struct, enum, union, trait or type alias, finds
+//! every item which mentions it, e.g. before changing a type shared across a crate:
+//!
+//! | Usage       | Mentions within                                                     |
+//! |-------------|---------------------------------------------------------------------|
+//! | `field`     | Fields of structs, unions and enum variants                         |
+//! | `signature` | Parameters and return types of fns, methods and trait methods       |
+//! | `impl`      | The self type or trait of an impl, e.g. `impl Display for Request`  |
+//! | `bound`     | Generic bounds, where clauses and supertraits                       |
+//! | `type`      | Types of type aliases, consts, statics and associated items         |
+//!
+//! ```text
+//! // crate::api
+//! pub struct Request {}
+//!
+//! // crate::server
+//! use crate::api::Request as Incoming;
+//! pub struct Server { pending: Vec<Incoming> }    field      struct crate::server::Server
+//! impl Server {
+//!     pub fn handle(&self, request: Incoming) {}   signature  fn crate::server::Server::handle
+//! }
+//! ```
+//!
+//! Mentions are resolved like paths within the mentioning module, through `use` imports, globs,
+//! re-exports and dependencies, so an unrelated `Request` of another module or crate isn't
+//! reported. Only paths ending in the definition's name, or in a name it is imported or
+//! re-exported as, e.g. `Incoming`, are resolved. Mentions of a type alias of the definition
+//! aren't reported, but the alias itself is. Private imports of a parent module, e.g.
+//! `super::Incoming`, aren't followed. Fn bodies aren't searched, and neither are items spliced
+//! in by `include!`. Impls, and their members, are reported within the module declaring the
+//! implemented type, e.g. `crate::api::Request` for `impl super::Request` in `crate::api::imp`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use syn::visit::{self, Visit};
+use syn::{Block, Expr, Generics, GenericParam, ImplItem, Item, Signature, TraitBound, TraitItem, Type, TypePath};
+use crate::core::{FoundDefinition, Resolver};
+use crate::errors::CargoQueryError;
+use crate::r#impl::attribute_search::named_item;
+use crate::r#impl::find_dependencies::CrateLocation;
+use crate::r#impl::index::{CrateIndex, IndexedModule};
+use crate::r#impl::recurse_export::flatten_use_tree;
+use crate::r#impl::type_alias::{resolve_impl_self_type, resolve_type_path};
+use crate::r#impl::verbatim::verbatim_items;
+use crate::source::SourceProvider;
+
+/// Which crates are searched for references.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceScope {
+    /// Only the crate declaring the definition
+    Crate,
+    /// The crate declaring the definition and every member of its workspace
+    Workspace,
+}
+
+/// Where an item mentions a definition. See the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Usage {
+    Field,
+    Signature,
+    Impl,
+    Bound,
+    Type,
+}
+
+impl Display for Usage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Usage::Field => "field",
+            Usage::Signature => "signature",
+            Usage::Impl => "impl",
+            Usage::Bound => "bound",
+            Usage::Type => "type",
+        })
+    }
+}
+
+/// An item mentioning a definition. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// Crate containing the item: `crate` for the queried crate, otherwise its library name
+    pub krate: String,
+    /// Module containing the item, relative to the crate root
+    pub module: Vec<String>,
+    /// Names from the module to the item, e.g. `["Server", "handle"]` for a method of `impl Server`
+    pub path: Vec<String>,
+    /// The kind of the item, e.g. `fn`, `struct` or `impl`
+    pub kind: &'static str,
+    pub usage: Usage,
+    /// The path as written, without generic arguments, e.g. `api::Request` or `Incoming`
+    pub mention: String,
+    pub file_path: PathBuf,
+    /// 1-based line of the enclosing top level item, e.g. of the impl for a method. `None` when
+    /// the source text of the item couldn't be recovered.
+    pub line: Option<usize>,
+}
+
+/// Renders the full path, e.g. `crate::server::Server::handle`.
+impl Display for Reference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let segments = std::iter::once(&self.krate).chain(&self.module).chain(&self.path);
+        write!(f, "{}", segments.map(String::as_str).collect::<Vec<_>>().join("::"))
+    }
+}
+
+/// Finds the items of `crates` which mention `definition`, in crate and module order. Each
+/// index is searched with paths resolved within the crate it's paired with.
+pub(crate) fn find_references(resolver: &Resolver, definition: &FoundDefinition, crates: &[(CrateLocation, CrateIndex)])
+    -> Result<Vec<Reference>, CargoQueryError> {
+    let (name, kind) = match named_item(&definition.item) {
+        Some((name, kind, _)) => (name, kind),
+        None => return Ok(vec![]),
+    };
+    let modules = crates.iter()
+        .flat_map(|(krate, index)| index.modules.iter().map(move |module| (krate, module, index)))
+        .map(|(krate, module, index)| Ok((krate, module, index.items(module)?)))
+        .collect::<Result<Vec<_>, CargoQueryError>>()?;
+    let names = imported_names(modules.iter().map(|(_, _, items)| items.as_slice()), &name);
+    // Dependencies are located through `cargo metadata`, whose paths are absolute
+    let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let crate_path = absolute(&definition.krate.path);
+    let is_definition = |found: &FoundDefinition| {
+        absolute(&found.krate.path) == crate_path && found.module == definition.module
+            && named_item(&found.item).is_some_and(|(found_name, found_kind, _)| (found_name.as_str(), found_kind) == (name.as_str(), kind))
+    };
+
+    let mut references = vec![];
+    for (krate, module, items) in &modules {
+        let mut resolved: BTreeMap<String, bool> = BTreeMap::new();
+        for item in items {
+            let mut line = None;
+            let mut self_type = None;
+            let mut found = vec![];
+            for Mention { path, kind, usage, mentioned } in mentions(item) {
+                if !mentioned.segments.last().is_some_and(|segment| names.contains(&segment.ident.to_string())) {
+                    continue
+                }
+                let written = written_path(&mentioned);
+                let refers = *resolved.entry(written.clone()).or_insert_with(|| {
+                    resolve_type_path(resolver, krate, &module.path, &mentioned).is_ok_and(|found| is_definition(&found))
+                });
+                if !refers || found.contains(&(path.clone(), usage, written.clone())) {
+                    continue
+                }
+                found.push((path.clone(), usage, written.clone()));
+                // Members of `impl super::Type` are named after the module declaring the type
+                let (item_module, path) = match self_type.get_or_insert_with(|| impl_self_type(resolver, krate, &module.path, item)) {
+                    Some((type_module, type_name)) => (type_module.clone(), [type_name.clone()].into_iter().chain(path.into_iter().skip(1)).collect()),
+                    None => (module.path.clone(), path),
+                };
+                references.push(Reference {
+                    krate: krate.name.clone(),
+                    module: item_module,
+                    path,
+                    kind,
+                    usage,
+                    mention: written,
+                    file_path: module.file_path.clone(),
+                    line: *line.get_or_insert_with(|| item_line(resolver.source_provider, module, item)),
+                });
+            }
+        }
+    }
+    Ok(references)
+}
+
+/// `name`, and every name it is imported or re-exported as through `use ... as ...`, including
+/// renames of renames.
+pub(crate) fn imported_names<'a>(scopes: impl Iterator<Item=&'a [Item]>, name: &str) -> BTreeSet<String> {
+    let renames: Vec<(String, String)> = scopes
+        .flat_map(|items| items.iter())
+        .filter_map(|item| match item {
+            Item::Use(item_use) => Some(flatten_use_tree(&item_use.tree)),
+            _ => None
+        })
+        .flatten()
+        .filter_map(|path| Some((path.segments.last()?.clone(), path.rename?)))
+        .collect();
+    let mut names = BTreeSet::from([name.to_string()]);
+    loop {
+        let added: Vec<String> = renames.iter()
+            .filter(|(original, rename)| names.contains(original) && !names.contains(rename))
+            .map(|(_, rename)| rename.clone())
+            .collect();
+        if added.is_empty() {
+            return names
+        }
+        names.extend(added);
+    }
+}
+
+/// The module and name of the type an impl within `module` of `krate` is for, e.g. `a` and
+/// `Request` for `impl super::a::Request`. `None` for other items, and for types which can't be
+/// resolved or are declared by another crate.
+fn impl_self_type(resolver: &Resolver, krate: &CrateLocation, module: &[String], item: &Item) -> Option<(Vec<String>, String)> {
+    let Item::Impl(item_impl) = item else { return None };
+    let found = resolve_impl_self_type(resolver, krate, module, item_impl)?;
+    let (name, _, _) = named_item(&found.item)?;
+    (found.krate == *krate).then_some((found.module, name))
+}
+
+/// `::a::b::C<T>` => `::a::b::C`
+fn written_path(path: &syn::Path) -> String {
+    let segments = path.segments.iter().map(|segment| segment.ident.to_string()).collect::<Vec<_>>().join("::");
+    match path.leading_colon {
+        Some(_) => format!("::{segments}"),
+        None => segments,
+    }
+}
+
+fn item_line(source_provider: &dyn SourceProvider, module: &IndexedModule, item: &Item) -> Option<usize> {
+    verbatim_items(source_provider, [module.file_path.as_path()], &module.path, vec![item.clone()]).ok()?
+        .pop()
+        .map(|verbatim| verbatim.line)
+}
+
+/// A path mentioned by an item, or by a member of an impl or trait.
+struct Mention {
+    path: Vec<String>,
+    kind: &'static str,
+    usage: Usage,
+    mentioned: syn::Path,
+}
+
+#[derive(Default)]
+struct Mentions(Vec<Mention>);
+
+impl Mentions {
+    /// Collects the paths `visit` passes to the collector, in which the parameters of `generics`
+    /// are in scope.
+    fn collect(&mut self, path: &[String], kind: &'static str, usage: Usage, generics: &[&Generics],
+               visit: impl FnOnce(&mut PathCollector)) {
+        let mut collector = PathCollector::new(generics);
+        visit(&mut collector);
+        self.0.extend(collector.paths.into_iter().map(|mentioned| Mention { path: path.to_vec(), kind, usage, mentioned }));
+    }
+
+    fn signature(&mut self, path: &[String], generics: &[&Generics], signature: &Signature) {
+        let generics = [generics, &[&signature.generics]].concat();
+        self.collect(path, "fn", Usage::Signature, &generics, |collector| {
+            signature.inputs.iter().for_each(|input| collector.visit_fn_arg(input));
+            collector.visit_return_type(&signature.output);
+        });
+        self.collect(path, "fn", Usage::Bound, &generics, |collector| collector.visit_generics(&signature.generics));
+    }
+}
+
+/// The paths mentioned by an item and its members. Modules are skipped, as inline modules are
+/// searched on their own.
+fn mentions(item: &Item) -> Vec<Mention> {
+    let mut mentions = Mentions::default();
+    let bounds = |mentions: &mut Mentions, path: &[String], kind, generics: &Generics| {
+        mentions.collect(path, kind, Usage::Bound, &[generics], |collector| collector.visit_generics(generics));
+    };
+    match item {
+        Item::Struct(i) => {
+            let path = [i.ident.to_string()];
+            mentions.collect(&path, "struct", Usage::Field, &[&i.generics], |collector| collector.visit_fields(&i.fields));
+            bounds(&mut mentions, &path, "struct", &i.generics);
+        },
+        Item::Union(i) => {
+            let path = [i.ident.to_string()];
+            mentions.collect(&path, "union", Usage::Field, &[&i.generics], |collector| collector.visit_fields_named(&i.fields));
+            bounds(&mut mentions, &path, "union", &i.generics);
+        },
+        Item::Enum(i) => {
+            let path = [i.ident.to_string()];
+            mentions.collect(&path, "enum", Usage::Field, &[&i.generics], |collector| {
+                i.variants.iter().for_each(|variant| collector.visit_fields(&variant.fields));
+            });
+            bounds(&mut mentions, &path, "enum", &i.generics);
+        },
+        Item::Fn(i) => mentions.signature(&[i.sig.ident.to_string()], &[], &i.sig),
+        Item::Type(i) => {
+            let path = [i.ident.to_string()];
+            mentions.collect(&path, "type", Usage::Type, &[&i.generics], |collector| collector.visit_type(&i.ty));
+            bounds(&mut mentions, &path, "type", &i.generics);
+        },
+        Item::Const(i) => mentions.collect(&[i.ident.to_string()], "const", Usage::Type, &[], |collector| collector.visit_type(&i.ty)),
+        Item::Static(i) => mentions.collect(&[i.ident.to_string()], "static", Usage::Type, &[], |collector| collector.visit_type(&i.ty)),
+        Item::Trait(i) => {
+            let name = i.ident.to_string();
+            mentions.collect(std::slice::from_ref(&name), "trait", Usage::Bound, &[&i.generics], |collector| {
+                collector.visit_generics(&i.generics);
+                i.supertraits.iter().for_each(|bound| collector.visit_type_param_bound(bound));
+            });
+            for member in &i.items {
+                match member {
+                    TraitItem::Method(m) => mentions.signature(&[name.clone(), m.sig.ident.to_string()], &[&i.generics], &m.sig),
+                    TraitItem::Type(t) => {
+                        let path = [name.clone(), t.ident.to_string()];
+                        let generics = [&i.generics, &t.generics];
+                        mentions.collect(&path, "type", Usage::Bound, &generics, |collector| {
+                            collector.visit_generics(&t.generics);
+                            t.bounds.iter().for_each(|bound| collector.visit_type_param_bound(bound));
+                        });
+                        if let Some((_, default)) = &t.default {
+                            mentions.collect(&path, "type", Usage::Type, &generics, |collector| collector.visit_type(default));
+                        }
+                    },
+                    TraitItem::Const(c) => mentions.collect(&[name.clone(), c.ident.to_string()], "const", Usage::Type, &[&i.generics],
+                                                            |collector| collector.visit_type(&c.ty)),
+                    _ => {}
+                }
+            }
+        },
+        Item::Impl(i) => {
+            let name = match i.self_ty.as_ref() {
+                Type::Path(ty) => ty.path.segments.last().map(|segment| segment.ident.to_string()),
+                _ => None,
+            }.unwrap_or_else(|| quote::ToTokens::to_token_stream(&i.self_ty).to_string());
+            mentions.collect(std::slice::from_ref(&name), "impl", Usage::Impl, &[&i.generics], |collector| {
+                collector.visit_type(&i.self_ty);
+                if let Some((_, trait_path, _)) = &i.trait_ {
+                    collector.push(trait_path);
+                    collector.visit_path(trait_path);
+                }
+            });
+            bounds(&mut mentions, std::slice::from_ref(&name), "impl", &i.generics);
+            for member in &i.items {
+                match member {
+                    ImplItem::Method(m) => mentions.signature(&[name.clone(), m.sig.ident.to_string()], &[&i.generics], &m.sig),
+                    ImplItem::Type(t) => mentions.collect(&[name.clone(), t.ident.to_string()], "type", Usage::Type, &[&i.generics, &t.generics],
+                                                          |collector| collector.visit_type(&t.ty)),
+                    ImplItem::Const(c) => mentions.collect(&[name.clone(), c.ident.to_string()], "const", Usage::Type, &[&i.generics],
+                                                           |collector| collector.visit_type(&c.ty)),
+                    _ => {}
+                }
+            }
+        },
+        _ => {}
+    }
+    mentions.0
+}
+
+/// Collects the paths of the types and traits within the visited syntax, except for generic
+/// parameters and paths starting with `Self`. Expressions, e.g. array lengths and fn bodies,
+/// aren't visited.
+struct PathCollector {
+    parameters: BTreeSet<String>,
+    paths: Vec<syn::Path>,
+}
+
+impl PathCollector {
+    fn new(generics: &[&Generics]) -> Self {
+        let parameters = generics.iter()
+            .flat_map(|generics| generics.params.iter())
+            .filter_map(|param| match param {
+                GenericParam::Type(param) => Some(param.ident.to_string()),
+                _ => None,
+            })
+            .collect();
+        PathCollector { parameters, paths: vec![] }
+    }
+
+    fn push(&mut self, path: &syn::Path) {
+        let first = match path.segments.first() {
+            Some(first) => first.ident.to_string(),
+            None => return,
+        };
+        let parameter = path.leading_colon.is_none() && path.segments.len() == 1 && self.parameters.contains(&first);
+        if first != "Self" && !parameter {
+            self.paths.push(path.clone());
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for PathCollector {
+    fn visit_block(&mut self, _: &'ast Block) {}
+
+    fn visit_expr(&mut self, _: &'ast Expr) {}
+
+    fn visit_trait_bound(&mut self, bound: &'ast TraitBound) {
+        self.push(&bound.path);
+        visit::visit_trait_bound(self, bound);
+    }
+
+    fn visit_type_path(&mut self, ty: &'ast TypePath) {
+        if ty.qself.is_none() {
+            self.push(&ty.path);
+        }
+        visit::visit_type_path(self, ty);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use maplit::btreemap;
+    use proc_macro2::Span;
+    use syn::Ident;
+    use crate::core::Resolver;
+    use crate::helpers::test::mock_dependencies::tests::MockDependencies;
+    use crate::r#impl::find_dependencies::CrateLocation;
+    use crate::r#impl::index::index_crate;
+    use crate::r#impl::references::find_references;
+    use crate::r#impl::type_alias::find_type;
+    use crate::source::InMemory;
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn source_provider() -> InMemory {
+        [
+            (lib_src_path(), [
+                "pub mod api;",
+                "mod server;",
+                "pub use api::Request as PublicRequest;",
+                "pub type Requests = Vec<api::Request>;",
+                "pub struct Request {}",
+                "pub fn local(request: Request) {}",
+            ].join("\n")),
+            (src_path().join("api.rs"), [
+                "pub struct Request {}",
+                "pub trait Handler<Request> { fn handle(&self, request: Request); }",
+                "pub trait Service: Into<Request> where Self: Sized {}",
+                "impl Clone for Request { fn clone(&self) -> Self { Request {} } }",
+            ].join("\n")),
+            (src_path().join("server.rs"), [
+                "use crate::api::Request as Incoming;",
+                "pub struct Server {",
+                "    pending: Vec<Incoming>,",
+                "}",
+                "impl Server {",
+                "    pub fn handle(&self, request: Incoming) -> Option<crate::PublicRequest> { None }",
+                "    pub fn handle_local(&self, request: crate::Request) {}",
+                "    pub fn generic<T: Into<Incoming>>(&self, value: T) { let unused: Incoming = value.into(); }",
+                "}",
+                "mod inline { pub enum Event { Received(super::super::api::Request) } }",
+            ].join("\n")),
+            (PathBuf::from("/client/src/lib.rs"), [
+                "use example::api::Request;",
+                "pub struct Client { last: Option<Request> }",
+                "pub struct Other { local: LocalRequest }",
+                "pub struct LocalRequest {}",
+            ].join("\n")),
+        ].into_iter().collect()
+    }
+
+    fn references(module: &[&str], name: &str) -> Vec<String> {
+        let source_provider = source_provider();
+        let dependencies = MockDependencies(btreemap! {
+            (PathBuf::from("/client"), "example".to_string()) =>
+                CrateLocation { name: "example".to_string(), path: crate_path().to_path_buf() },
+        });
+        let resolver = Resolver { source_provider: &source_provider, dependencies: &dependencies };
+        let root = CrateLocation { name: "crate".to_string(), path: crate_path().to_path_buf() };
+        let client = CrateLocation { name: "client".to_string(), path: PathBuf::from("/client") };
+        let module = module.iter().map(ToString::to_string).collect();
+        let definition = find_type(&resolver, &[(root.clone(), module, Ident::new(name, Span::call_site()))]).unwrap();
+        let crates = vec![
+            (root, index_crate(&source_provider, &lib_src_path(), Some(1)).unwrap()),
+            (client, index_crate(&source_provider, Path::new("/client/src/lib.rs"), Some(1)).unwrap()),
+        ];
+
+        find_references(&resolver, &definition, &crates).unwrap().iter()
+            .map(|reference| format!("{} {} {reference} `{}` line {}", reference.usage, reference.kind, reference.mention,
+                                     reference.line.map_or("?".to_string(), |line| line.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn find_references_through_imports_and_reexports() {
+        assert_eq!(references(&["api"], "Request"), vec![
+            "type type crate::Requests `api::Request` line 4",
+            "bound trait crate::api::Service `Request` line 3",
+            "impl impl crate::api::Request `Request` line 4",
+            "field struct crate::server::Server `Incoming` line 2",
+            "signature fn crate::server::Server::handle `Incoming` line 5",
+            "signature fn crate::server::Server::handle `crate::PublicRequest` line 5",
+            "bound fn crate::server::Server::generic `Incoming` line 5",
+            "field enum crate::server::inline::Event `super::super::api::Request` line 10",
+            "field struct client::Client `Request` line 2",
+        ]);
+    }
+
+    #[test]
+    fn skip_unrelated_definitions_with_the_same_name() {
+        assert_eq!(references(&[], "Request"), vec![
+            "signature fn crate::local `Request` line 6",
+            "signature fn crate::server::Server::handle_local `crate::Request` line 5",
+        ]);
+    }
+}
@@ -0,0 +1,244 @@
+//! # Resolution Traces
+//! Explains how a query was resolved. Each segment of the queried path is a [ResolutionStep],
+//! which lists every candidate the resolver tried and why it was rejected:
+//!
+//! ```text
+//! query nested_directory::missing_module
+//! ├── crate root
+//! │   └── ✓ file /example/src/lib.rs
+//! ├── nested_directory
+//! │   ├── ✓ declaration `mod nested_directory`
+//! │   ├── ✗ file /example/src/nested_directory.rs (missing)
+//! │   └── ✓ file /example/src/nested_directory/mod.rs
+//! └── missing_module
+//!     └── ✗ declaration `mod missing_module` (missing)
+//! ```
+//!
+//! Traces are produced by [QueryContext::explain_module](crate::context::QueryContext::explain_module)
+//! and [QueryContext::explain_definition](crate::context::QueryContext::explain_definition).
+
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+/// Why a candidate was not used.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rejection {
+    /// The declaration, definition or file does not exist
+    Missing,
+    /// The file exists but is not valid Rust
+    ParseError(String),
+    /// The candidate is not visible from outside of its module, e.g. a private `use`
+    NotVisible,
+    /// The declaration is disabled by the given `#[cfg(...)]` predicate
+    CfgDisabled(String),
+}
+
+/// Something the resolver tried while resolving a segment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CandidateKind {
+    /// `mod name;` or `mod name { ... }` in the parent module
+    Declaration(String),
+    /// The body of an inline module, `mod name { ... }`
+    Inline(String),
+    File(PathBuf),
+    /// A struct, enum or function named like the segment
+    Definition(String),
+    /// A `use` declaration which binds the segment, e.g. `pub use crate::a::Name;`
+    Reexport(String),
+    /// The dependency a re-export continues in, e.g. `another_crate` for
+    /// `pub use another_crate::Name;`
+    Dependency(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub kind: CandidateKind,
+    /// `None` when the candidate was used
+    pub rejection: Option<Rejection>,
+}
+
+/// The resolution of a single segment of the queried path.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolutionStep {
+    pub segment: String,
+    pub candidates: Vec<Candidate>,
+    /// The re-export the segment was resolved through, e.g. `crate::internal::Name`
+    pub followed_reexport: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolutionTrace {
+    pub query: String,
+    pub steps: Vec<ResolutionStep>,
+    /// Every path the definition was reached through, from the queried path to where it is
+    /// defined, e.g. `crate::Name`, `another_crate::Name` and `another_crate::inner::Name`
+    #[serde(default)]
+    pub provenance: Vec<String>,
+    /// Set when the backend does not record traces
+    pub unsupported: bool,
+}
+
+impl ResolutionTrace {
+    pub(crate) fn unsupported(query: String) -> Self {
+        ResolutionTrace { query, steps: vec![], provenance: vec![], unsupported: true }
+    }
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::Missing => write!(f, "missing"),
+            Rejection::ParseError(error) => write!(f, "parse error: {error}"),
+            Rejection::NotVisible => write!(f, "not visible"),
+            Rejection::CfgDisabled(predicate) => write!(f, "disabled by #[cfg({predicate})]"),
+        }
+    }
+}
+
+impl Display for CandidateKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CandidateKind::Declaration(name) => write!(f, "declaration `mod {name}`"),
+            CandidateKind::Inline(name) => write!(f, "inline module `mod {name} {{ ... }}`"),
+            CandidateKind::File(path) => write!(f, "file {}", path.display()),
+            CandidateKind::Definition(name) => write!(f, "definition `{name}`"),
+            CandidateKind::Reexport(path) => write!(f, "re-export `use {path}`"),
+            CandidateKind::Dependency(name) => write!(f, "dependency `{name}`"),
+        }
+    }
+}
+
+/// Renders the trace as a tree, followed by the provenance of definitions reached through
+/// re-exports.
+impl Display for ResolutionTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "query {}", self.query)?;
+        if self.unsupported {
+            return write!(f, "\n└── the query backend does not record resolution traces")
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            let last_step = i + 1 == self.steps.len();
+            let (branch, indent) = if last_step { ("└──", "    ") } else { ("├──", "│   ") };
+            write!(f, "\n{branch} {}", step.segment)?;
+            if let Some(reexport) = &step.followed_reexport {
+                write!(f, " (via `{reexport}`)")?;
+            }
+            for (j, candidate) in step.candidates.iter().enumerate() {
+                let branch = if j + 1 == step.candidates.len() { "└──" } else { "├──" };
+                match &candidate.rejection {
+                    None => write!(f, "\n{indent}{branch} ✓ {}", candidate.kind)?,
+                    Some(rejection) => write!(f, "\n{indent}{branch} ✗ {} ({rejection})", candidate.kind)?,
+                }
+            }
+        }
+        if self.provenance.len() > 1 {
+            write!(f, "\nresolved through {}", self.provenance.join(" → "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Records a [ResolutionTrace] while a query is resolved. Does nothing when disabled, so the
+/// resolver can record unconditionally.
+#[derive(Default)]
+pub(crate) struct Tracer {
+    trace: Option<RefCell<ResolutionTrace>>,
+}
+
+impl Tracer {
+    pub fn disabled() -> Self {
+        Tracer { trace: None }
+    }
+
+    pub fn enabled(query: String) -> Self {
+        Tracer { trace: Some(RefCell::new(ResolutionTrace { query, ..Default::default() })) }
+    }
+
+    /// Starts resolving the next segment.
+    pub fn step(&self, segment: impl ToString) {
+        if let Some(trace) = &self.trace {
+            trace.borrow_mut().steps.push(ResolutionStep { segment: segment.to_string(), ..Default::default() });
+        }
+    }
+
+    pub fn candidate(&self, kind: CandidateKind, rejection: Option<Rejection>) {
+        if let Some(trace) = &self.trace {
+            if let Some(step) = trace.borrow_mut().steps.last_mut() {
+                step.candidates.push(Candidate { kind, rejection });
+            }
+        }
+    }
+
+    pub fn followed_reexport(&self, reexport: impl ToString) {
+        if let Some(trace) = &self.trace {
+            if let Some(step) = trace.borrow_mut().steps.last_mut() {
+                step.followed_reexport = Some(reexport.to_string());
+            }
+        }
+    }
+
+    /// Records a path the definition is reached through.
+    pub fn provenance(&self, path: impl ToString) {
+        if let Some(trace) = &self.trace {
+            trace.borrow_mut().provenance.push(path.to_string());
+        }
+    }
+
+    /// A tracer for a tentative search, enabled when this one is. Its trace is only kept when
+    /// it's [appended](Self::append).
+    pub fn probe(&self) -> Tracer {
+        match &self.trace {
+            Some(trace) => Tracer::enabled(trace.borrow().query.clone()),
+            None => Tracer::disabled(),
+        }
+    }
+
+    /// Adds the steps and provenance recorded by a [probe](Self::probe).
+    pub fn append(&self, probe: Tracer) {
+        if let Some(trace) = &self.trace {
+            let probe = probe.into_trace();
+            let mut trace = trace.borrow_mut();
+            trace.steps.extend(probe.steps);
+            trace.provenance.extend(probe.provenance);
+        }
+    }
+
+    pub fn into_trace(self) -> ResolutionTrace {
+        self.trace.map(RefCell::into_inner).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::r#impl::trace::{CandidateKind, Rejection, Tracer};
+
+    #[test]
+    fn renders_trace_as_tree() {
+        let tracer = Tracer::enabled("foo::Bar".to_string());
+        tracer.step("foo");
+        tracer.candidate(CandidateKind::Declaration("foo".to_string()), None);
+        tracer.candidate(CandidateKind::File(PathBuf::from("/example/src/foo.rs")), Some(Rejection::Missing));
+        tracer.step("Bar");
+        tracer.candidate(CandidateKind::Definition("Bar".to_string()), Some(Rejection::Missing));
+        tracer.candidate(CandidateKind::Reexport("crate::bar::Bar".to_string()), None);
+        tracer.followed_reexport("crate::bar::Bar");
+        tracer.candidate(CandidateKind::Dependency("bar".to_string()), None);
+        tracer.provenance("crate::foo::Bar");
+        tracer.provenance("bar::Bar");
+
+        assert_eq!(tracer.into_trace().to_string(), [
+            "query foo::Bar",
+            "├── foo",
+            "│   ├── ✓ declaration `mod foo`",
+            "│   └── ✗ file /example/src/foo.rs (missing)",
+            "└── Bar (via `crate::bar::Bar`)",
+            "    ├── ✗ definition `Bar` (missing)",
+            "    ├── ✓ re-export `use crate::bar::Bar`",
+            "    └── ✓ dependency `bar`",
+            "resolved through crate::foo::Bar → bar::Bar",
+        ].join("\n"));
+    }
+}
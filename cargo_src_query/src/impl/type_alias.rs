@@ -0,0 +1,314 @@
+//! # Type Alias Chasing
+//! A path naming `type Alias = some::Other<T>;` is usually queried for the definition behind it.
+//! Aliases are followed until a struct, enum, union or trait is reached, substituting generic
+//! arguments along the way:
+//!
+//! ```text
+//! // crate::config
+//! use another_crate::Settings;
+//! pub type Config<T> = Settings<T, u8>;
+//!
+//! // another_crate
+//! pub type Settings<A, B> = Inner<B, A>;
+//! pub struct Inner<X, Y> { x: X, y: Y }
+//!
+//! crate::config::Config<String> => pub struct Inner { x: u8, y: String }
+//! ```
+//!
+//! The target of an alias is resolved like a path within the alias' module, so it may name:
+//! 1. A definition of the same module, or a path starting with `crate`, `self` or `super`
+//! 2. A definition imported with `use`, including private and glob imports
+//! 3. A definition of a dependency, e.g. `another_crate::Settings`. See
+//!    [DependencyLookup](crate::DependencyLookup).
+//!
+//! Trait objects, e.g. `type Handler = dyn Send + Handle<u8>;`, resolve to their principal trait,
+//! skipping auto traits such as `Send`, `Sync` and `Unpin`.
+//! Targets which can't be found, such as definitions of `std`, are reported as errors.
+
+use std::collections::BTreeSet;
+use syn::{GenericParam, Ident, Item, ItemImpl, ItemType, PathArguments, TraitBoundModifier, Type, TypeParamBound, TypePath};
+use crate::core::{find_definition, FoundDefinition, ident, module_path, Resolver, use_path_module};
+use crate::errors::CargoQueryError;
+use crate::r#impl::find_dependencies::{CrateLocation, Definition};
+use crate::r#impl::find_mod::find_mod_in_crate_traced;
+use crate::r#impl::generics::{is_prelude_type, substitute_generics};
+use crate::r#impl::recurse_export::flatten_use_tree;
+use crate::r#impl::trace::Tracer;
+
+/// Aliases which refer to each other, e.g. `type A = B; type B = A;`, are given up on after this
+/// many steps.
+const MAX_ALIAS_DEPTH: usize = 32;
+
+/// Definitions an alias can lead to.
+const DEFINITIONS: [Definition; 5] = [Definition::Type, Definition::Struct, Definition::Enum, Definition::Union, Definition::Trait];
+
+/// Traits which don't make up the principal trait of a trait object.
+const AUTO_TRAITS: [&str; 6] = ["Send", "Sync", "Unpin", "UnwindSafe", "RefUnwindSafe", "Sized"];
+
+/// A type alias which was followed.
+#[derive(Debug, Clone)]
+pub struct AliasStep {
+    /// Full path of the alias, e.g. `crate::config::Config` or `another_crate::Settings`
+    pub path: String,
+    /// The alias as declared
+    pub alias: ItemType,
+    /// Generic arguments the alias was used with, e.g. `<String>` for `Config<String>`
+    pub arguments: PathArguments,
+}
+
+/// The definition behind a chain of type aliases. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct AliasResolution {
+    /// The struct, enum, union or trait the aliases lead to, with `arguments` substituted
+    pub definition: Item,
+    /// Full path of the definition, e.g. `another_crate::Inner`
+    pub path: String,
+    /// The aliases followed, starting with the queried one. Empty when the queried path names
+    /// the definition itself.
+    pub aliases: Vec<AliasStep>,
+    /// Generic arguments substituted into the definition, after substitution through every alias
+    pub arguments: PathArguments,
+}
+
+/// Follows the type aliases starting at the definition `name` within `module` of `krate`, used
+/// with `arguments`.
+pub(crate) fn resolve_type_aliases(resolver: &Resolver, krate: CrateLocation, module: Vec<String>, name: Ident,
+                                   arguments: PathArguments) -> Result<AliasResolution, CargoQueryError> {
+    let mut aliases: Vec<AliasStep> = vec![];
+    let root = krate.clone();
+    let mut candidates = vec![(krate, module, name)];
+    let mut arguments = arguments;
+    // Generic parameters which may appear in `arguments`: those of the caller for the queried
+    // path, and those the previous alias was left with for its target
+    let mut alias_parameters: Option<BTreeSet<Ident>> = None;
+    loop {
+        let is_parameter = |name: &Ident| match &alias_parameters {
+            None => is_caller_parameter(resolver, &root, name),
+            Some(parameters) => parameters.contains(name),
+        };
+        let found = match find_type(resolver, &candidates) {
+            Ok(found) => found,
+            Err(e) => return Err(match aliases.last() {
+                Some(step) => CargoQueryError::could_not_follow_type_alias(&step.path, &step.alias.ty, "the target could not be found"),
+                None => e,
+            }),
+        };
+        let path = definition_path(&found);
+        let alias = match found.item {
+            Item::Type(alias) => alias,
+            item => return Ok(AliasResolution { definition: substitute_generics(item, &arguments, &is_parameter)?, path, aliases, arguments }),
+        };
+        if aliases.len() == MAX_ALIAS_DEPTH {
+            return Err(CargoQueryError::could_not_follow_type_alias(&path, &alias.ty, "the aliases refer to each other"))
+        }
+
+        let Item::Type(substituted) = substitute_generics(Item::Type(alias.clone()), &arguments, &is_parameter)? else {
+            unreachable!("substituting generics doesn't change the kind of an item")
+        };
+        alias_parameters = Some(substituted.generics.params.iter().filter_map(|param| match param {
+            GenericParam::Type(param) => Some(param.ident.clone()),
+            GenericParam::Const(param) => Some(param.ident.clone()),
+            _ => None,
+        }).collect());
+        let target = alias_target(&substituted.ty)
+            .ok_or_else(|| CargoQueryError::could_not_follow_type_alias(&path, &alias.ty, "the target is not a path or trait object"))?;
+        candidates = target_candidates(resolver, &found.krate, &found.module, target)?;
+        let target_arguments = target.segments.last().map_or(PathArguments::None, |segment| segment.arguments.clone());
+        aliases.push(AliasStep { path, alias, arguments: std::mem::replace(&mut arguments, target_arguments) });
+    }
+}
+
+/// Whether a generic argument of a queried path, e.g. `T` in `crate::Pair<String, T>`, is a
+/// generic parameter of the caller: it's neither a primitive or prelude type, nor a type
+/// reachable from the root of `krate`.
+pub(crate) fn is_caller_parameter(resolver: &Resolver, krate: &CrateLocation, name: &Ident) -> bool {
+    !is_prelude_type(name) && resolve_type_path(resolver, krate, &[], &syn::Path::from(name.clone())).is_err()
+}
+
+/// Finds the first alias or definition among `candidates`, which are tried in order.
+pub(crate) fn find_type(resolver: &Resolver, candidates: &[(CrateLocation, Vec<String>, Ident)]) -> Result<FoundDefinition, CargoQueryError> {
+    let mut error = None;
+    for (krate, module, name) in candidates {
+        match find_definition(resolver, krate, module, name, &DEFINITIONS, &Tracer::disabled()) {
+            Ok(found) => return Ok(found),
+            Err(e) => error = Some(e),
+        }
+    }
+    Err(error.unwrap_or_else(CargoQueryError::could_not_find_module))
+}
+
+/// The path a type alias refers to, e.g. `Other<T>` for `Other<T>` and `Trait` for
+/// `dyn Send + Trait`.
+fn alias_target(ty: &Type) -> Option<&syn::Path> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => Some(path),
+        Type::TraitObject(object) => {
+            let traits: Vec<&syn::Path> = object.bounds.iter().filter_map(|bound| match bound {
+                TypeParamBound::Trait(bound) if matches!(bound.modifier, TraitBoundModifier::None) => Some(&bound.path),
+                _ => None
+            }).collect();
+            // Objects of only auto traits, e.g. `dyn Send + Sync`, have no principal trait
+            traits.iter().find(|path| !is_auto_trait(path)).or(traits.first()).copied()
+        },
+        Type::Paren(paren) => alias_target(&paren.elem),
+        Type::Group(group) => alias_target(&group.elem),
+        _ => None,
+    }
+}
+
+/// Whether `path` names one of the marker traits which may be added to a trait object besides
+/// its principal trait, e.g. `Send` or `std::marker::Sync`.
+fn is_auto_trait(path: &syn::Path) -> bool {
+    path.segments.last().is_some_and(|segment|
+        AUTO_TRAITS.contains(&segment.ident.to_string().as_str()) && segment.arguments.is_empty())
+}
+
+/// Resolves a type or trait path written within `module` of `krate`, e.g. `super::Request`
+/// or an imported `Request`, to the alias or definition it names.
+pub(crate) fn resolve_type_path(resolver: &Resolver, krate: &CrateLocation, module: &[String], path: &syn::Path)
+    -> Result<FoundDefinition, CargoQueryError> {
+    find_type(resolver, &target_candidates(resolver, krate, module, path)?)
+}
+
+/// Resolves the self type of an impl within `module` of `krate`, e.g. `super::Request` or an
+/// imported `Request`, to the alias or definition it names.
+pub(crate) fn resolve_impl_self_type(resolver: &Resolver, krate: &CrateLocation, module: &[String], item_impl: &ItemImpl)
+    -> Option<FoundDefinition> {
+    let Type::Path(TypePath { qself: None, path }) = item_impl.self_ty.as_ref() else { return None };
+    resolve_type_path(resolver, krate, module, path).ok()
+}
+
+/// Where the target of an alias declared within `module` of `krate` may be declared, in order
+/// of precedence: imported with `use`, relative to the module, and imported through a glob.
+fn target_candidates(resolver: &Resolver, krate: &CrateLocation, module: &[String], target: &syn::Path)
+    -> Result<Vec<(CrateLocation, Vec<String>, Ident)>, CargoQueryError> {
+    let features = resolver.dependencies.enabled_features(&krate.path);
+    let scope = find_mod_in_crate_traced(resolver.source_provider, &krate.path, module_path(module).into(), features.as_ref(),
+                                         &Tracer::disabled())?;
+    let segments: Vec<String> = target.segments.iter().map(|s| s.ident.to_string()).collect();
+    let global = target.leading_colon.is_some();
+    let imports: Vec<_> = scope.iter()
+        .filter_map(|item| match item {
+            Item::Use(item_use) => Some(flatten_use_tree(&item_use.tree).into_iter().map(|path| (path, item_use.leading_colon.is_some()))),
+            _ => None
+        })
+        .flatten()
+        .collect();
+
+    let mut candidates = vec![];
+    let mut candidate = |segments: &[String], global: bool| {
+        let Some((name, module_segments)) = segments.split_last() else { return };
+        if let Some((krate, module)) = use_path_module(resolver, krate, module, &scope, module_segments, global) {
+            candidates.push((krate, module, ident(name)));
+        }
+    };
+    if !global {
+        let import = imports.iter().find(|(path, _)| path.binding() == segments.first().map(String::as_str));
+        if let Some((path, global)) = import {
+            candidate(&[path.segments.as_slice(), &segments[1..]].concat(), *global);
+        }
+    }
+    candidate(&segments, global);
+    if segments.len() == 1 {
+        for (path, global) in imports.iter().filter(|(path, _)| path.glob) {
+            candidate(&[path.segments.as_slice(), &segments].concat(), *global);
+        }
+    }
+    Ok(candidates)
+}
+
+pub(crate) fn definition_path(found: &FoundDefinition) -> String {
+    let name = match &found.item {
+        Item::Type(item) => item.ident.to_string(),
+        Item::Struct(item) => item.ident.to_string(),
+        Item::Enum(item) => item.ident.to_string(),
+        Item::Union(item) => item.ident.to_string(),
+        Item::Trait(item) => item.ident.to_string(),
+        _ => String::new(),
+    };
+    std::iter::once(found.krate.name.clone()).chain(found.module.iter().cloned()).chain([name]).collect::<Vec<_>>().join("::")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use maplit::btreemap;
+    use quote::{quote, ToTokens};
+    use syn::PathSegment;
+    use crate::core::Resolver;
+    use crate::errors::CargoQueryError;
+    use crate::helpers::test::mock_dependencies::tests::MockDependencies;
+    use crate::r#impl::find_dependencies::CrateLocation;
+    use crate::r#impl::type_alias::{AliasResolution, resolve_type_aliases};
+    use crate::source::InMemory;
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    fn resolve(path: &str) -> Result<AliasResolution, CargoQueryError> {
+        let source_provider: InMemory = [
+            (lib_src_path(), [
+                "pub mod config;",
+                "mod model { pub struct Local<T> { value: T } pub trait Handle<T> { fn handle(&self, value: T); } }",
+                "use model::*;",
+                "pub type Direct = Local<u32>;",
+                "pub type Handler = dyn Send + model::Handle<u8> + Sync;",
+                "pub type Map = std::collections::HashMap<u8, u8>;",
+                "pub type Cycle = Loop;",
+                "pub type Loop = Cycle;",
+            ].join("\n")),
+            (src_path().join("config.rs"), "use another_crate::Settings;\npub type Config<T> = Settings<T, u8>;".to_string()),
+            (PathBuf::from("/another_crate/src/lib.rs"), "pub type Settings<A, B> = Inner<B, A>;\npub struct Inner<X, Y> { pub x: X, pub y: Y }".to_string()),
+        ].into_iter().collect();
+        let dependencies = MockDependencies(btreemap! {
+            (crate_path().to_path_buf(), "another_crate".to_string()) =>
+                CrateLocation { name: "another_crate".to_string(), path: PathBuf::from("/another_crate") },
+        });
+
+        let mut segments: Vec<PathSegment> = syn::parse_str::<syn::Path>(path).unwrap().segments.into_iter().collect();
+        let definition = segments.pop().unwrap();
+        let module = segments.iter().map(|segment| segment.ident.to_string()).collect();
+        let root = CrateLocation { name: "crate".to_string(), path: crate_path().to_path_buf() };
+        let resolver = Resolver { source_provider: &source_provider, dependencies: &dependencies };
+        resolve_type_aliases(&resolver, root, module, definition.ident, definition.arguments)
+    }
+
+    #[test]
+    fn follow_aliases_across_crates_and_substitute_arguments() {
+        let resolution = resolve("config::Config<String>").unwrap();
+
+        assert_eq!(resolution.definition.to_token_stream().to_string(), quote!(pub struct Inner { pub x: u8, pub y: String }).to_string());
+        assert_eq!(resolution.path, "another_crate::Inner");
+        assert_eq!(resolution.arguments.to_token_stream().to_string(), quote!(<u8, String>).to_string());
+        let aliases: Vec<(&str, String)> = resolution.aliases.iter()
+            .map(|step| (step.path.as_str(), step.arguments.to_token_stream().to_string()))
+            .collect();
+        assert_eq!(aliases, vec![
+            ("crate::config::Config", quote!(<String>).to_string()),
+            ("another_crate::Settings", quote!(<String, u8>).to_string()),
+        ]);
+    }
+
+    #[test]
+    fn follow_aliases_to_imported_definitions_and_trait_objects() {
+        let resolution = resolve("Direct").unwrap();
+        assert_eq!(resolution.definition.to_token_stream().to_string(), quote!(pub struct Local { value: u32 }).to_string());
+        assert_eq!(resolution.path, "crate::model::Local");
+
+        let resolution = resolve("Handler").unwrap();
+        assert_eq!(resolution.definition.to_token_stream().to_string(), quote!(pub trait Handle { fn handle(&self, value: u8); }).to_string());
+
+        let resolution = resolve("model::Local").unwrap();
+        assert!(resolution.aliases.is_empty());
+    }
+
+    #[test]
+    fn report_aliases_which_cannot_be_followed() {
+        let error = resolve("Map").unwrap_err();
+        assert!(error.to_string().contains("Could not follow the type alias `crate::Map`: the target could not be found"));
+
+        let error = resolve("Cycle").unwrap_err();
+        assert!(error.to_string().contains("the aliases refer to each other"));
+    }
+}
@@ -0,0 +1,458 @@
+//! # Verbatim Source Text
+//! Query results are `syn` items, so printing them loses formatting, regular comments and the
+//! exact spelling of literals. A [VerbatimItem] pairs an item with the text it was parsed from:
+//!
+//! ```text
+//! /// Documented                  ─┐
+//! #[derive(Debug)]                 │ text of `Example`, from its first attribute or doc
+//! pub struct Example {             │ comment to its closing brace or semicolon
+//!     // Kept, unlike in `syn`     │
+//!     value: u8,                   │
+//! }                               ─┘
+//! ```
+//!
+//! The text is recovered without span locations, so it's also available inside of procedural
+//! macros. Each file read by the query is split into items with a small lexer, every candidate
+//! slice is parsed, and the slice whose tokens equal those of the queried item is used.
+//! Comments before the first attribute of an item, e.g. license headers, aren't part of its text.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use quote::ToTokens;
+use syn::Item;
+use crate::errors::CargoQueryError;
+use crate::source::SourceProvider;
+
+/// A queried item and the exact source text it was parsed from.
+#[derive(Debug, Clone)]
+pub struct VerbatimItem {
+    pub item: Item,
+    /// Source text including attributes, doc comments, regular comments and formatting
+    pub text: String,
+    pub file_path: PathBuf,
+    /// Byte offsets of `text` within the file
+    pub byte_range: Range<usize>,
+    /// 1-based line the item starts on
+    pub line: usize,
+}
+
+/// Recovers the source text of `items` from `files`, the files read by the query which returned
+/// them. Fails for items which were changed after parsing, e.g. by substituting generics.
+///
+/// Identical items can appear in several modules, e.g. `pub struct Empty {}`. Items are then
+/// taken from the module whose path shares the longest suffix with `module_path`, the path of the
+/// queried module relative to the crate root, preferring the least nested module.
+pub fn verbatim_items<'a>(source_provider: &dyn SourceProvider, files: impl IntoIterator<Item=&'a Path>,
+                          module_path: &[String], items: Vec<Item>) -> Result<Vec<VerbatimItem>, CargoQueryError> {
+    let mut candidates: BTreeMap<String, Vec<Candidate>> = BTreeMap::new();
+    let mut sources: BTreeMap<&Path, String> = BTreeMap::new();
+    for path in files {
+        if path.extension().is_none_or(|extension| extension != "rs") {
+            continue
+        }
+        let source = match source_provider.read_source(path) {
+            Ok(source) => source,
+            Err(_) => continue,
+        };
+        let file = match syn::parse_file(&source) {
+            Ok(file) => file,
+            Err(_) => continue,
+        };
+        let mut found = vec![];
+        scan_items(&source, content_start(&source), &file.items, &mut file_module(path), &mut found);
+        for (tokens, module, range) in found {
+            candidates.entry(tokens).or_default().push(Candidate { file_path: path, module, range });
+        }
+        sources.insert(path, source);
+    }
+
+    items.into_iter().map(|item| {
+        let candidate = candidates.get(&item.to_token_stream().to_string())
+            .and_then(|candidates| candidates.iter().rev().max_by_key(|candidate| {
+                (common_suffix(&candidate.module, module_path), std::cmp::Reverse(candidate.module.len()))
+            }));
+        let Candidate { file_path, range, .. } = match candidate {
+            Some(candidate) => candidate,
+            None => return Err(CargoQueryError::could_not_recover_source_text(&item)),
+        };
+        let source = &sources[file_path];
+        Ok(VerbatimItem {
+            text: source[range.clone()].to_string(),
+            file_path: file_path.to_path_buf(),
+            line: source[..range.start].matches('\n').count() + 1,
+            byte_range: range.clone(),
+            item,
+        })
+    }).collect()
+}
+
+/// An item found in a file.
+struct Candidate<'a> {
+    file_path: &'a Path,
+    /// Approximate path of the enclosing module: the module name implied by the file name,
+    /// followed by the enclosing inline modules
+    module: Vec<String>,
+    range: Range<usize>,
+}
+
+/// `src/foo/bar.rs` => `["bar"]`, `src/foo/mod.rs` => `["foo"]` and `src/lib.rs` => `[]`.
+fn file_module(path: &Path) -> Vec<String> {
+    let name = |path: &Path| path.file_stem().map(|stem| stem.to_string_lossy().to_string());
+    match name(path).as_deref() {
+        Some("lib" | "main") | None => vec![],
+        Some("mod") => path.parent().and_then(name).into_iter().collect(),
+        Some(stem) => vec![stem.to_string()],
+    }
+}
+
+fn common_suffix(a: &[String], b: &[String]) -> usize {
+    a.iter().rev().zip(b.iter().rev()).take_while(|(a, b)| a == b).count()
+}
+
+/// Finds the text of each of `items`, which were parsed from `source` starting at `position`,
+/// and of the items of inline modules. Stops at the first item which can't be found.
+fn scan_items(source: &str, mut position: usize, items: &[Item], module: &mut Vec<String>,
+              found: &mut Vec<(String, Vec<String>, Range<usize>)>) {
+    for item in items {
+        let start = skip_trivia(source, position);
+        let expected = item.to_token_stream().to_string();
+        let end = ItemEnds::new(source, start).find(|&end| {
+            syn::parse_str::<Item>(&source[start..end]).is_ok_and(|parsed| parsed.to_token_stream().to_string() == expected)
+        });
+        let end = match end {
+            Some(end) => end,
+            None => return,
+        };
+        if let Item::Mod(syn::ItemMod { ident, content: Some((_, content)), .. }) = item {
+            if let Some(body) = last_top_level_brace(source, start, end) {
+                module.push(ident.to_string());
+                scan_items(source, body + 1, content, module, found);
+                module.pop();
+            }
+        }
+        found.push((expected, module.clone(), start..end));
+        position = end;
+    }
+}
+
+/// Skips a byte order mark and a shebang line, both of which are ignored by `syn::parse_file`.
+fn content_start(source: &str) -> usize {
+    let start = if source.starts_with('\u{feff}') { '\u{feff}'.len_utf8() } else { 0 };
+    let rest = &source[start..];
+    if rest.starts_with("#!") && !rest[2..].trim_start().starts_with('[') {
+        return start + rest.find('\n').unwrap_or(rest.len())
+    }
+    start
+}
+
+/// Skips whitespace, regular comments and inner attributes, stopping at the first outer
+/// attribute, doc comment or token of the next item.
+fn skip_trivia(source: &str, mut position: usize) -> usize {
+    loop {
+        let rest = &source[position..];
+        let trimmed = rest.trim_start();
+        position += rest.len() - trimmed.len();
+
+        let is_outer_doc = (trimmed.starts_with("///") && !trimmed.starts_with("////"))
+            || (trimmed.starts_with("/**") && !trimmed.starts_with("/***") && !trimmed.starts_with("/**/"));
+        if is_outer_doc {
+            return position
+        }
+        if trimmed.starts_with("//") {
+            position += trimmed.find('\n').unwrap_or(trimmed.len());
+        } else if trimmed.starts_with("/*") {
+            position = block_comment_end(source, position);
+        } else if trimmed.starts_with("#!") && trimmed[2..].trim_start().starts_with('[') {
+            let mut lexer = Lexer { source, position: position + 2 };
+            let mut depth = 0;
+            while let Some(token) = lexer.next() {
+                match token {
+                    Token::Open => depth += 1,
+                    Token::Close => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    break
+                }
+            }
+            position = lexer.position;
+        } else {
+            return position
+        }
+    }
+}
+
+/// Returns the position after the (possibly nested) block comment starting at `position`.
+fn block_comment_end(source: &str, mut position: usize) -> usize {
+    let bytes = source.as_bytes();
+    let mut depth = 0;
+    while position < bytes.len() {
+        if bytes[position..].starts_with(b"/*") {
+            depth += 1;
+            position += 2;
+        } else if bytes[position..].starts_with(b"*/") {
+            depth -= 1;
+            position += 2;
+            if depth == 0 {
+                break
+            }
+        } else {
+            position += 1;
+        }
+    }
+    position
+}
+
+/// Position of the last `{` between `start` and `end` which isn't nested in another delimiter,
+/// i.e. the opening brace of an item's body.
+fn last_top_level_brace(source: &str, start: usize, end: usize) -> Option<usize> {
+    let mut lexer = Lexer { source, position: start };
+    let mut depth = 0;
+    let mut brace = None;
+    while lexer.position < end {
+        let token_start = skip_whitespace_and_comments(source, lexer.position);
+        match lexer.next()? {
+            Token::Open => {
+                if depth == 0 && source[token_start..].starts_with('{') {
+                    brace = Some(token_start);
+                }
+                depth += 1;
+            },
+            Token::Close => depth -= 1,
+            _ => {}
+        }
+    }
+    brace
+}
+
+fn skip_whitespace_and_comments(source: &str, mut position: usize) -> usize {
+    loop {
+        let rest = &source[position..];
+        let trimmed = rest.trim_start();
+        position += rest.len() - trimmed.len();
+        if trimmed.starts_with("//") {
+            position += trimmed.find('\n').unwrap_or(trimmed.len());
+        } else if trimmed.starts_with("/*") {
+            position = block_comment_end(source, position);
+        } else {
+            return position
+        }
+    }
+}
+
+/// Positions where an item starting at `position` may end: after every `;` and `}` which isn't
+/// nested in a delimiter. Ends at the closing brace of the enclosing module.
+struct ItemEnds<'a> {
+    lexer: Lexer<'a>,
+    depth: usize,
+}
+
+impl<'a> ItemEnds<'a> {
+    fn new(source: &'a str, position: usize) -> Self {
+        ItemEnds { lexer: Lexer { source, position }, depth: 0 }
+    }
+}
+
+impl Iterator for ItemEnds<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            match self.lexer.next()? {
+                Token::Open => self.depth += 1,
+                Token::Close => {
+                    self.depth = self.depth.checked_sub(1)?;
+                    if self.depth == 0 {
+                        return Some(self.lexer.position)
+                    }
+                },
+                Token::Semicolon if self.depth == 0 => return Some(self.lexer.position),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Semicolon,
+    Other,
+}
+
+/// Splits Rust source into just enough tokens to find delimiters: comments, string, character
+/// and byte literals are skipped as a whole, so that delimiters inside of them are ignored.
+struct Lexer<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl Lexer<'_> {
+    fn next(&mut self) -> Option<Token> {
+        self.position = skip_whitespace_and_comments(self.source, self.position);
+        let rest = &self.source[self.position..];
+        let first = rest.chars().next()?;
+        let (token, length) = match first {
+            '(' | '[' | '{' => (Token::Open, 1),
+            ')' | ']' | '}' => (Token::Close, 1),
+            ';' => (Token::Semicolon, 1),
+            '"' => (Token::Other, quoted_length(rest, '"')),
+            '\'' => (Token::Other, quote_or_lifetime_length(rest)),
+            c if c == '_' || c.is_alphanumeric() => (Token::Other, identifier_or_literal_length(rest)),
+            c => (Token::Other, c.len_utf8()),
+        };
+        self.position += length;
+        Some(token)
+    }
+}
+
+/// Length of a literal delimited by `delimiter`, e.g. `"a \" b"`, including its suffix.
+fn quoted_length(rest: &str, delimiter: char) -> usize {
+    let mut escaped = false;
+    for (index, c) in rest.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == delimiter => return index + c.len_utf8() + word_length(&rest[index + c.len_utf8()..]),
+            _ => {}
+        }
+    }
+    rest.len()
+}
+
+/// Length of a character literal, e.g. `'}'` or `'\''`, or of a lifetime, e.g. `'a`.
+fn quote_or_lifetime_length(rest: &str) -> usize {
+    let mut chars = rest.char_indices().skip(1);
+    match (chars.next(), chars.next()) {
+        (Some((_, '\\')), _) => quoted_length(rest, '\''),
+        (Some((_, c)), Some((index, '\''))) if c != '\'' => index + 1,
+        _ => 1 + word_length(&rest[1..]),
+    }
+}
+
+/// Length of an identifier, number, raw identifier or prefixed literal, e.g. `b"}"` or
+/// `r#"}"#`.
+fn identifier_or_literal_length(rest: &str) -> usize {
+    let word = word_length(rest);
+    let after = &rest[word..];
+    match &rest[..word] {
+        "b" | "c" if after.starts_with('"') => word + quoted_length(after, '"'),
+        "b" if after.starts_with('\'') => word + quoted_length(after, '\''),
+        "r" | "br" | "cr" if after.starts_with('"') || after.starts_with("#\"") || after.starts_with("##") => {
+            let hashes = after.len() - after.trim_start_matches('#').len();
+            let terminator = format!("\"{}", "#".repeat(hashes));
+            match after[hashes + 1..].find(&terminator) {
+                Some(index) => word + hashes + 1 + index + terminator.len(),
+                None => rest.len(),
+            }
+        },
+        "r" if after.starts_with('#') => word + 1 + word_length(&after[1..]),
+        _ => word,
+    }
+}
+
+fn word_length(rest: &str) -> usize {
+    rest.find(|c: char| c != '_' && !c.is_alphanumeric()).unwrap_or(rest.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use quote::quote;
+    use syn::Item;
+    use crate::errors::ErrorKind;
+    use crate::r#impl::verbatim::verbatim_items;
+    use crate::source::InMemory;
+
+    pub fn crate_path<'a>() -> &'a Path { Path::new("/example") }
+    pub fn src_path() -> PathBuf { crate_path().join("src") }
+    pub fn lib_src_path() -> PathBuf { src_path().join("lib.rs") }
+
+    const LIB_RS: &str = r##"#![allow(dead_code)]
+//! Crate documentation
+
+// License header, which isn't part of the next item
+use std::fmt;
+
+/// Documented
+#[derive(Debug)]
+pub struct Example<'a> {
+    // Regular comment
+    name:   &'a str,   /* odd spacing */
+    brace: char, // '}'
+}
+
+const RAW: &str = r#"} ; {"#;
+const INSTANCE: Example<'static> = Example { name: "}", brace: '}' };
+
+mod inner {
+    #![allow(unused)]
+
+    pub fn hex() -> u32 { 0xFF_u32 }
+
+    pub struct Empty {}
+}
+
+pub struct Empty {}
+"##;
+
+    fn verbatim(item: proc_macro2::TokenStream) -> Result<(String, usize), ErrorKind> {
+        verbatim_in_module(&[], item)
+    }
+
+    fn verbatim_in_module(module_path: &[&str], item: proc_macro2::TokenStream) -> Result<(String, usize), ErrorKind> {
+        let source_provider: InMemory = [(lib_src_path(), LIB_RS)].into_iter().collect();
+        let item: Item = syn::parse2(item).unwrap();
+        let lib_src_path = lib_src_path();
+        let module_path: Vec<String> = module_path.iter().map(|segment| segment.to_string()).collect();
+        verbatim_items(&source_provider, [lib_src_path.as_path()], &module_path, vec![item])
+            .map(|mut items| items.pop().map(|item| (item.text, item.line)).unwrap())
+            .map_err(|e| e.kind())
+    }
+
+    #[test]
+    fn recover_text_with_comments_and_formatting() {
+        let (text, line) = verbatim(quote! {
+            #[doc = " Documented"]
+            #[derive(Debug)]
+            pub struct Example<'a> {
+                name: &'a str,
+                brace: char,
+            }
+        }).unwrap();
+
+        assert_eq!(line, 7);
+        assert_eq!(text, [
+            "/// Documented",
+            "#[derive(Debug)]",
+            "pub struct Example<'a> {",
+            "    // Regular comment",
+            "    name:   &'a str,   /* odd spacing */",
+            "    brace: char, // '}'",
+            "}",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn delimiters_inside_of_literals_are_ignored() {
+        assert_eq!(verbatim(quote!(const RAW: &str = r#"} ; {"#;)).unwrap().0, r###"const RAW: &str = r#"} ; {"#;"###);
+        assert_eq!(
+            verbatim(quote!(const INSTANCE: Example<'static> = Example { name: "}", brace: '}' };)).unwrap().0,
+            r#"const INSTANCE: Example<'static> = Example { name: "}", brace: '}' };"#
+        );
+    }
+
+    #[test]
+    fn recover_text_of_items_in_inline_modules() {
+        assert_eq!(verbatim(quote!(pub fn hex() -> u32 { 0xFF_u32 })).unwrap(), ("pub fn hex() -> u32 { 0xFF_u32 }".to_string(), 21));
+        assert_eq!(verbatim(quote!(pub fn missing() {})), Err(ErrorKind::ParseFailure));
+    }
+
+    #[test]
+    fn identical_items_are_taken_from_the_queried_module() {
+        assert_eq!(verbatim_in_module(&[], quote!(pub struct Empty {})).unwrap().1, 26);
+        assert_eq!(verbatim_in_module(&["inner"], quote!(pub struct Empty {})).unwrap().1, 23);
+    }
+}
@@ -1,6 +1,7 @@
 #![feature(proc_macro_diagnostic)]
 #![feature(exact_size_is_empty)]
 #![feature(iter_advance_by)]
+#![cfg_attr(feature = "tracked-path", feature(proc_macro_tracked_path))]
 //!
 //!
 //!
@@ -8,45 +9,50 @@
 //!
 
 
+#[cfg(feature = "tracked-path")]
+extern crate proc_macro;
 use quote::quote;
 use quote::spanned::Spanned;
-use syn::{Ident, Item, parse2, Path};
+use syn::{Item, parse2};
 pub use crate::core::{Crate, Query, QueryType};
-use crate::core::query_cargo_src_core;
-use crate::errors::CargoQueryError;
-use crate::flags::{Flags};
-use proc_macro2::Span;
-use proc_macro2::TokenStream;
+pub use crate::context::{MetadataSource, QueryContext, QueryContextBuilder};
+pub use crate::r#impl::associated::{AssociatedItem, Member};
+pub use crate::r#impl::attribute_search::{AttributeMatch, AttributePattern};
+pub use crate::r#impl::find_dependencies::{CrateLocation, Definition, DependencyLookup};
+pub use crate::r#impl::index::{CrateIndex, IndexedModule, IndexFailure, ItemVisibility, ModuleKind};
+pub use crate::r#impl::invalidation::{DependencyGraph, Invalidation, Invalidator};
+pub use crate::r#impl::locate::{EnclosingItem, ReverseLookup, SourceLocation};
+pub use crate::r#impl::module_graph::{GraphFormat, GraphOptions};
+pub use crate::r#impl::path_pattern::{PathMatch, PathPattern};
+pub use crate::r#impl::public_api::{ApiChange, ApiDiff, ApiItem, ChangeKind, Severity};
+pub use crate::r#impl::references::{Reference, ReferenceScope, Usage};
+pub use crate::r#impl::trace::{Candidate, CandidateKind, Rejection, ResolutionStep, ResolutionTrace};
+pub use crate::r#impl::type_alias::{AliasResolution, AliasStep};
+pub use crate::r#impl::verbatim::VerbatimItem;
 
 pub mod flags;
 mod core;
+pub mod backend;
+pub mod context;
+#[cfg(unix)]
+pub mod daemon;
 pub mod errors;
 pub mod helpers;
+pub mod logger;
+pub mod reproducer;
+pub mod source;
+pub mod tracking;
 mod macros;
 mod bench;
 mod r#impl;
-mod logger;
 
 // TODO: Use https://github.com/bheisler/criterion.rs for benchmarking
-// TODO: Set up logger. Loggers can be defined per scope (so we can have a library specific logger according to ChatGPT)
-// Logger Info: https://stackoverflow.com/questions/61810740/log-source-file-and-line-numbers
 // TODO: Set up tracker which generates a Github issue each time a Rust Reference file is updated to review specification updates
 
 // TODO: Feature 1: () Dependency list for each queried item (e.g. struct, enum, function, etc.)
 // TODO: Feature 2: (Comprehensive) Expand files with macros using cargo-expand (before parsing) - Macros are very freaky and can affect the AST fundamentally. Meaning the only AST output we can trust is the one from cargo-expand.
 // TODO: Cache files/queries to avoid re-parsing the same file/request multiple times
 
-// TODO: Use rust-analyzer instead of implementing everything ourselves: ex. (https://rust-analyzer.github.io/manual.html#go-to-definition) - use this to find definitions
-
-pub fn get_module(flags: Flags, crate_name: Crate, module_path: Path) -> Result<Vec<Item>, CargoQueryError> {
-    query_cargo_src_core(flags, Query {
-        crate_name,
-        module_path,
-        query_type: QueryType::Mod,
-    })
-}
-
-pub fn get_definition(flags: Flags, crate_name: Crate, module_path: Path, definition_name: Ident) {}
 
 fn boop() {
     let token_stream: proc_macro2::TokenStream = quote! {
@@ -1,5 +1,184 @@
-use log::{info, LevelFilter};
-use std::io::Write;
+//! # Logging
+//! `cargo_src_query` usually runs inside of a procedural macro, where anything written to stderr
+//! is mixed into the compiler's output. Log records are therefore written to a file, one per
+//! build, when [LOG_ENV_VAR] is set to a level:
+//!
+//! ```text
+//! CARGO_SRC_QUERY_LOG=debug cargo build
+//!
+//! target/cargo_src_query/logs/
+//! ├── 20240101T120000.000Z-4242.log
+//! └── 20240101T120500.000Z-4318.log   [src/impl/find_mod.rs:212] DEBUG - ...
+//! ```
+//!
+//! Only the newest [MAX_LOG_FILES] logs are kept. Records are mirrored to stderr when
+//! [LOG_STDERR_ENV_VAR] is set, and are never written to stderr otherwise.
+//!
+//! The logger is installed by the first [QueryContext](crate::context::QueryContext) which is
+//! built while [LOG_ENV_VAR] is set, unless another logger has already been installed by the
+//! application. Nothing is installed and the global max level is left alone otherwise, so that
+//! applications can install their own logger later.
+
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// The level to log at, e.g. `debug`. Logging is disabled when unset.
+pub const LOG_ENV_VAR: &str = "CARGO_SRC_QUERY_LOG";
+/// Mirrors log records to stderr when set to anything other than an empty string or `0`.
+pub const LOG_STDERR_ENV_VAR: &str = "CARGO_SRC_QUERY_LOG_STDERR";
+pub const MAX_LOG_FILES: usize = 10;
+
+/// Returns the level set by [LOG_ENV_VAR], or [LevelFilter::Off] when it is unset or invalid.
+pub fn level() -> LevelFilter {
+    std::env::var(LOG_ENV_VAR).ok()
+        .and_then(|level| LevelFilter::from_str(level.trim()).ok())
+        .unwrap_or(LevelFilter::Off)
+}
+
+fn stderr_enabled() -> bool {
+    matches!(std::env::var(LOG_STDERR_ENV_VAR).as_deref(), Ok(value) if !value.is_empty() && value != "0")
+}
+
+/// Returns the target directory of the workspace containing `manifest_dir` without running
+/// `cargo metadata`: `$CARGO_TARGET_DIR`, or `target` next to the workspace's `Cargo.lock`.
+pub fn target_directory(manifest_dir: &Path) -> PathBuf {
+    if let Some(target_directory) = std::env::var_os("CARGO_TARGET_DIR") {
+        return PathBuf::from(target_directory)
+    }
+    manifest_dir.ancestors()
+        .find(|directory| directory.join("Cargo.lock").is_file())
+        .unwrap_or(manifest_dir)
+        .join("target")
+}
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Writes records at or above `level` to the log file and, if asked, stderr. Every record on a
+/// thread inside of [capture] is collected, regardless of `level`.
+struct QueryLogger {
+    level: LevelFilter,
+    file: Option<Mutex<LineWriter<File>>>,
+    stderr: bool,
+}
+
+impl QueryLogger {
+    fn capturing() -> bool {
+        CAPTURED.with(|captured| captured.borrow().is_some())
+    }
+}
+
+impl Log for QueryLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level || QueryLogger::capturing()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return
+        }
+        let line = format!("[{}:{}] {} - {}",
+            record.file().unwrap_or("unknown"), record.line().unwrap_or(0), record.level(), record.args());
+
+        CAPTURED.with(|captured| if let Some(captured) = captured.borrow_mut().as_mut() {
+            captured.push(line.clone());
+        });
+        if record.level() > self.level {
+            return
+        }
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = writeln!(file, "{} {line}", chrono::Utc::now().format("%H:%M:%S%.3f"));
+            }
+        }
+        if self.stderr {
+            eprintln!("cargo_src_query: {line}");
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(Ok(mut file)) = self.file.as_ref().map(Mutex::lock) {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Whether the [QueryLogger] is installed, and the file it writes to.
+static INSTALLED: OnceLock<Option<Option<PathBuf>>> = OnceLock::new();
+
+/// Installs the logger and creates a log file under `target_directory` when [LOG_ENV_VAR] is
+/// set. Only the first call has an effect. Returns the log file of this build, if there is one.
+pub fn init(target_directory: &Path) -> Option<&'static Path> {
+    INSTALLED.get_or_init(|| install(target_directory)).as_ref()?.as_deref()
+}
+
+fn install(target_directory: &Path) -> Option<Option<PathBuf>> {
+    let level = level();
+    if level == LevelFilter::Off {
+        return None
+    }
+    let stderr = stderr_enabled();
+    let (file, path) = match create_log_file(&target_directory.join("cargo_src_query").join("logs")) {
+        Ok((file, path)) => (Some(Mutex::new(LineWriter::new(file))), Some(path)),
+        Err(e) => {
+            if stderr {
+                eprintln!("cargo_src_query: could not create a log file: {e}");
+            }
+            (None, None)
+        }
+    };
+
+    log::set_boxed_logger(Box::new(QueryLogger { level, file, stderr })).ok()?;
+    // Records are always enabled, so that they can be captured for reproducers
+    log::set_max_level(LevelFilter::Trace);
+    Some(path)
+}
+
+/// Creates the log file of this build, removing old logs so that at most [MAX_LOG_FILES] remain.
+fn create_log_file(directory: &Path) -> io::Result<(File, PathBuf)> {
+    fs::create_dir_all(directory)?;
+    rotate(directory, MAX_LOG_FILES - 1)?;
+    let path = directory.join(format!(
+        "{}-{}.log", chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"), std::process::id()
+    ));
+    Ok((File::create(&path)?, path))
+}
+
+/// Removes the oldest `.log` files in `directory` until at most `keep` remain. Logs are named by
+/// their creation time, so they sort oldest first.
+fn rotate(directory: &Path, keep: usize) -> io::Result<()> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "log"))
+        .collect();
+    logs.sort();
+    let excess = logs.len().saturating_sub(keep);
+    for log in logs.into_iter().take(excess) {
+        fs::remove_file(log)?;
+    }
+    Ok(())
+}
+
+/// Runs `f`, collecting the log records it emits on the current thread. Records can only be
+/// collected when the logger from [init] is installed.
+pub(crate) fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    if !matches!(INSTALLED.get(), Some(Some(_))) {
+        return (f(), vec![format!(
+            "Log records were not captured, since `{LOG_ENV_VAR}` is unset or another logger is installed."
+        )])
+    }
+
+    CAPTURED.with(|captured| *captured.borrow_mut() = Some(vec![]));
+    let result = f();
+    let records = CAPTURED.with(|captured| captured.borrow_mut().take()).unwrap_or_default();
+    (result, records)
+}
 
 #[cfg(test)]
 pub fn setup_logger() {
@@ -17,5 +196,44 @@ pub fn setup_logger() {
         // .filter(Some("logger_example"), LevelFilter::Debug)
         .filter_level(LevelFilter::Debug)
         .target(env_logger::Target::Stdout)
-        .init();
-}
\ No newline at end of file
+        .try_init()
+        .ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::logger::{rotate, target_directory};
+
+    #[test]
+    fn rotation_removes_oldest_logs() {
+        let directory = std::env::temp_dir().join(format!("cargo_src_query_logs_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for name in ["20240101T000000.000Z-1.log", "20240102T000000.000Z-2.log", "20240103T000000.000Z-3.log", "notes.txt"] {
+            fs::write(directory.join(name), "").unwrap();
+        }
+
+        rotate(&directory, 1).unwrap();
+        let mut remaining: Vec<String> = fs::read_dir(&directory).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        remaining.sort();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(remaining, vec!["20240103T000000.000Z-3.log", "notes.txt"]);
+    }
+
+    #[test]
+    fn target_directory_is_next_to_cargo_lock() {
+        if std::env::var_os("CARGO_TARGET_DIR").is_some() { return }
+        let workspace = std::env::temp_dir().join(format!("cargo_src_query_workspace_{}", std::process::id()));
+        let member = workspace.join("member");
+        fs::create_dir_all(&member).unwrap();
+        fs::write(workspace.join("Cargo.lock"), "").unwrap();
+
+        let target = target_directory(&member);
+        fs::remove_dir_all(&workspace).unwrap();
+
+        assert_eq!(target, workspace.join("target"));
+    }
+}
@@ -0,0 +1,375 @@
+//! # Reproducers
+//! Query failures are often specific to the queried crate, which makes them hard to report. When
+//! [REPRODUCER_ENV_VAR] is set, every failed query writes a self-contained reproducer into the
+//! target directory of the queried workspace:
+//!
+//! ```text
+//! target/cargo_src_query/reproducers/20240101T120000.000Z-CSQ0001/
+//! ├── README.md      how to replay the failure
+//! ├── query.json     the query, flags, features, target and the error
+//! ├── metadata.json  `cargo metadata`, with local paths redacted
+//! ├── trace.log      log records emitted while the query ran, when logging is enabled
+//! └── sources/       every source file the query read
+//!     ├── workspace/    files under the workspace root
+//!     ├── cargo_home/   files under `$CARGO_HOME`, e.g. registry dependencies
+//!     ├── home/         other files under the home directory
+//!     └── root/         everything else
+//! ```
+//!
+//! Local paths are replaced with placeholders such as `$WORKSPACE`, so a bundle doesn't leak the
+//! layout of the machine it was created on. The failure is replayed offline, without the
+//! original workspace, with:
+//!
+//! ```text
+//! cargo_src_query --replay <bundle>
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use cargo_metadata::{CargoOpt, Metadata};
+use proc_macro2::Span;
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use syn::{Ident, Item};
+use crate::context::{MetadataSource, QueryContext};
+use crate::core::{Crate, Query, QueryType};
+use crate::errors::{CargoQueryError, ErrorKind};
+use crate::flags::Flags;
+use crate::r#impl::find_dependencies::Definition;
+use crate::source::{FileSystem, SourceProvider};
+
+/// Enables reproducers when set to anything other than an empty string or `0`.
+pub const REPRODUCER_ENV_VAR: &str = "CARGO_SRC_QUERY_REPRODUCER";
+
+const QUERY_FILE: &str = "query.json";
+const METADATA_FILE: &str = "metadata.json";
+const TRACE_FILE: &str = "trace.log";
+const SOURCES_DIRECTORY: &str = "sources";
+
+pub fn enabled() -> bool {
+    matches!(std::env::var(REPRODUCER_ENV_VAR).as_deref(), Ok(value) if !value.is_empty() && value != "0")
+}
+
+/// A query, recorded before it is resolved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedQuery {
+    pub manifest_path: PathBuf,
+    pub crate_name: Option<String>,
+    pub module_path: String,
+    pub definition: Option<(String, Definition)>,
+    pub flags: u32,
+    pub features: Vec<String>,
+    pub target: Option<String>,
+}
+
+impl RecordedQuery {
+    pub(crate) fn new(context: &QueryContext, flags: Flags, query: &Query) -> Self {
+        RecordedQuery {
+            manifest_path: context.manifest_path().to_path_buf(),
+            crate_name: match query.crate_name() {
+                Crate::Internal => None,
+                Crate::External(name) => Some(name.to_string()),
+            },
+            module_path: query.module_path().to_token_stream().to_string(),
+            definition: match query.query_type() {
+                QueryType::Definition(name, definition_type) => Some((name.to_string(), *definition_type)),
+                QueryType::Mod => None,
+            },
+            flags: flags.bits(),
+            features: context.features().iter().map(feature_to_string).collect(),
+            target: context.target().map(str::to_string),
+        }
+    }
+
+    fn into_query(self) -> Result<(Flags, Query), CargoQueryError> {
+        let module_path = if self.module_path.trim().is_empty() {
+            syn::Path { leading_colon: None, segments: Default::default() }
+        } else {
+            syn::parse_str(&self.module_path).map_err(|e| CargoQueryError::could_not_parse_item(&self.module_path, e))?
+        };
+        let query_type = match self.definition {
+            Some((name, definition_type)) => QueryType::Definition(Ident::new(&name, Span::call_site()), definition_type),
+            None => QueryType::Mod,
+        };
+        Ok((Flags::from_bits_truncate(self.flags), Query {
+            crate_name: match self.crate_name {
+                None => Crate::Internal,
+                Some(name) => Crate::External(Ident::new(&name, Span::call_site())),
+            },
+            module_path,
+            query_type,
+        }))
+    }
+}
+
+/// The error a recorded query failed with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedError {
+    pub kind: ErrorKind,
+    pub top_level_message: String,
+    pub indexed_messages: BTreeMap<String, Vec<String>>,
+    pub path_segment: Option<usize>,
+}
+
+impl From<&CargoQueryError> for RecordedError {
+    fn from(error: &CargoQueryError) -> Self {
+        RecordedError {
+            kind: error.kind(),
+            top_level_message: error.top_level_message().to_string(),
+            indexed_messages: error.indexed_messages().clone(),
+            path_segment: error.path_segment(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct QueryFile {
+    query: RecordedQuery,
+    error: RecordedError,
+}
+
+fn feature_to_string(features: &CargoOpt) -> String {
+    match features {
+        CargoOpt::AllFeatures => "--all-features".to_string(),
+        CargoOpt::NoDefaultFeatures => "--no-default-features".to_string(),
+        CargoOpt::SomeFeatures(features) => format!("--features={}", features.join(",")),
+    }
+}
+
+fn feature_from_string(features: &str) -> CargoOpt {
+    match features {
+        "--all-features" => CargoOpt::AllFeatures,
+        "--no-default-features" => CargoOpt::NoDefaultFeatures,
+        _ => CargoOpt::SomeFeatures(features.trim_start_matches("--features=")
+            .split(',')
+            .filter(|f| !f.is_empty())
+            .map(str::to_string)
+            .collect()),
+    }
+}
+
+/// Local roots replaced by placeholders, ordered from most to least specific.
+struct Redactions {
+    roots: Vec<(PathBuf, &'static str)>,
+}
+
+impl Redactions {
+    fn new(workspace_root: &Path) -> Self {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let cargo_home = std::env::var_os("CARGO_HOME").map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".cargo")));
+
+        let mut roots = vec![(workspace_root.to_path_buf(), "workspace")];
+        roots.extend(cargo_home.map(|root| (root, "cargo_home")));
+        roots.extend(home.map(|root| (root, "home")));
+        Self::from_roots(roots)
+    }
+
+    /// Roots are normalized, e.g. `/root/` to `/root`, and the filesystem root is skipped, since
+    /// every path would be redacted by it.
+    fn from_roots(roots: Vec<(PathBuf, &'static str)>) -> Self {
+        let mut roots: Vec<(PathBuf, &'static str)> = roots.into_iter()
+            .map(|(root, name)| (root.components().collect::<PathBuf>(), name))
+            .filter(|(root, _)| root.parent().is_some())
+            .collect();
+        roots.sort_by_key(|(root, _)| std::cmp::Reverse(root.as_os_str().len()));
+        Redactions { roots }
+    }
+
+    fn placeholder(name: &str) -> String {
+        format!("${}", name.to_uppercase())
+    }
+
+    /// `/home/user/project/src/lib.rs` => `$WORKSPACE/src/lib.rs`
+    fn redact_path(&self, path: &Path) -> PathBuf {
+        for (root, name) in &self.roots {
+            if let Ok(relative) = path.strip_prefix(root) {
+                return Path::new(&Self::placeholder(name)).join(relative)
+            }
+        }
+        Path::new(&Self::placeholder("root")).join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    /// Redacts every occurrence of a root which is a whole path prefix, so that `/root` isn't
+    /// redacted within `/rootfs` or `/srv/root`.
+    fn redact_text(&self, text: &str) -> String {
+        self.roots.iter().fold(text.to_string(), |text, (root, name)|
+            replace_path_prefix(&text, &root.to_string_lossy(), &Self::placeholder(name)))
+    }
+}
+
+fn replace_path_prefix(text: &str, prefix: &str, replacement: &str) -> String {
+    let is_path_character = |c: char| c.is_alphanumeric() || "_-.~/\\".contains(c);
+    let mut replaced = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (start, _) in text.match_indices(prefix) {
+        let end = start + prefix.len();
+        let starts_path = text[..start].chars().next_back().is_none_or(|c| !is_path_character(c));
+        let ends_component = text[end..].chars().next().is_none_or(|c| c == '/' || c == '\\' || !is_path_character(c));
+        if start >= copied && starts_path && ends_component {
+            replaced.push_str(&text[copied..start]);
+            replaced.push_str(replacement);
+            copied = end;
+        }
+    }
+    replaced.push_str(&text[copied..]);
+    replaced
+}
+
+/// Replaces placeholders with the source directories of a bundle. Paths outside of the redacted
+/// roots are left as they are, and are served from `sources/root` by [BundleSources].
+fn unredact_text(bundle: &Path, text: &str) -> String {
+    ["workspace", "cargo_home", "home"].into_iter().fold(text.to_string(), |text, name| {
+        let directory = bundle.join(SOURCES_DIRECTORY).join(name);
+        text.replace(&Redactions::placeholder(name), &directory.to_string_lossy())
+    })
+}
+
+/// Serves sources from a bundle. Absolute paths outside of the bundle are looked up in
+/// `sources/root`.
+struct BundleSources {
+    bundle: PathBuf,
+}
+
+impl SourceProvider for BundleSources {
+    fn read_source(&self, path: &Path) -> anyhow::Result<String> {
+        if path.starts_with(&self.bundle) {
+            return FileSystem.read_source(path)
+        }
+        FileSystem.read_source(&self.bundle.join(SOURCES_DIRECTORY).join("root").join(path.strip_prefix("/").unwrap_or(path)))
+    }
+}
+
+/// Location of a redacted path within a bundle, e.g. `$WORKSPACE/src/lib.rs` =>
+/// `sources/workspace/src/lib.rs`.
+fn bundle_path(redacted: &Path) -> PathBuf {
+    let mut components = redacted.components();
+    let root = components.next()
+        .map(|c| c.as_os_str().to_string_lossy().trim_start_matches('$').to_lowercase())
+        .unwrap_or_default();
+    Path::new(SOURCES_DIRECTORY).join(root).join(components.as_path())
+}
+
+/// Writes a reproducer bundle for a failed query. Returns the bundle directory.
+pub(crate) fn write(context: &QueryContext, query: RecordedQuery, error: &CargoQueryError,
+                    sources: BTreeMap<PathBuf, String>, trace: Vec<String>) -> io::Result<PathBuf> {
+    let metadata = context.metadata().ok();
+    let workspace_root = metadata.map(|m| m.workspace_root.clone().into_std_path_buf())
+        .unwrap_or_else(|| context.manifest_path().parent().map(Path::to_path_buf).unwrap_or_default());
+    let target_directory = metadata.map(|m| m.target_directory.clone().into_std_path_buf())
+        .unwrap_or_else(|| workspace_root.join("target"));
+    let redactions = Redactions::new(&workspace_root);
+
+    let bundle = target_directory.join("cargo_src_query").join("reproducers").join(format!(
+        "{}-{}", chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"), error.kind()
+    ));
+    fs::create_dir_all(&bundle)?;
+
+    let query = RecordedQuery { manifest_path: redactions.redact_path(&query.manifest_path), ..query };
+    let query_file = QueryFile { query, error: RecordedError::from(error) };
+    fs::write(bundle.join(QUERY_FILE), redactions.redact_text(&serde_json::to_string_pretty(&query_file)?))?;
+
+    if let Some(metadata) = metadata {
+        fs::write(bundle.join(METADATA_FILE), redactions.redact_text(&serde_json::to_string_pretty(metadata)?))?;
+    }
+
+    let manifest = fs::read_to_string(context.manifest_path()).ok()
+        .map(|manifest| (context.manifest_path().to_path_buf(), manifest));
+    for (path, source) in sources.into_iter().chain(manifest) {
+        let path = bundle.join(bundle_path(&redactions.redact_path(&path)));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, source)?;
+    }
+
+    fs::write(bundle.join(TRACE_FILE), redactions.redact_text(&trace.join("\n")))?;
+    fs::write(bundle.join("README.md"), format!(
+        "# cargo_src_query reproducer\n\n\
+        The query failed with `{}`: {}\n\n\
+        Replay the failure with:\n\n```text\ncargo_src_query --replay <path to this directory>\n```\n",
+        error.kind(), error.top_level_message()
+    ))?;
+    Ok(bundle)
+}
+
+/// The result of replaying a bundle.
+pub struct Replay {
+    /// The error recorded when the bundle was written
+    pub expected: RecordedError,
+    pub actual: Result<Vec<Item>, CargoQueryError>,
+}
+
+impl Replay {
+    /// Whether the replayed query failed with the same kind of error.
+    pub fn reproduced(&self) -> bool {
+        matches!(&self.actual, Err(e) if e.kind() == self.expected.kind)
+    }
+}
+
+/// Replays the query of the bundle at `bundle` against the sources and metadata in the bundle.
+pub fn replay(bundle: &Path) -> io::Result<Replay> {
+    let bundle = bundle.canonicalize()?;
+    let query_file: QueryFile = serde_json::from_str(&unredact_text(&bundle, &fs::read_to_string(bundle.join(QUERY_FILE))?))?;
+    let metadata_source = match fs::read_to_string(bundle.join(METADATA_FILE)) {
+        Ok(metadata) => {
+            let metadata: Metadata = serde_json::from_str(&unredact_text(&bundle, &metadata))?;
+            MetadataSource::Precomputed(Box::new(metadata))
+        },
+        Err(_) => MetadataSource::Command,
+    };
+
+    let QueryFile { query, error } = query_file;
+    let mut builder = QueryContext::builder(&query.manifest_path)
+        .metadata_source(metadata_source)
+        .source_provider(Arc::new(BundleSources { bundle: bundle.clone() }))
+        .reproducer(false);
+    for features in &query.features {
+        builder = builder.features(feature_from_string(features));
+    }
+    if let Some(target) = &query.target {
+        builder = builder.target(target);
+    }
+    let context = builder.build();
+
+    let actual = query.into_query().and_then(|(flags, query)| context.query(flags, query));
+    Ok(Replay { expected: error, actual })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use crate::reproducer::{bundle_path, Redactions, unredact_text};
+
+    #[test]
+    fn redacted_paths_map_into_the_bundle() {
+        let redactions = Redactions::new(Path::new("/work/project"));
+
+        let redacted = redactions.redact_path(Path::new("/work/project/src/lib.rs"));
+        assert_eq!(redacted, Path::new("$WORKSPACE/src/lib.rs"));
+        assert_eq!(bundle_path(&redacted), Path::new("sources/workspace/src/lib.rs"));
+
+        let redacted = redactions.redact_path(Path::new("/opt/vendor/src/lib.rs"));
+        assert_eq!(redacted, Path::new("$ROOT/opt/vendor/src/lib.rs"));
+        assert_eq!(bundle_path(&redacted), Path::new("sources/root/opt/vendor/src/lib.rs"));
+
+        assert_eq!(
+            unredact_text(Path::new("/bundle"), &redactions.redact_text(r#"{"root": "/work/project", "file": "/opt/a.rs"}"#)),
+            r#"{"root": "/bundle/sources/workspace", "file": "/opt/a.rs"}"#
+        );
+    }
+
+    #[test]
+    fn only_whole_path_prefixes_are_redacted() {
+        let redactions = Redactions::from_roots(vec![(PathBuf::from("/root/"), "home"), (PathBuf::from("/"), "cargo_home")]);
+
+        assert_eq!(
+            redactions.redact_text(r#"["/root", "/root/src/lib.rs", "/rootfs/lib.rs", "/srv/root/lib.rs"]"#),
+            r#"["$HOME", "$HOME/src/lib.rs", "/rootfs/lib.rs", "/srv/root/lib.rs"]"#
+        );
+        assert_eq!(redactions.redact_path(Path::new("/rootfs/lib.rs")), Path::new("$ROOT/rootfs/lib.rs"));
+    }
+}
@@ -0,0 +1,277 @@
+//! # Source Providers
+//! Every Rust source file read by `cargo_src_query` goes through a [SourceProvider]. By default,
+//! sources are read from the filesystem, but a provider can be swapped out on the
+//! [QueryContext](crate::context::QueryContext) to serve sources from anywhere.
+//!
+//! | Provider     | Use case                                                      |
+//! |--------------|---------------------------------------------------------------|
+//! | [FileSystem] | Procedural macros and build scripts                           |
+//! | [InMemory]   | Tests and sandboxes without filesystem access                 |
+//! | [Overlay]    | Editors with unsaved buffers layered over another provider    |
+//! | [Cached]     | Long-running processes which read the same files repeatedly   |
+//! | [Recording]  | Remembering which files a query read, e.g. for reproducers    |
+//!
+//! Every [QueryContext](crate::context::QueryContext) wraps its provider in an
+//! [IncludeExpanding](crate::r#impl::include::IncludeExpanding) provider, which splices the
+//! files named by `include!` into parsed files, and a
+//! [Tracking](crate::tracking::Tracking) provider, which reports the files read by a query.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use anyhow::{Error, Result};
+
+/// Supplies the contents of Rust source files to the resolver.
+pub trait SourceProvider: Send + Sync {
+    /// Reads the source file at `path` into a `String`.
+    fn read_source(&self, path: &Path) -> Result<String>;
+
+    /// Reads the source file at `path` and converts it into a parseable `syn::File`
+    /// data structure.
+    fn parse_file(&self, path: &Path) -> Result<syn::File> {
+        Ok(syn::parse_file(&self.read_source(path)?)?)
+    }
+
+    /// Whether sources are the files on disk, so that another process reading the same paths,
+    /// e.g. the [daemon](crate::daemon), sees the same sources.
+    fn reads_file_system(&self) -> bool {
+        false
+    }
+}
+
+/// Reads sources directly from the filesystem.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileSystem;
+
+impl SourceProvider for FileSystem {
+    fn read_source(&self, path: &Path) -> Result<String> {
+        let mut file = File::open(path)?;
+        let mut src = String::new();
+        file.read_to_string(&mut src)?;
+        Ok(src)
+    }
+
+    fn reads_file_system(&self) -> bool {
+        true
+    }
+}
+
+/// Serves sources from a map of file paths to file contents.
+#[derive(Debug, Default, Clone)]
+pub struct InMemory {
+    files: BTreeMap<PathBuf, String>,
+}
+
+impl InMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces the source file at `path`.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, source: impl Into<String>) -> &mut Self {
+        self.files.insert(path.into(), source.into());
+        self
+    }
+}
+
+impl<P: Into<PathBuf>, S: Into<String>> FromIterator<(P, S)> for InMemory {
+    fn from_iter<T: IntoIterator<Item=(P, S)>>(iter: T) -> Self {
+        InMemory {
+            files: iter.into_iter().map(|(path, source)| (path.into(), source.into())).collect()
+        }
+    }
+}
+
+impl SourceProvider for InMemory {
+    fn read_source(&self, path: &Path) -> Result<String> {
+        self.files.get(path).cloned().ok_or_else(|| Error::msg(format!(
+            "Path `{}` does not exist in the in-memory map of paths. Paths: {:#?}",
+            path.to_string_lossy(), self.files.keys()
+        )))
+    }
+}
+
+/// Layers unsaved editor buffers over a base provider. Buffers take precedence over the
+/// base provider and can be updated while the overlay is shared between queries.
+pub struct Overlay {
+    buffers: RwLock<BTreeMap<PathBuf, String>>,
+    base: Arc<dyn SourceProvider>,
+}
+
+impl Overlay {
+    pub fn new(base: Arc<dyn SourceProvider>) -> Self {
+        Overlay { buffers: Default::default(), base }
+    }
+
+    /// Sets the unsaved contents of the file at `path`.
+    pub fn set_buffer(&self, path: impl Into<PathBuf>, source: impl Into<String>) {
+        self.buffers.write().expect("Overlay buffers are poisoned").insert(path.into(), source.into());
+    }
+
+    /// Discards the unsaved contents of the file at `path`, e.g. after the file is saved.
+    pub fn clear_buffer(&self, path: &Path) {
+        self.buffers.write().expect("Overlay buffers are poisoned").remove(path);
+    }
+}
+
+impl SourceProvider for Overlay {
+    fn read_source(&self, path: &Path) -> Result<String> {
+        let buffer = self.buffers.read().expect("Overlay buffers are poisoned").get(path).cloned();
+        match buffer {
+            Some(source) => Ok(source),
+            None => self.base.read_source(path)
+        }
+    }
+}
+
+/// Caches sources read from a base provider. Cached sources are served until they are
+/// invalidated.
+pub struct Cached {
+    sources: RwLock<BTreeMap<PathBuf, String>>,
+    base: Arc<dyn SourceProvider>,
+}
+
+impl Cached {
+    pub fn new(base: Arc<dyn SourceProvider>) -> Self {
+        Cached { sources: Default::default(), base }
+    }
+
+    /// Removes the file at `path` from the cache. Returns whether the file was cached.
+    pub fn invalidate(&self, path: &Path) -> bool {
+        self.sources.write().expect("Cached sources are poisoned").remove(path).is_some()
+    }
+
+    /// Removes every file from the cache.
+    pub fn clear(&self) {
+        self.sources.write().expect("Cached sources are poisoned").clear();
+    }
+
+    /// The paths of every cached file.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.sources.read().expect("Cached sources are poisoned").keys().cloned().collect()
+    }
+}
+
+impl SourceProvider for Cached {
+    fn read_source(&self, path: &Path) -> Result<String> {
+        if let Some(source) = self.sources.read().expect("Cached sources are poisoned").get(path) {
+            return Ok(source.clone())
+        }
+        let source = self.base.read_source(path)?;
+        self.sources.write().expect("Cached sources are poisoned").insert(path.to_path_buf(), source.clone());
+        Ok(source)
+    }
+
+    fn reads_file_system(&self) -> bool {
+        self.base.reads_file_system()
+    }
+}
+
+/// Records every source successfully read from a base provider while a recording is open. See
+/// [Recording::record].
+///
+/// Reads are attributed to every open recording, including reads made on other threads while
+/// indexing. Recordings which overlap therefore contain each other's sources as well.
+pub struct Recording {
+    recordings: Mutex<BTreeMap<u64, BTreeMap<PathBuf, String>>>,
+    next_recording: AtomicU64,
+    base: Arc<dyn SourceProvider>,
+}
+
+impl Recording {
+    pub fn new(base: Arc<dyn SourceProvider>) -> Self {
+        Recording { recordings: Default::default(), next_recording: AtomicU64::new(0), base }
+    }
+
+    /// Runs `f`, returning the sources read through this provider until it returns, keyed by
+    /// path.
+    pub fn record<T>(&self, f: impl FnOnce() -> T) -> (T, BTreeMap<PathBuf, String>) {
+        let id = self.next_recording.fetch_add(1, Ordering::Relaxed);
+        self.recordings.lock().expect("Recorded sources are poisoned").insert(id, BTreeMap::new());
+        // Closes the recording when dropped, so that a panicking query doesn't leave it open
+        let recording = OpenRecording { recordings: &self.recordings, id };
+        let result = f();
+        (result, recording.close())
+    }
+}
+
+impl SourceProvider for Recording {
+    fn read_source(&self, path: &Path) -> Result<String> {
+        let source = self.base.read_source(path)?;
+        for recorded in self.recordings.lock().expect("Recorded sources are poisoned").values_mut() {
+            recorded.insert(path.to_path_buf(), source.clone());
+        }
+        Ok(source)
+    }
+}
+
+struct OpenRecording<'a> {
+    recordings: &'a Mutex<BTreeMap<u64, BTreeMap<PathBuf, String>>>,
+    id: u64,
+}
+
+impl OpenRecording<'_> {
+    fn close(self) -> BTreeMap<PathBuf, String> {
+        self.recordings.lock().expect("Recorded sources are poisoned").remove(&self.id).unwrap_or_default()
+    }
+}
+
+impl Drop for OpenRecording<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut recordings) = self.recordings.lock() {
+            recordings.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+    use crate::source::{InMemory, Overlay, Recording, SourceProvider};
+
+    #[test]
+    fn in_memory_reads_inserted_files() {
+        let mut source_provider = InMemory::new();
+        source_provider.insert("/example/src/lib.rs", "pub struct Example {}");
+
+        assert_eq!(source_provider.read_source(Path::new("/example/src/lib.rs")).unwrap(), "pub struct Example {}");
+        assert!(source_provider.read_source(Path::new("/example/src/foo.rs")).is_err());
+    }
+
+    #[test]
+    fn overlay_buffers_take_precedence_over_base() {
+        let base: InMemory = [
+            ("/example/src/lib.rs", "mod foo;"),
+            ("/example/src/foo.rs", "pub struct Saved {}"),
+        ].into_iter().collect();
+        let overlay = Overlay::new(Arc::new(base));
+        let foo = Path::new("/example/src/foo.rs");
+
+        overlay.set_buffer(foo, "pub struct Unsaved {}");
+        assert_eq!(overlay.read_source(foo).unwrap(), "pub struct Unsaved {}");
+        assert_eq!(overlay.read_source(Path::new("/example/src/lib.rs")).unwrap(), "mod foo;");
+
+        overlay.clear_buffer(foo);
+        assert_eq!(overlay.read_source(foo).unwrap(), "pub struct Saved {}");
+    }
+
+    #[test]
+    fn recording_remembers_successful_reads() {
+        let base: InMemory = [("/example/src/lib.rs", "mod foo;"), ("/example/src/bar.rs", "")].into_iter().collect();
+        let recording = Recording::new(Arc::new(base));
+
+        recording.read_source(Path::new("/example/src/bar.rs")).unwrap();
+        let ((), recorded) = recording.record(|| {
+            recording.read_source(Path::new("/example/src/lib.rs")).unwrap();
+            assert!(recording.read_source(Path::new("/example/src/foo.rs")).is_err());
+        });
+        assert_eq!(recorded.keys().collect::<Vec<_>>(), vec![Path::new("/example/src/lib.rs")]);
+
+        let ((), recorded) = recording.record(|| ());
+        assert!(recorded.is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+//! # Dependency Tracking
+//! Cargo only re-expands a procedural macro when the crate invoking it changes. A macro which
+//! reads another crate's sources through `cargo_src_query` must therefore tell the compiler which
+//! files its expansion depends on, or edits to them won't be picked up until the next clean build.
+//!
+//! [QueryContext::tracked](crate::context::QueryContext::tracked) returns every file read while
+//! running a query, and the returned [TrackedFiles] can be appended to the macro's expansion:
+//!
+//! ```no_run
+//! # use proc_macro2::{Span, TokenStream};
+//! # use quote::quote;
+//! # use syn::Ident;
+//! # use cargo_src_query::{Crate, Definition, QueryContext};
+//! # use cargo_src_query::flags::DEFAULT_FLAGS;
+//! # fn expand() -> TokenStream {
+//! let context = QueryContext::from_env().unwrap();
+//! let (definition, files) = context.tracked(|context| context.get_definition(
+//!     DEFAULT_FLAGS,
+//!     Crate::External(Ident::new("dependency", Span::call_site())),
+//!     syn::parse_quote!(some::module),
+//!     Ident::new("Example", Span::call_site()),
+//!     Definition::Struct,
+//! ));
+//! let definition = definition.unwrap();
+//!
+//! quote! {
+//!     #definition
+//!     #files
+//! }
+//! # }
+//! ```
+//!
+//! On stable, [TrackedFiles] expands to an unused `include_bytes!` constant naming every file,
+//! which makes cargo record the files as inputs of the invoking crate. With the `tracked-path`
+//! feature, the files are instead registered through `proc_macro::tracked::path` and nothing is
+//! added to the expansion. The feature can only be used from within a procedural macro.
+//!
+//! Only files which were read successfully are tracked. Creating a file that a query looked for
+//! but didn't find, e.g. `foo.rs` next to an existing `foo/mod.rs`, won't trigger a re-expansion.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use anyhow::Result;
+use proc_macro2::{Literal, TokenStream};
+use quote::{quote, ToTokens};
+use crate::source::SourceProvider;
+
+/// Records the files read from a base provider while at least one tracking session is open.
+/// Every [QueryContext](crate::context::QueryContext) reads its sources through a [Tracking]
+/// provider.
+///
+/// Reads are attributed to every open session, including reads made on other threads while
+/// indexing. Sessions which overlap therefore track each other's files, which can only cause
+/// additional re-expansions.
+pub struct Tracking {
+    sessions: Mutex<BTreeMap<u64, BTreeSet<PathBuf>>>,
+    next_session: AtomicU64,
+    base: Arc<dyn SourceProvider>,
+}
+
+impl Tracking {
+    pub fn new(base: Arc<dyn SourceProvider>) -> Self {
+        Tracking { sessions: Default::default(), next_session: AtomicU64::new(0), base }
+    }
+
+    /// Runs `f`, returning the files read through this provider until it returns.
+    pub fn track<T>(&self, f: impl FnOnce() -> T) -> (T, TrackedFiles) {
+        let session = Session::open(self);
+        let result = f();
+        (result, session.close())
+    }
+
+    /// Attributes files read elsewhere, e.g. by the [daemon](crate::daemon), to every open
+    /// session.
+    pub fn record(&self, files: &TrackedFiles) {
+        for session in self.sessions.lock().expect("Tracking sessions are poisoned").values_mut() {
+            session.extend(files.files.iter().cloned());
+        }
+    }
+}
+
+impl SourceProvider for Tracking {
+    fn read_source(&self, path: &Path) -> Result<String> {
+        let source = self.base.read_source(path)?;
+        for files in self.sessions.lock().expect("Tracking sessions are poisoned").values_mut() {
+            files.insert(path.to_path_buf());
+        }
+        Ok(source)
+    }
+}
+
+/// Closes its session when dropped, so that a panicking query doesn't leave it open.
+struct Session<'a> {
+    tracking: &'a Tracking,
+    id: u64,
+}
+
+impl<'a> Session<'a> {
+    fn open(tracking: &'a Tracking) -> Self {
+        let id = tracking.next_session.fetch_add(1, Ordering::Relaxed);
+        tracking.sessions.lock().expect("Tracking sessions are poisoned").insert(id, BTreeSet::new());
+        Session { tracking, id }
+    }
+
+    fn close(self) -> TrackedFiles {
+        let files = self.tracking.sessions.lock().expect("Tracking sessions are poisoned")
+            .remove(&self.id)
+            .unwrap_or_default();
+        TrackedFiles { files }
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut sessions) = self.tracking.sessions.lock() {
+            sessions.remove(&self.id);
+        }
+    }
+}
+
+/// The files read by a query. See the [module documentation](self) for how to use them in a
+/// procedural macro.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TrackedFiles {
+    files: BTreeSet<PathBuf>,
+}
+
+impl TrackedFiles {
+    pub fn paths(&self) -> impl Iterator<Item=&Path> {
+        self.files.iter().map(PathBuf::as_path)
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Adds the files tracked by another query.
+    pub fn extend(&mut self, other: TrackedFiles) {
+        self.files.extend(other.files);
+    }
+
+    /// Tells the compiler that the current expansion depends on the tracked files. Returns the
+    /// tokens which must be added to the expansion, which are empty with the `tracked-path`
+    /// feature.
+    pub fn tracking_tokens(&self) -> TokenStream {
+        #[cfg(feature = "tracked-path")]
+        {
+            for path in self.absolute_paths() {
+                proc_macro::tracked::path(path);
+            }
+            TokenStream::new()
+        }
+        #[cfg(not(feature = "tracked-path"))]
+        {
+            self.dependency_anchors()
+        }
+    }
+
+    /// Expands to an unused constant which includes every tracked file, e.g.
+    /// `const _: &[&[u8]] = &[include_bytes!("/path/to/src/lib.rs")];`.
+    pub fn dependency_anchors(&self) -> TokenStream {
+        if self.files.is_empty() {
+            return TokenStream::new()
+        }
+        let paths = self.absolute_paths().map(|path| Literal::string(&path.to_string_lossy()));
+        quote! {
+            const _: &[&[u8]] = &[#(::core::include_bytes!(#paths)),*];
+        }
+    }
+
+    /// `include_bytes!` resolves relative paths against the invoking file, so every path is made
+    /// absolute against the current directory first.
+    fn absolute_paths(&self) -> impl Iterator<Item=PathBuf> + '_ {
+        self.files.iter().map(|path| std::path::absolute(path).unwrap_or_else(|_| path.clone()))
+    }
+}
+
+impl ToTokens for TrackedFiles {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(self.tracking_tokens());
+    }
+}
+
+impl FromIterator<PathBuf> for TrackedFiles {
+    fn from_iter<T: IntoIterator<Item=PathBuf>>(iter: T) -> Self {
+        TrackedFiles { files: iter.into_iter().collect() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use quote::quote;
+    use crate::helpers::test::assert_tokens_eq::test_commons::assert_tokens_eq;
+    use crate::source::{InMemory, SourceProvider};
+    use crate::tracking::{TrackedFiles, Tracking};
+
+    #[test]
+    fn tracking_only_records_successful_reads_inside_of_a_session() {
+        let base: InMemory = [
+            ("/example/src/lib.rs", "mod foo;"),
+            ("/example/src/foo.rs", "pub struct Foo {}"),
+        ].into_iter().collect();
+        let tracking = Tracking::new(Arc::new(base));
+
+        tracking.read_source(Path::new("/example/src/lib.rs")).unwrap();
+        let (_, files) = tracking.track(|| {
+            tracking.read_source(Path::new("/example/src/foo.rs")).unwrap();
+            assert!(tracking.read_source(Path::new("/example/src/foo/mod.rs")).is_err());
+        });
+
+        assert_eq!(files.paths().collect::<Vec<_>>(), vec![Path::new("/example/src/foo.rs")]);
+    }
+
+    #[test]
+    fn nested_sessions_share_reads() {
+        let base: InMemory = [
+            ("/example/src/lib.rs", "mod foo;"),
+            ("/example/src/foo.rs", "pub struct Foo {}"),
+        ].into_iter().collect();
+        let tracking = Tracking::new(Arc::new(base));
+
+        let (inner, outer) = tracking.track(|| {
+            tracking.read_source(Path::new("/example/src/lib.rs")).unwrap();
+            let (_, inner) = tracking.track(|| tracking.read_source(Path::new("/example/src/foo.rs")).unwrap());
+            inner
+        });
+
+        assert_eq!(inner.len(), 1);
+        assert_eq!(outer.len(), 2);
+        assert!(outer.contains(Path::new("/example/src/foo.rs")));
+    }
+
+    #[test]
+    fn recorded_files_are_added_to_open_sessions() {
+        let tracking = Tracking::new(Arc::new(InMemory::default()));
+        let files: TrackedFiles = [PathBuf::from("/example/src/lib.rs")].into_iter().collect();
+
+        tracking.record(&files);
+        let (_, tracked) = tracking.track(|| tracking.record(&files));
+
+        assert_eq!(tracked, files);
+    }
+
+    #[test]
+    fn dependency_anchors_include_every_file() {
+        let files: TrackedFiles = [
+            PathBuf::from("/example/src/lib.rs"),
+            PathBuf::from("/example/src/foo.rs"),
+        ].into_iter().collect();
+
+        assert_tokens_eq(&quote! {
+            const _: &[&[u8]] = &[
+                ::core::include_bytes!("/example/src/foo.rs"),
+                ::core::include_bytes!("/example/src/lib.rs")
+            ];
+        }, &files.dependency_anchors());
+        assert!(TrackedFiles::default().dependency_anchors().is_empty());
+    }
+}
@@ -0,0 +1,28 @@
+// `CARGO_HOME` is set for the whole process, so this is kept apart from other tests
+#[cfg(test)]
+mod tests {
+    use cargo_src_query::{QueryContext, Severity};
+
+    #[test]
+    fn diff_registry_versions_with_a_library_path() {
+        let cargo_home = std::env::temp_dir().join(format!("cargo_src_query_registry_{}", std::process::id()));
+        let registry = cargo_home.join("registry/src/index.crates.io-0000000000000000");
+        for (version, source) in [("1.0.0", "pub struct Shape;"), ("1.1.0", "pub struct Shape; pub fn area() {}")] {
+            let crate_path = registry.join(format!("shapes-{version}"));
+            std::fs::create_dir_all(crate_path.join("lib")).unwrap();
+            std::fs::write(crate_path.join("Cargo.toml"), format!(
+                "[package]\nname = \"shapes\"\nversion = \"{version}\"\nedition = \"2021\"\n\n[lib]\npath = \"lib/shapes.rs\"\n"
+            )).unwrap();
+            std::fs::write(crate_path.join("lib/shapes.rs"), source).unwrap();
+        }
+
+        std::env::set_var("CARGO_HOME", &cargo_home);
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let diff = context.diff_registry_versions("shapes", "1.0.0", "1.1.0");
+        std::fs::remove_dir_all(&cargo_home).unwrap();
+
+        let diff = diff.expect("Could not diff registry versions");
+        assert_eq!(diff.changes.iter().map(|change| change.path.join("::")).collect::<Vec<_>>(), vec!["area"]);
+        assert_eq!(diff.severity(), Severity::Minor);
+    }
+}
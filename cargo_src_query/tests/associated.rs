@@ -0,0 +1,42 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use quote::{quote, ToTokens};
+    use syn::parse2;
+    use cargo_src_query::QueryContext;
+    use crate::common::integration_test_crate;
+
+    fn associated(path: syn::Path) -> Vec<(String, String)> {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        context.get_associated_items(&integration_test_crate(), &path)
+            .expect("Could not find associated items")
+            .iter()
+            .map(|item| (format!("{} {item}", item.member.kind()), item.header.to_token_stream().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn find_associated_items_in_integration_test_crate() {
+        assert_eq!(associated(parse2(quote!(crate::nested_directory::ExampleStruct::LIMIT)).unwrap()), vec![(
+            "const crate::nested_directory::ExampleStruct::LIMIT".to_string(),
+            quote!(impl ExampleStruct { pub const LIMIT: usize = 8; }).to_string(),
+        )]);
+        assert_eq!(associated(parse2(quote!(nested_directory::ExampleTrait::Output)).unwrap()), vec![(
+            "type crate::nested_directory::ExampleTrait::Output".to_string(),
+            quote!(pub trait ExampleTrait { type Output; }).to_string(),
+        )]);
+        assert_eq!(associated(parse2(quote!(nested_directory::ExampleEnum::Second)).unwrap()), vec![(
+            "variant crate::nested_directory::ExampleEnum::Second".to_string(),
+            quote!(pub enum ExampleEnum { Second(u32) }).to_string(),
+        )]);
+    }
+
+    #[test]
+    fn report_missing_member_of_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let error = context.get_associated_items(&integration_test_crate(), &parse2(quote!(crate::nested_directory::ExampleStruct::missing)).unwrap())
+            .unwrap_err();
+        assert_eq!(error.path_segment(), Some(3));
+    }
+}
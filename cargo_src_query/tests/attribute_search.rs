@@ -0,0 +1,32 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cargo_src_query::{AttributePattern, QueryContext};
+    use crate::common::integration_test_crate;
+
+    fn search(pattern: AttributePattern) -> Vec<String> {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let crate_root = syn::Path { leading_colon: None, segments: Default::default() };
+        context.find_attributes(&integration_test_crate(), &crate_root, &pattern)
+            .expect("Could not search attributes")
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn find_std_and_non_std_derives_in_integration_test_crate() {
+        assert_eq!(search(AttributePattern::derive("Clone")), vec!["crate::ExampleStructWithStdAttributeMacros"]);
+        assert_eq!(search(AttributePattern::derive("Serialize")), vec!["crate::ExampleStructWithNonStdAttributeMacros"]);
+        assert!(search(AttributePattern::derive("Debug")).is_empty());
+    }
+
+    #[test]
+    fn searching_missing_module_of_integration_test_crate_fails() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let error = context.find_attributes(&integration_test_crate(), &syn::parse_str("missing_module").unwrap(), &AttributePattern::attribute("test"))
+            .expect_err("Module should not exist");
+        assert_eq!(error.kind(), cargo_src_query::errors::ErrorKind::ModuleNotFound);
+    }
+}
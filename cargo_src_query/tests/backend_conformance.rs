@@ -0,0 +1,115 @@
+//! Conformance tests which every [QueryBackend] must pass. Each backend must return the same
+//! items as the [SyntacticBackend], in any order.
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use proc_macro2::Span;
+    use quote::{quote, ToTokens};
+    use syn::{Ident, Item, parse2};
+    use cargo_src_query::backend::{QueryBackend, SyntacticBackend};
+    use cargo_src_query::flags::DEFAULT_FLAGS;
+    use cargo_src_query::{Definition, QueryContext};
+    use crate::common::integration_test_crate;
+
+    fn context(backend: Arc<dyn QueryBackend>) -> QueryContext {
+        let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set");
+        QueryContext::builder(std::path::Path::new(&manifest_dir).join("Cargo.toml"))
+            .backend(backend)
+            .build()
+    }
+
+    fn ident(item: &Item) -> Option<String> {
+        match item {
+            Item::Struct(s) => Some(s.ident.to_string()),
+            Item::Enum(e) => Some(e.ident.to_string()),
+            Item::Fn(f) => Some(f.sig.ident.to_string()),
+            Item::Mod(m) => Some(m.ident.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Items rendered as tokens, sorted since backends may return a module's items in any order.
+    fn rendered(items: &[Item]) -> Vec<String> {
+        let mut rendered: Vec<String> = items.iter().map(|item| item.to_token_stream().to_string()).collect();
+        rendered.sort();
+        rendered
+    }
+
+    fn assert_same_module(context: &QueryContext, reference: &QueryContext, module_path: syn::Path) {
+        let items = context.get_module(DEFAULT_FLAGS, integration_test_crate(), module_path.clone())
+            .expect("Could not find module");
+        let expected = reference.get_module(DEFAULT_FLAGS, integration_test_crate(), module_path)
+            .expect("Could not find module");
+        assert_eq!(rendered(&items), rendered(&expected));
+    }
+
+    fn assert_same_definition(context: &QueryContext, reference: &QueryContext, module_path: syn::Path, name: &str,
+                              definition_type: Definition) {
+        let name = Ident::new(name, Span::call_site());
+        let item = context.get_definition(DEFAULT_FLAGS, integration_test_crate(), module_path.clone(), name.clone(), definition_type)
+            .expect("Could not find definition");
+        let expected = reference.get_definition(DEFAULT_FLAGS, integration_test_crate(), module_path, name, definition_type)
+            .expect("Could not find definition");
+        assert_eq!(rendered(&[item]), rendered(&[expected]));
+    }
+
+    fn assert_conformance(backend: Arc<dyn QueryBackend>) {
+        let context = context(backend);
+        let reference = self::context(Arc::new(SyntacticBackend));
+
+        assert_same_module(&context, &reference, parse2(quote!(nested_directory)).unwrap());
+        assert_same_module(&context, &reference, parse2(quote!(nested_directory::another_file)).unwrap());
+        assert_same_definition(&context, &reference, parse2(quote!(nested_in_lib)).unwrap(), "public_example_function", Definition::Fn);
+        assert_same_definition(&context, &reference, parse2(quote!(nested_in_lib)).unwrap(), "GenericExampleStruct", Definition::Struct);
+        assert_same_definition(&context, &reference, parse2(quote!(nested_directory)).unwrap(), "ExampleTrait", Definition::Trait);
+
+        let module = context.get_module(DEFAULT_FLAGS, integration_test_crate(),
+                                        parse2(quote!(nested_directory::another_file)).unwrap())
+            .expect("Could not find module");
+        assert_eq!(module.iter().filter_map(ident).collect::<Vec<_>>(), vec!["ExampleStruct"]);
+
+        let definition = context.get_definition(DEFAULT_FLAGS, integration_test_crate(),
+                                                parse2(quote!(nested_in_lib)).unwrap(),
+                                                Ident::new("public_example_function", Span::call_site()),
+                                                Definition::Fn)
+            .expect("Could not find function");
+        assert!(matches!(&definition, Item::Fn(_)));
+        assert_eq!(ident(&definition).as_deref(), Some("public_example_function"));
+
+        let definition = context.get_definition(DEFAULT_FLAGS, integration_test_crate(),
+                                                parse2(quote!(nested_in_lib)).unwrap(),
+                                                Ident::new("PublicExampleStruct", Span::call_site()),
+                                                Definition::Struct)
+            .expect("Could not find struct");
+        assert!(matches!(&definition, Item::Struct(_)));
+        assert_eq!(ident(&definition).as_deref(), Some("PublicExampleStruct"));
+
+        let missing = context.get_module(DEFAULT_FLAGS, integration_test_crate(),
+                                         parse2(quote!(nested_directory::missing_module)).unwrap());
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn syntactic_backend_conforms() {
+        assert_conformance(Arc::new(SyntacticBackend));
+    }
+
+    #[cfg(feature = "rust-analyzer")]
+    #[test]
+    fn rust_analyzer_backend_conforms() {
+        assert_conformance(Arc::new(cargo_src_query::backend::RustAnalyzerBackend::default()));
+    }
+
+    #[cfg(feature = "rust-analyzer")]
+    #[test]
+    fn rust_analyzer_backend_excludes_private_items() {
+        let context = context(Arc::new(cargo_src_query::backend::RustAnalyzerBackend::default()));
+        let module = context.get_module(DEFAULT_FLAGS, integration_test_crate(), parse2(quote!(nested_directory)).unwrap())
+            .expect("Could not find module");
+        assert!(!module.iter().any(|item| matches!(item, Item::Mod(_))));
+        assert!(module.iter().any(|item| matches!(item, Item::Impl(_))));
+    }
+}
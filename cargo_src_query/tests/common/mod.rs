@@ -0,0 +1,13 @@
+//! Helpers shared by the integration tests. Each test file includes this module with `mod common;`
+//! and uses only some of the helpers, hence `dead_code` is allowed.
+#![allow(dead_code)]
+
+use proc_macro2::Span;
+use syn::Ident;
+use cargo_src_query::Crate;
+
+pub const INTEGRATION_TEST_CRATE: &str = "integration_test_crate";
+
+pub fn integration_test_crate() -> Crate {
+    Crate::External(Ident::new(INTEGRATION_TEST_CRATE, Span::call_site()))
+}
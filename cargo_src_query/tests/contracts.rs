@@ -2,12 +2,14 @@
 //! These are Contract tests [1] which specify the dependencies with Cargo, the filesystem interface,
 //! and Rust Spec that this library depends on. If any test in this file fails, then parts of
 //! this crate potentially needs to be re-architected.
+mod common;
+
 #[cfg(test)]
 mod tests {
     use cargo_metadata::{CargoOpt, MetadataCommand};
+    use crate::common::INTEGRATION_TEST_CRATE;
 
     pub const CARGO_ENV_VAR: &str = "CARGO_MANIFEST_DIR";
-    pub const INTEGRATION_TEST_CRATE: &str = "integration_test_crate";
 
     #[test]
     fn cargo_env_variable_is_correct() {
@@ -0,0 +1,131 @@
+mod common;
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use quote::quote;
+    use syn::parse2;
+    use uuid::Uuid;
+    use cargo_src_query::{Crate, QueryContext};
+    use cargo_src_query::daemon::client::DaemonClient;
+    use cargo_src_query::daemon::server::Daemon;
+    use cargo_src_query::flags::DEFAULT_FLAGS;
+    use cargo_src_query::source::InMemory;
+    use crate::common::integration_test_crate;
+
+    fn socket_path() -> PathBuf {
+        std::env::temp_dir().join(format!("cargo_src_query_test_{}.sock", Uuid::new_v4()))
+    }
+
+    fn expected_module() -> Vec<syn::Item> {
+        parse2::<syn::File>(quote!(pub struct ExampleStruct {})).unwrap().items
+    }
+
+    #[test]
+    fn query_module_through_daemon() {
+        let socket_path = socket_path();
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let daemon = Arc::new(Daemon::default());
+        let server = {
+            let daemon = daemon.clone();
+            std::thread::spawn(move || daemon.serve_listener(listener))
+        };
+
+        let context = QueryContext::from_env().unwrap();
+        let client = DaemonClient::new(&socket_path).without_autostart();
+        let module = client.get_module(&context, DEFAULT_FLAGS, integration_test_crate(),
+                                       parse2(quote!(nested_directory::another_file)).unwrap())
+            .expect("Could not find module");
+        assert_eq!(module, expected_module());
+
+        // The daemon, not the in-process fallback, read the module
+        let another_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("integration_test_crate/src/nested_directory/another_file.rs");
+        assert!(daemon.sources().invalidate(&another_file));
+
+        client.shutdown().unwrap();
+        server.join().unwrap().unwrap();
+        std::fs::remove_file(socket_path).unwrap();
+    }
+
+    #[test]
+    fn files_read_by_daemon_are_tracked_by_client() {
+        let socket_path = socket_path();
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let server = std::thread::spawn(move || Arc::new(Daemon::default()).serve_listener(listener));
+
+        let context = QueryContext::from_env().unwrap();
+        let client = DaemonClient::new(&socket_path).without_autostart();
+        let (module, files) = context.tracked(|context| client.get_module(context, DEFAULT_FLAGS, integration_test_crate(),
+                                                                          parse2(quote!(nested_directory::another_file)).unwrap()));
+        assert_eq!(module.expect("Could not find module"), expected_module());
+
+        let another_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("integration_test_crate/src/nested_directory/another_file.rs");
+        assert!(files.contains(&another_file));
+
+        client.shutdown().unwrap();
+        server.join().unwrap().unwrap();
+        std::fs::remove_file(socket_path).unwrap();
+    }
+
+    #[test]
+    fn serve_restricts_socket_to_current_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let socket_path = socket_path();
+        let server = {
+            let socket_path = socket_path.clone();
+            std::thread::spawn(move || Arc::new(Daemon::default()).serve(&socket_path))
+        };
+        let client = DaemonClient::new(&socket_path).without_autostart();
+        while !socket_path.exists() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mode = std::fs::metadata(&socket_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        client.shutdown().unwrap();
+        server.join().unwrap().unwrap();
+        assert!(!socket_path.exists());
+    }
+
+    #[test]
+    fn query_module_in_process_when_sources_are_not_on_disk() {
+        let socket_path = socket_path();
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let daemon = Arc::new(Daemon::default());
+        let server = {
+            let daemon = daemon.clone();
+            std::thread::spawn(move || daemon.serve_listener(listener))
+        };
+
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("integration_test_crate");
+        let sources: InMemory = [(manifest_dir.join("src/lib.rs"), "pub struct InMemoryStruct {}")].into_iter().collect();
+        let context = QueryContext::builder(manifest_dir.join("Cargo.toml"))
+            .source_provider(Arc::new(sources))
+            .build();
+        let client = DaemonClient::new(&socket_path).without_autostart();
+        let module = client.get_module(&context, DEFAULT_FLAGS, Crate::Internal,
+                                       syn::Path { leading_colon: None, segments: Default::default() })
+            .expect("Could not find module");
+        assert_eq!(module, parse2::<syn::File>(quote!(pub struct InMemoryStruct {})).unwrap().items);
+
+        client.shutdown().unwrap();
+        server.join().unwrap().unwrap();
+        std::fs::remove_file(socket_path).unwrap();
+    }
+
+    #[test]
+    fn query_module_in_process_when_socket_is_missing() {
+        let context = QueryContext::from_env().unwrap();
+        let client = DaemonClient::new(socket_path()).without_autostart();
+        let module = client.get_module(&context, DEFAULT_FLAGS, integration_test_crate(),
+                                       parse2(quote!(nested_directory::another_file)).unwrap())
+            .expect("Could not find module");
+        assert_eq!(module, expected_module());
+    }
+}
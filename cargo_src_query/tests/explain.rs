@@ -0,0 +1,64 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::Span;
+    use quote::quote;
+    use syn::{Ident, Item, parse2};
+    use cargo_src_query::flags::DEFAULT_FLAGS;
+    use cargo_src_query::{CandidateKind, Definition, QueryContext, Rejection};
+    use crate::common::integration_test_crate;
+
+    fn crate_root() -> syn::Path {
+        syn::Path { leading_colon: None, segments: Default::default() }
+    }
+
+    #[test]
+    fn explain_module_in_mod_rs() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let (module, trace) = context.explain_module(DEFAULT_FLAGS, integration_test_crate(),
+                                                     parse2(quote!(nested_directory::another_file)).unwrap());
+        assert!(module.is_ok());
+
+        let segments: Vec<&str> = trace.steps.iter().map(|step| step.segment.as_str()).collect();
+        assert_eq!(segments, vec!["crate root", "nested_directory", "another_file"]);
+
+        let rejections: Vec<Option<&Rejection>> = trace.steps[1].candidates.iter().map(|c| c.rejection.as_ref()).collect();
+        assert_eq!(rejections, vec![None, Some(&Rejection::Missing), None]);
+
+        let tree = trace.to_string();
+        assert!(tree.starts_with("query integration_test_crate::nested_directory::another_file\n├── crate root"));
+        assert!(tree.contains("│   ├── ✗ file ") && tree.contains("nested_directory.rs (missing)"));
+    }
+
+    #[test]
+    fn explain_definition_through_reexport() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let (definition, trace) = context.explain_definition(DEFAULT_FLAGS, integration_test_crate(),
+                                                             crate_root(),
+                                                             Ident::new("ReexportedExampleStruct", Span::call_site()),
+                                                             Definition::Struct);
+        let definition = definition.expect("Could not find definition");
+        assert!(matches!(definition, Item::Struct(s) if s.ident == "GenericExampleStruct"));
+
+        let reexport_step = trace.steps.iter().find(|step| step.segment == "ReexportedExampleStruct").unwrap();
+        assert_eq!(reexport_step.followed_reexport.as_deref(),
+                   Some("nested_in_lib::GenericExampleStruct as ReexportedExampleStruct"));
+        assert_eq!(trace.steps.last().unwrap().segment, "GenericExampleStruct");
+    }
+
+    #[test]
+    fn explain_private_use_is_not_followed() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let (definition, trace) = context.explain_definition(DEFAULT_FLAGS, integration_test_crate(),
+                                                             crate_root(),
+                                                             Ident::new("public_example_function", Span::call_site()),
+                                                             Definition::Fn);
+        assert!(definition.is_err());
+
+        let candidates = &trace.steps.last().unwrap().candidates;
+        assert!(candidates.iter().any(|c|
+            matches!(&c.kind, CandidateKind::Reexport(_)) && c.rejection == Some(Rejection::NotVisible)
+        ));
+    }
+}
@@ -1,25 +1,102 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
-    use color_eyre::owo_colors::OwoColorize;
     use proc_macro2::Span;
     use quote::quote;
-    use syn::{Ident, parse2, Path};
+    use syn::{Ident, parse2};
     use cargo_src_query::flags::{DEFAULT_FLAGS, Flags};
-    use cargo_src_query::{Crate, get_module};
-
-    pub const CARGO_ENV_VAR: &str = "CARGO_MANIFEST_DIR";
-    pub const INTEGRATION_TEST_CRATE: &str = "integration_test_crate";
+    use cargo_src_query::{Definition, QueryContext};
+    use crate::common::integration_test_crate;
 
     #[test]
     fn successfully_find_module_in_integration_test_crate() {
-        let module = get_module(DEFAULT_FLAGS, Crate::External(Ident::new(INTEGRATION_TEST_CRATE, Span::call_site().into())),
-                                parse2(quote!(nested_directory::another_file)).unwrap())
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let module = context.get_module(DEFAULT_FLAGS, integration_test_crate(),
+                                        parse2(quote!(nested_directory::another_file)).unwrap())
             .expect("Could not find module");
 
         let expected = quote!(pub struct ExampleStruct {});
         let expected: syn::File = parse2(expected).unwrap();
         let expected = expected.items;
 
-        assert_eq!(format!("{module:#?}"), format!("{expected:#?}"))
+        assert_eq!(module, expected)
+    }
+
+    #[test]
+    fn exclude_private_items_from_module_in_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let private_modules = |flags| context.get_module(flags, integration_test_crate(), parse2(quote!(nested_directory)).unwrap())
+            .expect("Could not find module")
+            .iter()
+            .filter(|item| matches!(item, syn::Item::Mod(m) if m.ident == "another_file"))
+            .count();
+
+        assert_eq!(private_modules(DEFAULT_FLAGS), 0);
+        assert_eq!(private_modules(Flags::empty()), 1);
+    }
+
+    #[test]
+    fn successfully_find_definition_in_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let definition = context.get_definition(DEFAULT_FLAGS, integration_test_crate(),
+                                                parse2(quote!(nested_in_lib)).unwrap(),
+                                                Ident::new("public_example_function", Span::call_site()),
+                                                Definition::Fn)
+            .expect("Could not find definition");
+
+        let expected: syn::Item = parse2(quote!(pub fn public_example_function() {})).unwrap();
+
+        assert_eq!(definition, expected)
+    }
+
+    #[test]
+    fn successfully_find_definition_included_from_out_dir_in_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let definition = context.get_definition(DEFAULT_FLAGS, integration_test_crate(),
+                                                parse2(quote!(nested_in_lib)).unwrap(),
+                                                Ident::new("GeneratedExampleStruct", Span::call_site()),
+                                                Definition::Struct)
+            .expect("Could not find definition generated by the build script");
+
+        let expected: syn::Item = parse2(quote!(pub struct GeneratedExampleStruct {})).unwrap();
+
+        assert_eq!(definition, expected)
+    }
+
+    #[test]
+    fn successfully_substitute_generics_of_definition_in_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let definition = context.get_definition_at_path(DEFAULT_FLAGS, integration_test_crate(),
+                                                        parse2(quote!(nested_in_lib::GenericExampleStruct<'static, String>)).unwrap(),
+                                                        Definition::Struct)
+            .expect("Could not find definition");
+
+        let expected: syn::Item = parse2(quote!(
+            pub struct GenericExampleStruct {
+                pub first: &'static String,
+                pub second: Vec<u32>,
+            }
+        )).unwrap();
+
+        assert_eq!(definition, expected)
+    }
+
+    #[test]
+    fn substitute_generics_with_a_parameter_of_the_caller() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let definition = context.get_definition_at_path(DEFAULT_FLAGS, integration_test_crate(),
+                                                        parse2(quote!(nested_in_lib::GenericExampleStruct<'static, String, T>)).unwrap(),
+                                                        Definition::Struct)
+            .expect("Could not find definition");
+
+        let expected: syn::Item = parse2(quote!(
+            pub struct GenericExampleStruct<T> {
+                pub first: &'static String,
+                pub second: Vec<T>,
+            }
+        )).unwrap();
+
+        assert_eq!(definition, expected)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,38 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cargo_src_query::QueryContext;
+    use crate::common::{INTEGRATION_TEST_CRATE, integration_test_crate};
+
+    #[test]
+    fn index_integration_test_crate() {
+        let context = QueryContext::builder(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+            .threads(2)
+            .build();
+        let index = context.index_crate(&integration_test_crate()).expect("Could not index crate");
+
+        let module_paths: Vec<String> = index.modules.iter().map(|m| m.display_path()).collect();
+        assert_eq!(module_paths, vec![
+            "crate",
+            "crate::nested_directory",
+            "crate::nested_directory::another_file",
+            "crate::nested_directory::constructors",
+            "crate::nested_in_lib",
+            "crate::nested_in_lib::deeply_nested",
+        ]);
+        assert!(index.failures.is_empty());
+    }
+
+    #[test]
+    fn index_dependency_closure_of_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let indexes = context.index_dependency_closure(&integration_test_crate())
+            .expect("Could not index dependency closure");
+
+        let package_names: Vec<&str> = indexes.keys().map(|id| context.metadata().unwrap()[id].name.as_str()).collect();
+        assert!(package_names.contains(&INTEGRATION_TEST_CRATE));
+        assert!(package_names.contains(&"serde"));
+        assert!(indexes.values().all(Result::is_ok));
+    }
+}
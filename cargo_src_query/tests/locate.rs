@@ -0,0 +1,33 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cargo_src_query::QueryContext;
+    use crate::common::integration_test_crate;
+
+    #[test]
+    fn locate_file_in_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let lookup = context.locate(&integration_test_crate(), &"src/nested_directory/another_file.rs".parse().unwrap())
+            .expect("Could not locate file");
+
+        assert_eq!(lookup.to_string(), "crate::nested_directory::another_file");
+    }
+
+    #[test]
+    #[cfg(feature = "span-locations")]
+    fn locate_item_in_integration_test_crate() {
+        use cargo_src_query::SourceLocation;
+
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let crate_path = context.crate_path(&integration_test_crate()).unwrap();
+        let source = std::fs::read_to_string(crate_path.join("src/lib.rs")).unwrap();
+        let line = source.lines().position(|line| line.contains("pub fn public_example_function")).unwrap() + 1;
+
+        let lookup = context.locate(&integration_test_crate(), &SourceLocation::new("src/lib.rs", Some(line), None))
+            .expect("Could not locate item");
+
+        assert_eq!(lookup.to_string(), "crate::nested_in_lib::public_example_function");
+        assert_eq!(lookup.item.unwrap().kind, "fn");
+    }
+}
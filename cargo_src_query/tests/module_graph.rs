@@ -0,0 +1,20 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cargo_src_query::{GraphFormat, GraphOptions, QueryContext};
+    use crate::common::integration_test_crate;
+
+    #[test]
+    fn module_graph_of_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let graph = context.module_graph(&integration_test_crate(), GraphFormat::Mermaid, GraphOptions { items: true, reexports: true })
+            .expect("Could not render module graph");
+
+        assert!(graph.contains("[\"integration_test_crate<br/>crate root · lib.rs\"]"));
+        assert!(graph.contains("[\"nested_directory<br/>nested_directory/mod.rs · private\"]"));
+        assert!(graph.contains("[\"another_file<br/>another_file.rs · "));
+        assert!(graph.contains("[\"deeply_nested<br/>inline · private\"]"));
+        assert!(graph.contains(" -. \"pub use nested_in_lib::GenericExampleStruct as ReexportedExampleStruct\" .-> "));
+    }
+}
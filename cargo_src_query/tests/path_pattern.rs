@@ -0,0 +1,31 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cargo_src_query::{PathPattern, QueryContext};
+    use crate::common::integration_test_crate;
+
+    fn find(pattern: &str) -> Vec<String> {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let pattern: PathPattern = pattern.parse().expect("Could not parse pattern");
+        context.find_paths(&integration_test_crate(), &pattern)
+            .expect("Could not find paths")
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn find_structs_across_module_tree_of_integration_test_crate() {
+        assert_eq!(find("struct crate::**::PublicExampleStruct"), vec![
+            "crate::PublicExampleStruct",
+            "crate::nested_in_lib::PublicExampleStruct",
+            "crate::nested_in_lib::deeply_nested::PublicExampleStruct",
+        ]);
+        assert_eq!(find("crate::nested_in_lib::{Generic,Private}*"), vec![
+            "crate::nested_in_lib::GenericExampleStruct",
+            "crate::nested_in_lib::PrivateExampleStruct",
+        ]);
+        assert_eq!(find("fn **"), vec!["crate::nested_in_lib::public_example_function"]);
+    }
+}
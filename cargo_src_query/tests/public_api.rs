@@ -0,0 +1,19 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cargo_src_query::QueryContext;
+    use crate::common::integration_test_crate;
+
+    #[test]
+    fn public_api_of_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let api = context.public_api(&integration_test_crate()).expect("Could not list public API");
+        let listing: Vec<String> = api.iter().map(|item| format!("{}: {}", item.path.join("::"), item.signature)).collect();
+
+        assert!(listing.contains(&"ExampleStructWithStdAttributeMacros: #[derive(Clone, Copy)] pub struct ExampleStructWithStdAttributeMacros { }".to_string()));
+        assert!(listing.contains(&"nested_in_lib::public_example_function: pub fn public_example_function ()".to_string()));
+        assert!(listing.iter().any(|item| item.starts_with("ReexportedExampleStruct: pub struct GenericExampleStruct")));
+        assert!(!listing.iter().any(|item| item.contains("PrivateExampleStruct") || item.contains("deeply_nested")));
+    }
+}
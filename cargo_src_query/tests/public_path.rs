@@ -0,0 +1,37 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::Span;
+    use quote::{quote, ToTokens};
+    use syn::Ident;
+    use cargo_src_query::QueryContext;
+    use cargo_src_query::errors::ErrorKind;
+    use crate::common::integration_test_crate;
+
+    fn ident(name: &str) -> Ident {
+        Ident::new(name, Span::call_site())
+    }
+
+    #[test]
+    fn canonical_public_path_prefers_reexport() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let paths = context.public_paths(&integration_test_crate(), &syn::parse2(quote!(nested_in_lib)).unwrap(), &ident("GenericExampleStruct"))
+            .expect("Could not find public paths");
+
+        let paths: Vec<String> = paths.iter().map(|path| path.to_token_stream().to_string().replace(' ', "")).collect();
+        assert_eq!(paths, vec![
+            "integration_test_crate::ReexportedExampleStruct",
+            "integration_test_crate::nested_in_lib::GenericExampleStruct",
+        ]);
+    }
+
+    #[test]
+    fn private_module_is_not_publicly_reachable() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let error = context.canonical_public_path(&integration_test_crate(), &syn::parse2(quote!(nested_in_lib::deeply_nested)).unwrap(), &ident("PublicExampleStruct"))
+            .expect_err("Private module should not be publicly reachable");
+
+        assert_eq!(error.kind(), ErrorKind::NotPubliclyReachable);
+    }
+}
@@ -0,0 +1,38 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+    use syn::parse2;
+    use cargo_src_query::{QueryContext, ReferenceScope};
+    use crate::common::integration_test_crate;
+
+    fn references(path: syn::Path) -> Vec<String> {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        context.find_references(&integration_test_crate(), path, ReferenceScope::Crate)
+            .expect("Could not find references")
+            .iter()
+            .map(|reference| format!("{} {reference} {} line {}", reference.kind, reference.usage, reference.line.unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn find_references_in_integration_test_crate() {
+        assert_eq!(references(parse2(quote!(crate::nested_directory::ExampleStruct)).unwrap()), vec![
+            "impl integration_test_crate::nested_directory::ExampleStruct impl line 16",
+            "impl integration_test_crate::nested_directory::ExampleStruct impl line 20",
+            "impl integration_test_crate::nested_directory::ExampleStruct impl line 29",
+        ]);
+        assert_eq!(references(parse2(quote!(nested_in_lib::GenericExampleStruct)).unwrap()), vec![
+            "type integration_test_crate::nested_directory::ExampleAlias type line 26",
+        ]);
+    }
+
+    #[test]
+    fn report_missing_definition_of_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let error = context.find_references(&integration_test_crate(), parse2(quote!(crate::nested_directory::Missing)).unwrap(), ReferenceScope::Crate)
+            .unwrap_err();
+        assert_eq!(error.path_segment(), Some(2));
+    }
+}
@@ -0,0 +1,40 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+    use syn::parse2;
+    use cargo_src_query::errors::ErrorKind;
+    use cargo_src_query::flags::DEFAULT_FLAGS;
+    use cargo_src_query::reproducer::replay;
+    use cargo_src_query::QueryContext;
+    use crate::common::integration_test_crate;
+
+    #[test]
+    fn failed_query_writes_replayable_reproducer() {
+        let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set");
+        let context = QueryContext::builder(std::path::Path::new(&manifest_dir).join("Cargo.toml"))
+            .reproducer(true)
+            .build();
+
+        let error = context.get_module(DEFAULT_FLAGS, integration_test_crate(),
+                                       parse2(quote!(nested_directory::missing_module)).unwrap())
+            .expect_err("Query should fail");
+        let bundle = error.indexed_messages().get("reproducer").and_then(|b| b.first())
+            .expect("Reproducer was not written");
+        let bundle = std::path::PathBuf::from(bundle);
+
+        for file in ["README.md", "query.json", "metadata.json", "trace.log"] {
+            assert!(bundle.join(file).is_file(), "{file} is missing from the reproducer");
+        }
+        let query = std::fs::read_to_string(bundle.join("query.json")).unwrap();
+        assert!(!query.contains(&manifest_dir), "local paths should be redacted");
+        assert!(bundle.join("sources/workspace/integration_test_crate/src/lib.rs").is_file());
+
+        let replay = replay(&bundle).expect("Could not replay reproducer");
+        assert_eq!(replay.expected.kind, ErrorKind::ModuleNotFound);
+        assert!(replay.reproduced());
+
+        std::fs::remove_dir_all(&bundle).unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use proc_macro2::Span;
+    use quote::{quote, ToTokens};
+    use syn::{Ident, parse2};
+    use cargo_src_query::flags::DEFAULT_FLAGS;
+    use cargo_src_query::{Definition, QueryContext};
+    use crate::common::integration_test_crate;
+
+    #[test]
+    fn tracked_query_returns_files_read_including_build_script_outputs() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let (definition, files) = context.tracked(|context| context.get_definition(
+            DEFAULT_FLAGS, integration_test_crate(),
+            parse2(quote!(nested_in_lib)).unwrap(),
+            Ident::new("GeneratedExampleStruct", Span::call_site()),
+            Definition::Struct,
+        ));
+        definition.expect("Could not find definition generated by the build script");
+
+        let crate_path = context.crate_path(&integration_test_crate()).unwrap();
+        assert!(files.contains(&crate_path.join("src").join("lib.rs")));
+        assert!(files.paths().any(|path| path.ends_with(Path::new("out").join("generated.rs"))));
+        assert!(!files.paths().any(|path| path.ends_with("another_file.rs")));
+
+        let anchors = files.to_token_stream().to_string();
+        assert!(anchors.starts_with("const _ : & [& [u8]]"));
+        assert_eq!(anchors.matches("include_bytes").count(), files.len());
+    }
+
+    #[test]
+    fn queries_outside_of_tracked_are_not_tracked() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        context.get_module(DEFAULT_FLAGS, integration_test_crate(), parse2(quote!(nested_in_lib)).unwrap())
+            .expect("Could not find module");
+
+        let (_, files) = context.tracked(|_| ());
+        assert!(files.is_empty());
+    }
+}
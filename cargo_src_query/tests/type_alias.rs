@@ -0,0 +1,52 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use quote::{quote, ToTokens};
+    use syn::parse2;
+    use cargo_src_query::{Definition, QueryContext};
+    use cargo_src_query::flags::Flags;
+    use crate::common::integration_test_crate;
+
+    #[test]
+    fn follow_type_alias_through_reexport_of_integration_test_crate() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let resolution = context.resolve_type_aliases(&integration_test_crate(), parse2(quote!(nested_directory::ExampleAlias<String>)).unwrap())
+            .expect("Could not follow type alias");
+
+        assert_eq!(resolution.path, "integration_test_crate::nested_in_lib::GenericExampleStruct");
+        assert_eq!(resolution.aliases.len(), 1);
+        assert_eq!(resolution.aliases[0].path, "integration_test_crate::nested_directory::ExampleAlias");
+        assert_eq!(resolution.definition.to_token_stream().to_string(), quote!(
+            pub struct GenericExampleStruct {
+                pub first: &'static String,
+                pub second: Vec<u32>,
+            }
+        ).to_string());
+    }
+
+    #[test]
+    fn follow_type_alias_used_with_a_parameter_of_the_caller() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let resolution = context.resolve_type_aliases(&integration_test_crate(), parse2(quote!(nested_directory::ExampleAlias<T>)).unwrap())
+            .expect("Could not follow type alias");
+
+        assert_eq!(resolution.definition.to_token_stream().to_string(), quote!(
+            pub struct GenericExampleStruct<T> {
+                pub first: &'static T,
+                pub second: Vec<u32>,
+            }
+        ).to_string());
+    }
+
+    #[test]
+    fn get_definition_at_path_follows_type_aliases_when_flagged() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let path = || parse2(quote!(crate::nested_directory::ExampleAlias<u8>)).unwrap();
+
+        let definition = context.get_definition_at_path(Flags::FOLLOW_TYPE_ALIASES, integration_test_crate(), path(), Definition::Struct)
+            .expect("Could not follow type alias");
+        assert!(definition.to_token_stream().to_string().starts_with("pub struct GenericExampleStruct"));
+        assert!(context.get_definition_at_path(Flags::empty(), integration_test_crate(), path(), Definition::Struct).is_err());
+    }
+}
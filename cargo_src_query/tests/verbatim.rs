@@ -0,0 +1,46 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::Span;
+    use quote::quote;
+    use syn::{Ident, parse2};
+    use cargo_src_query::flags::DEFAULT_FLAGS;
+    use cargo_src_query::{Definition, QueryContext};
+    use crate::common::integration_test_crate;
+
+    #[test]
+    fn definition_keeps_comments_and_formatting() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let definition = context.get_definition_verbatim(DEFAULT_FLAGS, integration_test_crate(),
+                                                         parse2(quote!(nested_in_lib)).unwrap(),
+                                                         Ident::new("GenericExampleStruct", Span::call_site()),
+                                                         Definition::Struct)
+            .expect("Could not find definition");
+
+        assert!(definition.file_path.ends_with("integration_test_crate/src/lib.rs"));
+        assert_eq!(definition.text, [
+            "pub struct GenericExampleStruct<'a, A, B = u32> {",
+            "        pub first: &'a A,",
+            "        // Regular comments are only kept in verbatim source text",
+            "        pub second: Vec<B>,",
+            "    }",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn module_items_include_build_script_outputs() {
+        let context = QueryContext::from_env().expect("Could not create query context");
+        let items = context.get_module_verbatim(DEFAULT_FLAGS, integration_test_crate(), parse2(quote!(nested_in_lib)).unwrap())
+            .expect("Could not find module");
+
+        let nested = items.iter().find(|item| item.text.contains("PublicExampleStruct"))
+            .expect("Nested struct is missing");
+        assert_eq!(nested.line, 18);
+
+        let generated = items.iter().find(|item| item.text.contains("GeneratedExampleStruct"))
+            .expect("Generated struct is missing");
+        assert!(generated.file_path.ends_with("out/generated.rs"));
+        assert_eq!(generated.text, "pub struct GeneratedExampleStruct {}");
+    }
+}